@@ -0,0 +1,153 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Which disks and partitions the running live system is using
+//!
+//! A disk is busy while any of its partitions is mounted, an active swap,
+//! held by another block device (an LVM PV, a dm-crypt or md member) or the
+//! device the live medium booted from. Wiping one pulls the ground out from
+//! under the session, so the provisioner refuses them.
+
+use disks::BlockDevice;
+use std::{fs, path::Path};
+
+/// A snapshot of what the live system has in use
+#[derive(Debug, Default)]
+pub struct Usage {
+    /// Mounted device nodes, with where they are mounted
+    mounts: Vec<(String, String)>,
+    /// Active swap device nodes
+    swaps: Vec<String>,
+    /// The device the live medium booted from
+    live: Option<String>,
+}
+
+impl Usage {
+    /// Read the current mounts, swaps and live boot device
+    pub fn read() -> Self {
+        let read = |path| fs::read_to_string(path).unwrap_or_default();
+        let mut usage = Self::parse(&read("/proc/self/mounts"), &read("/proc/swaps"), &read("/proc/cmdline"));
+
+        // Sources may be udev symlinks, such as /dev/disk/by-label/..
+        for (device, _) in &mut usage.mounts {
+            *device = canonical(device);
+        }
+        for device in &mut usage.swaps {
+            *device = canonical(device);
+        }
+        usage.live = usage.live.as_deref().map(canonical);
+
+        usage
+    }
+
+    fn parse(mounts: &str, swaps: &str, cmdline: &str) -> Self {
+        Self {
+            mounts: mounts
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.split_whitespace();
+                    let (source, target) = (fields.next()?, fields.next()?);
+                    source
+                        .starts_with("/dev/")
+                        .then(|| (source.to_owned(), target.replace("\\040", " ")))
+                })
+                .collect(),
+            swaps: swaps
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split_whitespace().next())
+                .filter(|source| source.starts_with("/dev/"))
+                .map(str::to_owned)
+                .collect(),
+            live: cmdline
+                .split_whitespace()
+                .find_map(|arg| arg.strip_prefix("root=live:"))
+                .and_then(live_source),
+        }
+    }
+
+    /// Why a whole disk is busy, naming the partition responsible
+    pub fn disk(&self, device: &BlockDevice) -> Option<String> {
+        self.node(device.name(), &device.device().to_string_lossy())
+            .or_else(|| {
+                device.partitions().iter().find_map(|partition| {
+                    let node = partition.device.to_string_lossy();
+                    self.node(&partition.name, &node)
+                        .map(|reason| format!("{node} is {reason}"))
+                })
+            })
+    }
+
+    /// Why a single block device is busy, if it is. `name` is its sysfs name.
+    pub fn node(&self, name: &str, device: &str) -> Option<String> {
+        if self.live.as_deref() == Some(device) {
+            return Some("the live boot device".to_owned());
+        }
+        if let Some((_, target)) = self.mounts.iter().find(|(source, _)| source == device) {
+            return Some(format!("mounted at {target}"));
+        }
+        if self.swaps.iter().any(|swap| swap == device) {
+            return Some("used as swap".to_owned());
+        }
+
+        let holders = fs::read_dir(Path::new("/sys/class/block").join(name).join("holders")).ok()?;
+        let holder = holders.flatten().next()?;
+        Some(format!("held by {}", holder.file_name().to_string_lossy()))
+    }
+}
+
+/// The device node a dracut `root=live:` argument names
+fn live_source(source: &str) -> Option<String> {
+    let (kind, value) = match source.split_once('=') {
+        Some(("CDLABEL" | "LABEL", value)) => ("by-label", value),
+        Some(("UUID", value)) => ("by-uuid", value),
+        Some(("PARTUUID", value)) => ("by-partuuid", value),
+        Some(("PARTLABEL", value)) => ("by-partlabel", value),
+        Some(_) => return None,
+        None => return source.starts_with("/dev/").then(|| source.to_owned()),
+    };
+    Some(format!("/dev/disk/{kind}/{value}"))
+}
+
+/// A device path with symlinks resolved, or as given when it cannot be
+fn canonical(device: &str) -> String {
+    fs::canonicalize(device)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| device.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mounts_swaps_and_live_device() {
+        let mounts = "\
+/dev/sr0 /run/initramfs/live iso9660 ro,relatime 0 0
+overlay / overlay rw 0 0
+/dev/sdb1 /media/usb\\040stick vfat rw 0 0
+";
+        let swaps = "\
+Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority
+/dev/sda3                               partition\t8388604\t\t0\t\t-2
+/dev/zram0                              partition\t4194300\t\t0\t\t100
+";
+        let usage = Usage::parse(
+            mounts,
+            swaps,
+            "BOOT_IMAGE=/boot/vmlinuz root=live:CDLABEL=AERYNOS rd.live.image",
+        );
+
+        assert_eq!(
+            usage.node("sr0", "/dev/sr0").as_deref(),
+            Some("mounted at /run/initramfs/live")
+        );
+        assert_eq!(
+            usage.node("sdb1", "/dev/sdb1").as_deref(),
+            Some("mounted at /media/usb stick")
+        );
+        assert_eq!(usage.node("sda3", "/dev/sda3").as_deref(), Some("used as swap"));
+        assert_eq!(usage.live.as_deref(), Some("/dev/disk/by-label/AERYNOS"));
+    }
+}
@@ -3,28 +3,50 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+};
 
 use disks::BlockDevice;
 use lichen_macros::authorized;
-use protocols::lichen::storage::disks::{ListDisksRequest, ListDisksResponse, disks_server};
-use tonic::{Request, Response};
+use protocols::lichen::storage::disks::{
+    AttachImageRequest, AttachImageResponse, DetachImageRequest, Disk, DiskEvent, DiskHealth, DiskKind,
+    ListDisksRequest, ListDisksResponse, WatchDisksRequest, disks_server,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::info;
 
-use crate::auth::AuthService;
+use crate::{auth::AuthService, busy::Usage, simulate};
+
+mod identify;
+mod image;
+mod watch;
 
 /// Service represents the disk management service implementation
 #[derive(Debug)]
 pub struct Service {
     auth: Arc<AuthService>,
+    /// Images attached by AttachImage, by loop device
+    images: Mutex<HashMap<String, image::Attached>>,
 }
 
 /// Creates a new Disks gRPC server instance using the default Service implementation
 pub fn service(auth: Arc<AuthService>) -> disks_server::DisksServer<Service> {
-    disks_server::DisksServer::new(Service { auth })
+    disks_server::DisksServer::new(Service {
+        auth,
+        images: Mutex::default(),
+    })
 }
 
 #[tonic::async_trait]
 impl disks_server::Disks for Service {
+    type WatchDisksStream = ReceiverStream<Result<DiskEvent, Status>>;
+
     /// Lists all available disk devices and their partitions
     ///
     /// # Parameters
@@ -38,23 +60,159 @@ impl disks_server::Disks for Service {
         &self,
         request: Request<ListDisksRequest>,
     ) -> Result<Response<ListDisksResponse>, tonic::Status> {
-        // Discover all block devices on the system
-        let devices = BlockDevice::discover()?;
-
-        // Filter and transform block devices into disk information
-        let disks = devices
-            .iter()
-            .filter(|device| {
-                if request.get_ref().exclude_loopback {
-                    !matches!(device, BlockDevice::Loopback(_))
-                } else {
-                    true
-                }
-            })
-            .map(Into::into)
-            .collect();
+        let disks = tokio::task::block_in_place(|| {
+            let mut disks = describe(request.get_ref().exclude_loopback)?;
+            disks.iter_mut().for_each(assess);
+            Ok::<_, Status>(disks)
+        })?;
 
         let response = ListDisksResponse { disks };
         Ok(Response::new(response))
     }
+
+    #[authorized("com.aerynos.lichen.disks.list")]
+    async fn watch_disks(
+        &self,
+        request: Request<WatchDisksRequest>,
+    ) -> Result<Response<Self::WatchDisksStream>, tonic::Status> {
+        let request = request.into_inner();
+        let (tx, rx) = mpsc::channel(16);
+        let source = watch::SystemDisks::new(request.exclude_loopback);
+
+        thread::spawn(move || watch::watch(source, request.known, tx));
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    #[authorized("com.aerynos.lichen.disks.image")]
+    async fn attach_image(
+        &self,
+        request: Request<AttachImageRequest>,
+    ) -> Result<Response<AttachImageResponse>, tonic::Status> {
+        let request = request.into_inner();
+        info!(path = request.path, size = request.size, "Attaching new image file");
+
+        // The mock disks stand in for images: nothing may be attached
+        if simulate::active() {
+            return Err(Status::failed_precondition(
+                "images cannot be attached while simulating",
+            ));
+        }
+
+        let (device, attached) =
+            tokio::task::block_in_place(|| image::attach(&request.path, request.size, request.format()))?;
+        self.images.lock().unwrap().insert(device.clone(), attached);
+
+        Ok(Response::new(AttachImageResponse { device }))
+    }
+
+    #[authorized("com.aerynos.lichen.disks.image")]
+    async fn detach_image(&self, request: Request<DetachImageRequest>) -> Result<Response<()>, tonic::Status> {
+        let device = request.into_inner().device;
+
+        // Only images attached here, and still backed by their file: a stale
+        // device node may since have been reused for another loop disk
+        let attached = self
+            .images
+            .lock()
+            .unwrap()
+            .remove(&device)
+            .ok_or_else(|| Status::not_found(format!("no image is attached as {device}")))?;
+        if !image::is_backed_by(&device, &attached) {
+            return Err(Status::failed_precondition(format!(
+                "{device} is no longer backed by the attached image"
+            )));
+        }
+
+        // Keep the image on failure, so the detach can be retried rather than
+        // leaking the loop device
+        if let Err(status) = tokio::task::block_in_place(|| image::detach(&device, &attached)) {
+            self.images.lock().unwrap().insert(device, attached);
+            return Err(status);
+        }
+
+        Ok(Response::new(()))
+    }
+}
+
+/// Every disk with its partitions, identifiers and busy state, without the
+/// slow health assessment
+fn describe(exclude_loopback: bool) -> Result<Vec<Disk>, Status> {
+    // Discover all block devices on the system
+    let devices = simulate::block_devices()?;
+    let usage = Usage::read();
+
+    // Filter and transform block devices into disk information
+    Ok(devices
+        .iter()
+        .filter(|device| !exclude_loopback || !matches!(device, BlockDevice::Loopback(_)))
+        .map(|device| {
+            let mut disk: Disk = device.into();
+            identify::identify(&mut disk);
+            disk.busy = usage.disk(device);
+            for partition in &mut disk.partitions {
+                partition.busy = usage.node(&partition.name, &partition.device);
+            }
+            disk
+        })
+        .collect())
+}
+
+/// Fill in the disk's health, which takes a smartctl run per disk
+fn assess(disk: &mut Disk) {
+    if disk.kind() != DiskKind::Loopback {
+        let (health, detail) = smart_health(&disk.device);
+        disk.health = health as i32;
+        disk.health_detail = detail;
+    }
+}
+
+/// Ask smartctl for the disk's health self-assessment, with its verdict line
+fn smart_health(device: &str) -> (DiskHealth, Option<String>) {
+    let Ok(output) = Command::new("smartctl").args(["--health", device]).output() else {
+        return (DiskHealth::Unknown, None);
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let detail = stdout
+        .lines()
+        .find(|line| line.contains("overall-health") || line.starts_with("SMART Health Status"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, verdict)| verdict.trim().to_owned());
+
+    (output.status.code().map_or(DiskHealth::Unknown, classify), detail)
+}
+
+/// Classify smartctl's exit status, a bitmask of what it found
+fn classify(status: i32) -> DiskHealth {
+    // Bits 0-2: the command line, the device or the SMART command failed
+    const NOT_ASSESSED: i32 = 0b0000_0111;
+    // Bits 3-4: the disk reports failing, or prefail attributes are at threshold
+    const FAILING: i32 = 0b0001_1000;
+    // Bits 5-7: usage attributes crossed thresholds before, or errors were logged
+    const LOGGED: i32 = 0b1110_0000;
+
+    if status & NOT_ASSESSED != 0 {
+        DiskHealth::Unknown
+    } else if status & FAILING != 0 {
+        DiskHealth::Failing
+    } else if status & LOGGED != 0 {
+        DiskHealth::Warning
+    } else {
+        DiskHealth::Passed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_smartctl_exit_status() {
+        assert_eq!(classify(0), DiskHealth::Passed);
+        assert_eq!(classify(2), DiskHealth::Unknown);
+        assert_eq!(classify(8), DiskHealth::Failing);
+        assert_eq!(classify(8 | 64), DiskHealth::Failing);
+        assert_eq!(classify(64), DiskHealth::Warning);
+    }
 }
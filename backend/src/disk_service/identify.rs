@@ -0,0 +1,164 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Identifying disks beyond what their block devices describe
+//!
+//! Stable identifiers come from the udev database, and media attributes
+//! from sysfs, so a disk can be recognised again across boots and its
+//! hardware told apart.
+
+use protocols::lichen::storage::disks::{Disk, DiskKind};
+use std::fs;
+
+/// Fill in the disk's stable identifiers, transport and media attributes
+pub fn identify(disk: &mut Disk) {
+    let ids = Identifiers::read(&disk.name);
+    let attributes = Attributes::read(&disk.name);
+
+    disk.transport = transport(ids.bus.as_deref(), disk.kind());
+    disk.by_id = ids.by_id;
+    disk.wwn = ids.wwn;
+    disk.serial = ids.serial;
+    disk.by_path = ids.by_path;
+    disk.partition_table = ids.partition_table;
+    disk.rotational = attributes.rotational;
+    disk.removable = attributes.removable;
+    disk.logical_sector_size = attributes.logical_sector_size;
+    disk.physical_sector_size = attributes.physical_sector_size;
+}
+
+/// Stable identifiers and properties udev recorded for a block device
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Identifiers {
+    by_id: Vec<String>,
+    wwn: Option<String>,
+    serial: Option<String>,
+    by_path: Option<String>,
+    bus: Option<String>,
+    partition_table: Option<String>,
+}
+
+impl Identifiers {
+    /// Read the udev database entry for the named block device. Devices udev
+    /// has not processed simply have no identifiers.
+    fn read(name: &str) -> Self {
+        fs::read_to_string(format!("/sys/class/block/{name}/dev"))
+            .and_then(|dev| fs::read_to_string(format!("/run/udev/data/b{}", dev.trim())))
+            .map(|data| Self::parse(&data))
+            .unwrap_or_default()
+    }
+
+    /// Parse a udev database entry: `S:` lines are symlinks relative to /dev,
+    /// `E:` lines are device properties
+    fn parse(data: &str) -> Self {
+        let mut ids = Self::default();
+        let mut serial = None;
+        let mut wwn = None;
+
+        for line in data.lines() {
+            if let Some(link) = line.strip_prefix("S:") {
+                let path = format!("/dev/{link}");
+                if link.starts_with("disk/by-id/") {
+                    ids.by_id.push(path);
+                } else if link.starts_with("disk/by-path/") && ids.by_path.is_none() {
+                    ids.by_path = Some(path);
+                }
+            } else if let Some((key, value)) = line.strip_prefix("E:").and_then(|prop| prop.split_once('=')) {
+                match key {
+                    "ID_SERIAL_SHORT" => ids.serial = Some(value.to_owned()),
+                    "ID_SERIAL" => serial = Some(value.to_owned()),
+                    "ID_WWN_WITH_EXTENSION" => ids.wwn = Some(value.to_owned()),
+                    "ID_WWN" => wwn = Some(value.to_owned()),
+                    "ID_BUS" => ids.bus = Some(value.to_owned()),
+                    "ID_PART_TABLE_TYPE" => ids.partition_table = Some(value.to_owned()),
+                    _ => {}
+                }
+            }
+        }
+
+        ids.serial = ids.serial.or(serial);
+        ids.wwn = ids.wwn.or(wwn);
+        ids.by_id.sort();
+        ids
+    }
+}
+
+/// Queue and media attributes the kernel reports in sysfs
+#[derive(Debug, Default)]
+struct Attributes {
+    rotational: bool,
+    removable: bool,
+    logical_sector_size: u32,
+    physical_sector_size: u32,
+}
+
+impl Attributes {
+    /// Read the sysfs attributes of the named block device; anything missing
+    /// reads as false or zero
+    fn read(name: &str) -> Self {
+        let read = |attribute: &str| -> u32 {
+            fs::read_to_string(format!("/sys/class/block/{name}/{attribute}"))
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or_default()
+        };
+
+        Self {
+            rotational: read("queue/rotational") == 1,
+            removable: read("removable") == 1,
+            logical_sector_size: read("queue/logical_block_size"),
+            physical_sector_size: read("queue/physical_block_size"),
+        }
+    }
+}
+
+/// The transport a disk is attached by: udev's bus, named as lsblk does,
+/// or the kind of disk when udev has none for it
+fn transport(bus: Option<&str>, kind: DiskKind) -> Option<String> {
+    match (bus, kind) {
+        (Some("ata"), _) => Some("sata".to_owned()),
+        (Some(bus), _) => Some(bus.to_owned()),
+        (None, DiskKind::Nvme) => Some("nvme".to_owned()),
+        (None, DiskKind::Mmc) => Some("mmc".to_owned()),
+        (None, DiskKind::Virtual) => Some("virtio".to_owned()),
+        (None, _) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_udev_identifiers() {
+        let data = "\
+S:disk/by-path/pci-0000:00:17.0-ata-1
+S:disk/by-id/wwn-0x5002538e40a1b2c3
+S:disk/by-path/pci-0000:00:17.0-ata-1.0
+S:disk/by-id/ata-Samsung_SSD_860_S3Z9NB0K123456
+E:ID_SERIAL=Samsung_SSD_860_S3Z9NB0K123456
+E:ID_SERIAL_SHORT=S3Z9NB0K123456
+E:ID_WWN=0x5002538e40a1b2c3
+E:ID_BUS=ata
+E:ID_PART_TABLE_TYPE=gpt
+G:systemd
+";
+
+        assert_eq!(
+            Identifiers::parse(data),
+            Identifiers {
+                by_id: vec![
+                    "/dev/disk/by-id/ata-Samsung_SSD_860_S3Z9NB0K123456".to_owned(),
+                    "/dev/disk/by-id/wwn-0x5002538e40a1b2c3".to_owned(),
+                ],
+                wwn: Some("0x5002538e40a1b2c3".to_owned()),
+                serial: Some("S3Z9NB0K123456".to_owned()),
+                by_path: Some("/dev/disk/by-path/pci-0000:00:17.0-ata-1".to_owned()),
+                bus: Some("ata".to_owned()),
+                partition_table: Some("gpt".to_owned()),
+            }
+        );
+        assert_eq!(Identifiers::parse("E:DEVTYPE=disk\n"), Identifiers::default());
+    }
+}
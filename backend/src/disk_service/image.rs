@@ -0,0 +1,131 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Image files as install targets
+//!
+//! An image is a sparse raw file attached as a loopback disk with partition
+//! scanning, so strategies and the install treat it as any other disk. A
+//! qcow2 image is installed to as raw and converted once it is detached, as
+//! loop devices only take raw files.
+
+use protocols::lichen::storage::disks::ImageFormat;
+use std::{
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tonic::Status;
+use tracing::info;
+
+/// An image file attached as a loopback disk
+#[derive(Debug)]
+pub struct Attached {
+    /// Where the finished image goes
+    output: PathBuf,
+    /// The raw file the loop device is backed by
+    raw: PathBuf,
+    format: ImageFormat,
+}
+
+/// Create a sparse image of `size` bytes at `path` and attach it, returning
+/// the loop device
+pub fn attach(path: &str, size: u64, format: ImageFormat) -> Result<(String, Attached), Status> {
+    let output = PathBuf::from(path);
+    if !output.is_absolute() {
+        return Err(Status::invalid_argument(format!("image path must be absolute: {path}")));
+    }
+    if size == 0 {
+        return Err(Status::invalid_argument("image size must not be zero"));
+    }
+
+    let raw = match format {
+        ImageFormat::Raw => output.clone(),
+        ImageFormat::Qcow2 => PathBuf::from(format!("{path}.raw")),
+    };
+    for file in [&output, &raw] {
+        if file.exists() {
+            return Err(Status::already_exists(format!("{} already exists", file.display())));
+        }
+    }
+
+    // set_len on a new file allocates nothing until the install writes
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&raw)?
+        .set_len(size)?;
+
+    // sysfs reports the loop device's backing file by its canonical path,
+    // which is what it is checked against before detaching
+    let raw = match fs::canonicalize(&raw) {
+        Ok(raw) => raw,
+        Err(e) => {
+            let _ = fs::remove_file(&raw);
+            return Err(e.into());
+        }
+    };
+    let output = match format {
+        ImageFormat::Raw => raw.clone(),
+        ImageFormat::Qcow2 => raw.with_extension(""),
+    };
+
+    let device = match output_of(
+        Command::new("losetup")
+            .args(["--find", "--show", "--partscan"])
+            .arg(&raw),
+    ) {
+        Ok(device) => device,
+        Err(status) => {
+            let _ = fs::remove_file(&raw);
+            return Err(status);
+        }
+    };
+    info!(device, image = %output.display(), "Attached image");
+
+    Ok((device, Attached { output, raw, format }))
+}
+
+/// Detach the loop device and write the image out in its format
+pub fn detach(device: &str, attached: &Attached) -> Result<(), Status> {
+    // Partition devices of the loop disk must be gone before it can detach
+    let _ = output_of(Command::new("udevadm").arg("settle"));
+    output_of(Command::new("losetup").arg("--detach").arg(device))?;
+
+    if attached.format == ImageFormat::Qcow2 {
+        output_of(
+            Command::new("qemu-img")
+                .args(["convert", "-f", "raw", "-O", "qcow2"])
+                .arg(&attached.raw)
+                .arg(&attached.output),
+        )?;
+        fs::remove_file(&attached.raw)?;
+    }
+    info!(device, image = %attached.output.display(), "Detached image");
+
+    Ok(())
+}
+
+/// Whether `device` is the loop device backed by `raw`, per sysfs
+pub fn is_backed_by(device: &str, attached: &Attached) -> bool {
+    let name = Path::new(device).file_name().unwrap_or_default().to_string_lossy();
+    fs::read_to_string(format!("/sys/class/block/{name}/loop/backing_file"))
+        .is_ok_and(|backing| Path::new(backing.trim()) == attached.raw)
+}
+
+/// Run a command, returning its trimmed stdout, or its stderr as the error
+fn output_of(command: &mut Command) -> Result<String, Status> {
+    let output = command
+        .output()
+        .map_err(|e| Status::internal(format!("failed to spawn {:?}: {e}", command.get_program())))?;
+
+    if !output.status.success() {
+        return Err(Status::internal(format!(
+            "{:?} failed: {}",
+            command.get_program(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
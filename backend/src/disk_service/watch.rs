@@ -0,0 +1,238 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Watching for disks being added, removed or changing
+//!
+//! Disks are described again whenever the kernel announces a block device
+//! uevent, and at least every few seconds regardless: mounting or swapping
+//! on a partition changes its busy state without any uevent. Each snapshot
+//! is compared with the last to produce the events.
+
+use super::{assess, describe};
+use nix::sys::{
+    socket::{
+        AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType, bind, recv, setsockopt, socket, sockopt,
+    },
+    time::{TimeVal, TimeValLike},
+};
+use protocols::lichen::storage::disks::{Disk, DiskEvent, DiskEventKind};
+use std::{
+    os::fd::{AsRawFd, OwnedFd},
+    thread,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tonic::Status;
+use tracing::warn;
+
+/// How long to wait for a uevent, or between snapshots without them
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long udev gets to finish with a device before it is described again
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// Where disk snapshots come from, and what wakes the watcher to take one
+pub trait DiskSource: Send + 'static {
+    /// The disks present now
+    fn snapshot(&mut self) -> Result<Vec<Disk>, Status>;
+
+    /// Fill in what is too slow to take with every snapshot, for a disk
+    /// about to be reported
+    fn complete(&mut self, _disk: &mut Disk) {}
+
+    /// Block until the disks may have changed; false stops the watch
+    fn wait(&mut self) -> bool;
+}
+
+/// The system's disks, woken by kernel uevents where the netlink socket can
+/// be opened and by polling otherwise
+pub struct SystemDisks {
+    exclude_loopback: bool,
+    uevents: Option<OwnedFd>,
+}
+
+impl SystemDisks {
+    pub fn new(exclude_loopback: bool) -> Self {
+        let uevents = uevent_socket()
+            .inspect_err(|e| warn!("cannot listen for uevents, polling for disk changes: {e}"))
+            .ok();
+
+        Self {
+            exclude_loopback,
+            uevents,
+        }
+    }
+}
+
+impl DiskSource for SystemDisks {
+    fn snapshot(&mut self) -> Result<Vec<Disk>, Status> {
+        describe(self.exclude_loopback)
+    }
+
+    fn complete(&mut self, disk: &mut Disk) {
+        assess(disk);
+    }
+
+    fn wait(&mut self) -> bool {
+        let Some(uevents) = &self.uevents else {
+            thread::sleep(POLL_INTERVAL);
+            return true;
+        };
+
+        // A timeout is as good as an event: it is time to poll again
+        let mut buffer = [0u8; 8192];
+        let Ok(len) = recv(uevents.as_raw_fd(), &mut buffer, MsgFlags::empty()) else {
+            return true;
+        };
+        if is_block_event(&buffer[..len]) {
+            // A disk arrives as a burst of events, one per partition
+            thread::sleep(SETTLE);
+            while recv(uevents.as_raw_fd(), &mut buffer, MsgFlags::MSG_DONTWAIT).is_ok() {}
+        }
+
+        true
+    }
+}
+
+/// Listen to the kernel's uevent broadcast group
+fn uevent_socket() -> nix::Result<OwnedFd> {
+    let fd = socket(
+        AddressFamily::Netlink,
+        SockType::Datagram,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::NetlinkKObjectUEvent,
+    )?;
+    bind(fd.as_raw_fd(), &NetlinkAddr::new(0, 1))?;
+    setsockopt(
+        &fd,
+        sockopt::ReceiveTimeout,
+        &TimeVal::milliseconds(POLL_INTERVAL.as_millis() as i64),
+    )?;
+
+    Ok(fd)
+}
+
+/// Whether a uevent, NUL-separated `KEY=value` fields, is for a block device
+fn is_block_event(uevent: &[u8]) -> bool {
+    uevent.split(|byte| *byte == 0).any(|field| field == b"SUBSYSTEM=block")
+}
+
+/// Stream the changes from `known` until the receiver goes away or the
+/// source stops
+pub fn watch(mut source: impl DiskSource, mut known: Vec<Disk>, tx: mpsc::Sender<Result<DiskEvent, Status>>) {
+    loop {
+        let disks = match source.snapshot() {
+            Ok(disks) => disks,
+            Err(status) => {
+                let _ = tx.blocking_send(Err(status));
+                return;
+            }
+        };
+
+        for mut event in changes(&known, &disks) {
+            if event.kind() != DiskEventKind::Removed
+                && let Some(disk) = &mut event.disk
+            {
+                source.complete(disk);
+            }
+            if tx.blocking_send(Ok(event)).is_err() {
+                return;
+            }
+        }
+        known = disks;
+
+        if !source.wait() || tx.is_closed() {
+            return;
+        }
+    }
+}
+
+/// The events turning `before` into `after`, matching disks by device path.
+/// Health is not compared, as snapshots leave it unassessed.
+fn changes(before: &[Disk], after: &[Disk]) -> Vec<DiskEvent> {
+    let event = |kind: DiskEventKind, disk: &Disk| DiskEvent {
+        kind: kind as i32,
+        disk: Some(disk.clone()),
+    };
+    let unassessed = |disk: &Disk| Disk {
+        health: 0,
+        health_detail: None,
+        ..disk.clone()
+    };
+
+    let mut events = Vec::new();
+    for disk in after {
+        match before.iter().find(|old| old.device == disk.device) {
+            None => events.push(event(DiskEventKind::Added, disk)),
+            Some(old) if unassessed(old) != unassessed(disk) => events.push(event(DiskEventKind::Changed, disk)),
+            Some(_) => {}
+        }
+    }
+    for old in before {
+        if !after.iter().any(|disk| disk.device == old.device) {
+            events.push(event(DiskEventKind::Removed, old));
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocols::lichen::storage::disks::DiskHealth;
+    use std::collections::VecDeque;
+
+    /// Hands out prepared snapshots, stopping once they run out
+    struct Scripted(VecDeque<Vec<Disk>>);
+
+    impl DiskSource for Scripted {
+        fn snapshot(&mut self) -> Result<Vec<Disk>, Status> {
+            Ok(self.0.pop_front().unwrap_or_default())
+        }
+
+        fn wait(&mut self) -> bool {
+            !self.0.is_empty()
+        }
+    }
+
+    fn disk(device: &str, busy: Option<&str>) -> Disk {
+        Disk {
+            device: device.to_owned(),
+            busy: busy.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn streams_added_changed_and_removed_disks() {
+        let assessed = Disk {
+            health: DiskHealth::Passed as i32,
+            ..disk("/dev/sda", None)
+        };
+        let source = Scripted(VecDeque::from([
+            vec![disk("/dev/sda", None)],
+            vec![disk("/dev/sda", None), disk("/dev/sdb", None)],
+            vec![disk("/dev/sda", Some("mounted at /mnt"))],
+        ]));
+        let (tx, mut rx) = mpsc::channel(16);
+
+        watch(source, vec![assessed], tx);
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            let event = event.expect("no errors");
+            events.push((event.kind(), event.disk.expect("a disk").device));
+        }
+        assert_eq!(
+            events,
+            [
+                (DiskEventKind::Added, "/dev/sdb".to_owned()),
+                (DiskEventKind::Changed, "/dev/sda".to_owned()),
+                (DiskEventKind::Removed, "/dev/sdb".to_owned()),
+            ],
+            "a known disk differing only in health is not reported"
+        );
+    }
+}
@@ -4,31 +4,48 @@
 
 //! Install service: privileged operations for installing the target system
 
+mod accounts;
+mod autologin;
 pub mod btrfs;
-
-use crate::{auth::AuthService, install_service::btrfs::is_btrfs};
-use disks::BlockDevice;
+mod fstab;
+mod locales;
+mod probe;
+mod progress;
+mod refresh;
+mod repair;
+pub mod runner;
+
+use crate::{
+    auth::AuthService,
+    busy::Usage,
+    install_service::{
+        btrfs::is_btrfs,
+        progress::Progress,
+        runner::{Audited, CommandRunner, Stream},
+    },
+    simulate,
+};
 use lichen_macros::authorized;
-use protocols::lichen::install::{
-    DiscoverSystemModelsResponse, DiscoveredModel, InstallProgress, InstallSystemRequest, TargetMount,
-    WriteSystemModelRequest, WriteSystemModelResponse,
-    install_server::{Install, InstallServer},
+use protocols::{
+    is_valid_ntp_server,
+    lichen::{
+        install::{
+            AuditLogResponse, DiscoverSystemModelsResponse, DiscoveredModel, InstallProgress, InstallStage,
+            InstallSystemRequest, ProbeDisksResponse, RefreshTargetRequest, RefreshTargetResponse, RepairRequest,
+            RootAccount, TargetMount, WriteSystemModelRequest, WriteSystemModelResponse,
+            install_server::{Install, InstallServer},
+        },
+        keyboard::KeyboardLayout,
+    },
 };
 use std::{
     collections::VecDeque,
     fs,
-    io::{BufRead, BufReader, Write},
     os::unix,
     path::{Path, PathBuf},
-    process::{Command, Stdio},
-    sync::{
-        Arc, Mutex,
-        atomic::{AtomicBool, Ordering},
-    },
-    thread,
-    time::Duration,
+    process::Command,
+    sync::{Arc, Mutex},
 };
-use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 use tracing::{info, warn};
@@ -46,6 +63,12 @@ const SYSTEM_MODEL_PATH: &str = "usr/lib/system-model.kdl";
 const INSTALL_MODEL_PATH: &str = "etc/moss/install-model.kdl";
 /// Repo config directory inside the target root
 const REPO_DIR: &str = "etc/moss/repo.d";
+/// locale(7) categories a regional format override may set
+const LOCALE_FORMAT_CATEGORIES: &[&str] = &["LC_TIME", "LC_NUMERIC", "LC_MONETARY", "LC_PAPER", "LC_MEASUREMENT"];
+/// ntpd-rs configuration inside the target root
+const NTPD_CONFIG: &str = "etc/ntpd-rs/ntp.toml";
+/// Xorg drop-in directory inside the target root, shared with systemd-localed
+const XORG_CONF_DIR: &str = "etc/X11/xorg.conf.d";
 /// The unstable repo kdl entry
 const UNSTABLE_REPO: &str = r#"unstable {
     description "AerynOS unstable package stream"
@@ -62,6 +85,8 @@ const UNSTABLE_REPO: &str = r#"unstable {
 #[derive(Debug)]
 pub struct Service {
     auth: Arc<AuthService>,
+    /// Runs every command, keeping the audit log
+    runner: Arc<Audited>,
 }
 
 /// A target mount resolved to its on-disk filesystem type, with a btrfs root
@@ -73,14 +98,18 @@ struct ResolvedMount {
     subvol: Option<String>,
 }
 
-/// Creates a new Install gRPC server instance using the default Service implementation
-pub fn service(auth: Arc<AuthService>) -> InstallServer<Service> {
-    InstallServer::new(Service { auth })
+/// Creates a new Install gRPC server instance, running commands with `runner`
+pub fn service(auth: Arc<AuthService>, runner: Box<dyn CommandRunner>) -> InstallServer<Service> {
+    InstallServer::new(Service {
+        auth,
+        runner: Arc::new(Audited::new(runner)),
+    })
 }
 
 #[tonic::async_trait]
 impl Install for Service {
     type InstallSystemStream = ReceiverStream<Result<InstallProgress, Status>>;
+    type RepairStream = ReceiverStream<Result<InstallProgress, Status>>;
 
     #[authorized("com.aerynos.lichen.install.write-model")]
     async fn write_system_model(
@@ -93,12 +122,17 @@ impl Install for Service {
         if request.root_device.is_empty() {
             return Err(Status::invalid_argument("no root device provided"));
         }
-        if !Path::new(&request.root_device).exists() {
+        if !self.runner.device_exists(Path::new(&request.root_device)) {
             return Err(Status::not_found(format!("no such device: {}", request.root_device)));
         }
 
         tokio::task::block_in_place(|| {
-            write_to_target(&request.root_device, &request.system_model, &request.install_model)
+            write_to_target(
+                &*self.runner,
+                &request.root_device,
+                &request.system_model,
+                &request.install_model,
+            )
         })?;
 
         Ok(Response::new(WriteSystemModelResponse {}))
@@ -111,11 +145,58 @@ impl Install for Service {
     ) -> Result<Response<DiscoverSystemModelsResponse>, tonic::Status> {
         let _ = request;
         info!("Probing disks for previous installation system-models");
-        let models = tokio::task::block_in_place(discover_models)?;
+        let models = tokio::task::block_in_place(|| discover_models(&*self.runner))?;
 
         Ok(Response::new(DiscoverSystemModelsResponse { models }))
     }
 
+    #[authorized("com.aerynos.lichen.install.discover")]
+    async fn probe_disks(&self, request: Request<()>) -> Result<Response<ProbeDisksResponse>, tonic::Status> {
+        let _ = request;
+        info!("Probing disks for existing filesystems and operating systems");
+        let partitions = tokio::task::block_in_place(|| probe::probe_disks(&*self.runner))?;
+
+        Ok(Response::new(ProbeDisksResponse { partitions }))
+    }
+
+    #[authorized("com.aerynos.lichen.install.refresh")]
+    async fn refresh_target(
+        &self,
+        request: Request<RefreshTargetRequest>,
+    ) -> Result<Response<RefreshTargetResponse>, tonic::Status> {
+        let request = request.into_inner();
+        info!(mounts = ?request.mounts, "Refreshing previous installation (destructive, keeps /home)");
+
+        // As with ApplyStrategy: recreating a filesystem the live system is
+        // using would take the session's mounts or swap with it
+        let usage = Usage::read();
+        for mount in &request.mounts {
+            let name = Path::new(&mount.device)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            if let Some(reason) = usage.node(&name, &mount.device) {
+                return Err(Status::failed_precondition(format!(
+                    "{} is in use by the live system: {reason}",
+                    mount.device
+                )));
+            }
+        }
+
+        tokio::task::block_in_place(|| {
+            refresh::refresh(&*self.runner, &simulate::path(TARGET_MOUNT), &request.mounts)
+        })?;
+
+        Ok(Response::new(RefreshTargetResponse {}))
+    }
+
+    #[authorized("com.aerynos.lichen.install.validate")]
+    async fn validate_install(&self, request: Request<InstallSystemRequest>) -> Result<Response<()>, tonic::Status> {
+        validate_request(request.get_ref())?;
+
+        Ok(Response::new(()))
+    }
+
     #[authorized("com.aerynos.lichen.install.system")]
     async fn install_system(
         &self,
@@ -128,64 +209,102 @@ impl Install for Service {
         if !request.mounts.iter().any(|mount| mount.mountpoint == "/") {
             return Err(Status::invalid_argument("no root mount provided"));
         }
+        validate_request(&request)?;
 
         info!("Installing system to target");
 
-        let (tx, rx) = mpsc::channel(64);
-        let done = Arc::new(AtomicBool::new(false));
+        let runner = self.runner.clone();
+        Ok(Response::new(progress::stream(
+            "Installation complete",
+            progress::INSTALL_STAGES,
+            move |progress| install_target(&*runner, &request, progress),
+        )))
+    }
 
-        // Keep-alive ticks so the stream never idles, even while moss is quiet
-        {
-            let tx = tx.clone();
-            let done = done.clone();
+    #[authorized("com.aerynos.lichen.install.repair")]
+    async fn repair(&self, request: Request<RepairRequest>) -> Result<Response<Self::RepairStream>, tonic::Status> {
+        let request = request.into_inner();
+        info!(root_device = %request.root_device, "Repairing existing installation");
 
-            thread::spawn(move || {
-                while !done.load(Ordering::Relaxed) {
-                    thread::sleep(Duration::from_secs(10));
+        if request.root_device.is_empty() {
+            return Err(Status::invalid_argument("no root device provided"));
+        }
+        let mounts = tokio::task::block_in_place(|| {
+            repair::installation_mounts(&*self.runner, &request.root_device, request.regenerate_fstab)
+        })?;
 
-                    let update = InstallProgress {
-                        message: String::new(),
-                        finished: false,
-                    };
+        let runner = self.runner.clone();
+        Ok(Response::new(progress::stream(
+            "Repair complete",
+            progress::REPAIR_STAGES,
+            move |progress| repair::repair(&*runner, &request, &mounts, progress),
+        )))
+    }
 
-                    if tx.blocking_send(Ok(update)).is_err() {
-                        break;
-                    }
-                }
-            });
-        }
+    #[authorized("com.aerynos.lichen.install.audit")]
+    async fn get_audit_log(&self, request: Request<()>) -> Result<Response<AuditLogResponse>, tonic::Status> {
+        let _ = request;
+        let entries = self.runner.entries();
 
-        thread::spawn(move || {
-            let progress = |message: String| {
-                let _ = tx.blocking_send(Ok(InstallProgress {
-                    message,
-                    finished: false,
-                }));
-            };
-            let result = install_target(&request, &progress);
-
-            done.store(true, Ordering::Relaxed);
-            match result {
-                Ok(()) => {
-                    let _ = tx.blocking_send(Ok(InstallProgress {
-                        message: "Installation complete".to_string(),
-                        finished: true,
-                    }));
-                }
-                Err(status) => {
-                    let _ = tx.blocking_send(Err(status));
-                }
-            }
-        });
+        Ok(Response::new(AuditLogResponse { entries }))
+    }
+}
+
+/// Refuse settings the install would fail on, or that would corrupt the
+/// files they are written to. Everything but the mounts is checked, so this
+/// runs before the target is partitioned as well as when installing.
+fn validate_request(request: &InstallSystemRequest) -> Result<(), Status> {
+    // locale.conf is read by every login: only the format categories may
+    // be set, never an arbitrary variable for every session's environment
+    if let Some(entry) = request
+        .locale_overrides
+        .iter()
+        .find(|entry| !LOCALE_FORMAT_CATEGORIES.contains(&entry.category.as_str()))
+    {
+        return Err(Status::invalid_argument(format!(
+            "unsupported locale category: {}",
+            entry.category
+        )));
+    }
 
-        Ok(Response::new(ReceiverStream::new(rx)))
+    accounts::validate(&request.users, request.ssh_keys_only)?;
+    accounts::validate_root(request.root_account(), &request.root_password_hash, &request.users)?;
+    if let Some(autologin) = &request.autologin
+        && !request.users.iter().any(|user| user.username == autologin.user)
+    {
+        return Err(Status::invalid_argument(format!(
+            "autologin user {} is not being created",
+            autologin.user
+        )));
+    }
+
+    // Written verbatim into vconsole.conf and quoted xorg.conf options, so a
+    // newline or quote would add variables or directives of its own
+    if let Some(keyboard) = &request.keyboard
+        && let Some(name) = [&keyboard.layout, &keyboard.variant, &keyboard.keymap]
+            .into_iter()
+            .find(|name| !is_keyboard_name(name))
+    {
+        return Err(Status::invalid_argument(format!("invalid keyboard setting: {name:?}")));
     }
+
+    // Server names are written verbatim into ntp.toml
+    if let Some(server) = request.ntp_servers.iter().find(|server| !is_valid_ntp_server(server)) {
+        return Err(Status::invalid_argument(format!("invalid NTP server: {server}")));
+    }
+
+    Ok(())
 }
 
 /// Mount the target root, write the model, and always unmount again,
 /// even when the write fails
-fn write_to_target(root_device: &str, system_model: &str, install_model: &str) -> Result<(), Status> {
-    let target = Path::new(TARGET_MOUNT);
+fn write_to_target(
+    runner: &dyn CommandRunner,
+    root_device: &str,
+    system_model: &str,
+    install_model: &str,
+) -> Result<(), Status> {
+    let target = &simulate::path(TARGET_MOUNT);
 
     fs::create_dir_all(target)?;
 
@@ -193,13 +312,13 @@ fn write_to_target(root_device: &str, system_model: &str, install_model: &str) -
     // not the top level, which the installed system never mounts.
     let mut mount = Command::new("mount");
 
-    if is_btrfs(root_device)? {
-        btrfs::create_subvolumes(target, root_device)?;
+    if is_btrfs(runner, root_device)? {
+        btrfs::create_subvolumes(runner, target, root_device)?;
         mount.args(["-o", &format!("subvol={}", btrfs::ROOT_SUBVOL)]);
     }
 
     mount.arg(root_device).arg(target);
-    run(&mut mount)?;
+    run(runner, &mut mount)?;
 
     let result = (|| -> Result<(), Status> {
         let system_model_path = target.join(SYSTEM_MODEL_PATH);
@@ -219,19 +338,16 @@ fn write_to_target(root_device: &str, system_model: &str, install_model: &str) -
         Ok(())
     })();
 
-    let unmounted = run(Command::new("umount").arg(target));
+    let unmounted = run(runner, Command::new("umount").arg(target));
 
     result.and(unmounted)
 }
 
 /// Probe every unmounted partition read-only for a system-model from a previous
 /// installation. Unmountable partitions are auto skipped.
-fn discover_models() -> Result<Vec<DiscoveredModel>, Status> {
-    let probe = Path::new(PROBE_MOUNT);
-    fs::create_dir_all(probe)?;
-
+fn discover_models(runner: &dyn CommandRunner) -> Result<Vec<DiscoveredModel>, Status> {
     let mounted = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
-    let devices = BlockDevice::discover()?;
+    let devices = simulate::block_devices()?;
     let mut models = Vec::new();
 
     for device in &devices {
@@ -243,24 +359,40 @@ fn discover_models() -> Result<Vec<DiscoveredModel>, Status> {
                 continue;
             }
 
-            // Read-only, because these are the user's existing partitions and
-            // may hold a foreign OS. A rw mount would replay journals and bump
-            // mount counts on filesystems that do not need to be touched. No
-            // mountable filesystem means not a candidate.
-            if run(Command::new("mount").args(["-o", "ro", &node, &probe.to_string_lossy()])).is_err() {
+            // No mountable filesystem means not a candidate
+            let Some((model_contents, fstab)) = probe_read(runner, &node, |probe| {
+                let model_contents = fs::read_to_string(probe.join(INSTALL_MODEL_PATH))
+                    .or_else(|_| fs::read_to_string(probe.join(SYSTEM_MODEL_PATH)))
+                    .ok();
+                let fstab = fs::read_to_string(probe.join("etc/fstab")).unwrap_or_default();
+                (model_contents, fstab)
+            }) else {
                 continue;
-            }
-
-            let model_contents = fs::read_to_string(probe.join(INSTALL_MODEL_PATH))
-                .or_else(|_| fs::read_to_string(probe.join(SYSTEM_MODEL_PATH)))
-                .ok();
-            let _ = run(Command::new("umount").arg(probe));
+            };
 
             if let Some(model_contents) = model_contents {
                 info!(device = %node, "Found system or install-model from a previous installation");
+
+                // Offer to keep /home only when its owners can be read, so the
+                // accounts can be recreated with the ids owning their files
+                let mounts = refresh::existing_mounts(&fstab).unwrap_or_default();
+                let home = mounts.iter().find(|mount| mount.mountpoint == "/home").cloned();
+                let (mounts, home_owners) = match home {
+                    Some(home) => match refresh::home_owners(runner, &home) {
+                        Ok(owners) => (mounts, owners),
+                        Err(status) => {
+                            warn!(device = %home.device, "cannot read /home owners: {}", status.message());
+                            (Vec::new(), Vec::new())
+                        }
+                    },
+                    None => (Vec::new(), Vec::new()),
+                };
+
                 models.push(DiscoveredModel {
                     device: node,
                     contents: model_contents,
+                    mounts,
+                    home_owners,
                 });
             }
         }
@@ -269,29 +401,71 @@ fn discover_models() -> Result<Vec<DiscoveredModel>, Status> {
     Ok(models)
 }
 
+/// Mount a partition read-only at the probe mountpoint, run `read` against
+/// it and unmount again. `None` when the partition has no mountable filesystem.
+fn probe_read<T>(runner: &dyn CommandRunner, node: &str, read: impl FnOnce(&Path) -> T) -> Option<T> {
+    let probe = &simulate::path(PROBE_MOUNT);
+    fs::create_dir_all(probe).ok()?;
+
+    // Read-only, because these are the user's existing partitions and
+    // may hold a foreign OS. A rw mount would replay journals and bump
+    // mount counts on filesystems that do not need to be touched.
+    run(runner, Command::new("mount").args(["-o", "ro", node]).arg(probe)).ok()?;
+    let result = read(probe);
+    let _ = run(runner, Command::new("umount").arg(probe));
+
+    Some(result)
+}
+
 /// Mount the target filesystems, install the OS via moss from the system
 /// model written earlier, configure the target, and always unmount again
-fn install_target(request: &InstallSystemRequest, progress: &(dyn Fn(String) + Sync)) -> Result<(), Status> {
-    let target = Path::new(TARGET_MOUNT);
+fn install_target(
+    runner: &dyn CommandRunner,
+    request: &InstallSystemRequest,
+    progress: &Progress,
+) -> Result<(), Status> {
+    let target = &simulate::path(TARGET_MOUNT);
     fs::create_dir_all(target)?;
 
     // Sort by path length so a parent is always mounted before its children:
     // mounting /boot after /boot/efi would shadow the ESP, and blsforme would
     // write boot entries into a directory nothing ever reads.
-    let mounts = resolve_mounts(&request.mounts)?;
+    let mounts = resolve_mounts(runner, &request.mounts)?;
 
     // If this is a btrfs root, create the @/@home subvolumes
     if let Some(root) = mounts
         .iter()
         .find(|mount| mount.mountpoint == "/" && mount.subvol.is_some())
     {
-        btrfs::create_subvolumes(target, &root.device)?;
+        btrfs::create_subvolumes(runner, target, &root.device)?;
     }
 
+    with_target(runner, target, &mounts, progress, || {
+        // `moss sync --import` does not bootstrap repos on an empty root
+        if request.repositories.is_empty() {
+            warn!("no repos to prime; sync will fail unless moss bootstraps them itself");
+        }
+        configure_repos(target)?;
+        sync_packages(runner, target, progress)?;
+
+        progress.stage(InstallStage::Configure, "Configuring target system");
+        configure_target(runner, target, request, progress)
+    })
+}
+
+/// Mount the target filesystems and the virtual filesystems chroot commands
+/// need, run `work`, and always unmount again
+fn with_target(
+    runner: &dyn CommandRunner,
+    target: &Path,
+    mounts: &[ResolvedMount],
+    progress: &Progress,
+    work: impl FnOnce() -> Result<(), Status>,
+) -> Result<(), Status> {
     let mut mounted: Vec<PathBuf> = Vec::new();
     let result = (|| -> Result<(), Status> {
-        progress("Mounting target filesystems".to_string());
-        for mount in &mounts {
+        progress.stage(InstallStage::Mounting, "Mounting target filesystems");
+        for mount in mounts {
             let mountpoint = target.join(mount.mountpoint.trim_start_matches('/'));
             fs::create_dir_all(&mountpoint)?;
 
@@ -301,7 +475,7 @@ fn install_target(request: &InstallSystemRequest, progress: &(dyn Fn(String) + S
                 cmd.args(["-o", &format!("subvol={subvol}")]);
             }
             cmd.arg(&mount.device).arg(&mountpoint);
-            run(&mut cmd)?;
+            run(runner, &mut cmd)?;
             mounted.push(mountpoint);
         }
 
@@ -315,51 +489,55 @@ fn install_target(request: &InstallSystemRequest, progress: &(dyn Fn(String) + S
         ] {
             let mountpoint = target.join(dest);
             fs::create_dir_all(&mountpoint)?;
-            run(Command::new("mount").args(["--bind", source, &mountpoint.to_string_lossy()]))?;
+            run(
+                runner,
+                Command::new("mount").args(["--bind", source, &mountpoint.to_string_lossy()]),
+            )?;
             mounted.push(mountpoint);
         }
 
-        // `moss sync --import` does not bootstrap repos on an empty root
-        if request.repositories.is_empty() {
-            warn!("no repos to prime; sync will fail unless moss bootstraps them itself");
-        }
-        configure_repos(target)?;
-        progress("Refreshing package index".to_string());
-        run(Command::new("moss").arg("-D").arg(target).args(["repo", "update"]))?;
-
-        // moss materializes the system from the model, including populating
-        // the mounted ESP/XBOOTLDR with boot entries via its blsforme
-        // integration, which is why the boot mounts must be live first
-        progress("Installing packages".to_string());
-        info!("Running moss sync against the target (this can take a while)");
-        run_streaming(
-            Command::new("moss")
-                .args(["sync", "--import"])
-                .arg(target.join(SYSTEM_MODEL_PATH))
-                .arg("-D")
-                .arg(target)
-                .arg("-u")
-                .arg("-y"),
-            progress,
-        )?;
-
-        progress("Configuring target system".to_string());
-        configure_target(target, request)
+        work()
     })();
 
     // Unwind in reverse: the bind mounts and nested boot mounts sit under the
     // target root, so unmounting the root first fails with EBUSY and pins it
     // for the rest of the session. sync last, because the user is told they
     // may reboot the moment this returns.
-    progress("Unmounting target filesystems".to_string());
+    progress.stage(InstallStage::Unmount, "Unmounting target filesystems");
     for mountpoint in mounted.iter().rev() {
-        let _ = run(Command::new("umount").arg(mountpoint));
+        let _ = run(runner, Command::new("umount").arg(mountpoint));
     }
-    let _ = run(&mut Command::new("sync"));
+    let _ = run(runner, &mut Command::new("sync"));
 
     result
 }
 
+/// Bring the target's packages in line with its system-model
+fn sync_packages(runner: &dyn CommandRunner, target: &Path, progress: &Progress) -> Result<(), Status> {
+    progress.stage(InstallStage::RepoRefresh, "Refreshing package index");
+    run(
+        runner,
+        Command::new("moss").arg("-D").arg(target).args(["repo", "update"]),
+    )?;
+
+    // moss materializes the system from the model, including populating
+    // the mounted ESP/XBOOTLDR with boot entries via its blsforme
+    // integration, which is why the boot mounts must be live first
+    progress.stage(InstallStage::PackageFetch, "Installing packages");
+    info!("Running moss sync against the target (this can take a while)");
+    run_streaming(
+        runner,
+        Command::new("moss")
+            .args(["sync", "--import"])
+            .arg(target.join(SYSTEM_MODEL_PATH))
+            .arg("-D")
+            .arg(target)
+            .arg("-u")
+            .arg("-y"),
+        progress,
+    )
+}
+
 /// Make the unstable stream the only configured repo on the installed system,
 /// regardless of which stream the live media primed. Inheriting the live
 /// medium's volatile repo would have the installed system self-upgrade onto a
@@ -381,9 +559,14 @@ fn configure_repos(target: &Path) -> Result<(), Status> {
 }
 
 /// Apply the installer-owned config to the installed target
-fn configure_target(target: &Path, req: &InstallSystemRequest) -> Result<(), Status> {
+fn configure_target(
+    runner: &dyn CommandRunner,
+    target: &Path,
+    req: &InstallSystemRequest,
+    progress: &Progress,
+) -> Result<(), Status> {
     if !req.locale.is_empty() {
-        fs::write(target.join("etc/locale.conf"), format!("LANG={}\n", req.locale))?;
+        write_locale_conf(runner, target, req, progress)?;
     }
 
     if !req.timezone.is_empty() {
@@ -392,63 +575,166 @@ fn configure_target(target: &Path, req: &InstallSystemRequest) -> Result<(), Sta
         unix::fs::symlink(format!("../usr/share/zoneinfo/{}", req.timezone), &localtime)?;
     }
 
+    write_clock(target, req)?;
+
+    if let Some(keyboard) = &req.keyboard {
+        write_keyboard(target, keyboard)?;
+    }
+
+    regenerate_machine_id(runner, target)?;
+
+    for user in &req.users {
+        accounts::create(runner, target, user)?;
+    }
+
+    if req.ssh_keys_only {
+        accounts::write_sshd_dropin(target)?;
+    }
+
+    if let Some(login) = &req.autologin {
+        autologin::configure(runner, target, login)?;
+    }
+
+    accounts::apply_root(runner, target, req.root_account())?;
+
+    let mut entries = String::new();
+    if matches!(req.root_account(), RootAccount::Password | RootAccount::Unspecified)
+        && !req.root_password_hash.is_empty()
+    {
+        entries.push_str(&format!("root:{}\n", req.root_password_hash));
+    }
+    for user in &req.users {
+        entries.push_str(&format!("{}:{}\n", user.username, user.password_hash));
+    }
+    if !entries.is_empty() {
+        set_passwords(runner, target, &entries)?;
+    }
+
+    write_fstab(runner, target, &resolve_mounts(runner, &req.mounts)?)?;
+
+    Ok(())
+}
+
+/// Give the target a machine-id of its own
+fn regenerate_machine_id(runner: &dyn CommandRunner, target: &Path) -> Result<(), Status> {
     // moss installs systemd's /etc/machine-id from the package set, so the
     // target would inherit the live medium's id. Every machine installed from
     // that medium would then share a DHCP DUID and journal id, and systemd
     // would treat the first boot as an nth boot and skip ConditionFirstBoot
     // units. Absent is the expected case, hence the ignored result.
     let _ = fs::remove_file(target.join("etc/machine-id"));
-    run(Command::new("chroot").arg(target).arg("systemd-machine-id-setup"))?;
+    run(
+        runner,
+        Command::new("chroot").arg(target).arg("systemd-machine-id-setup"),
+    )
+}
 
-    if let Some(user) = &req.user {
-        run(Command::new("chroot")
-            .arg(target)
-            .args([
-                "useradd",
-                "-m",
-                "-U",
-                "-G",
-                "audio,adm,wheel,render,kvm,input,users",
-                "-c",
-            ])
-            .args([&user.real_name, &user.username]))?;
+/// Write LANG plus any regional format overrides that differ from it, after
+/// making sure each locale exists on the target. A missing LANG locale fails
+/// the install; a missing override is dropped with a warning so the category
+/// falls back to LANG rather than C.
+fn write_locale_conf(
+    runner: &dyn CommandRunner,
+    target: &Path,
+    req: &InstallSystemRequest,
+    progress: &Progress,
+) -> Result<(), Status> {
+    locales::ensure_available(runner, target, &req.locale)?;
+
+    let mut contents = format!("LANG={}\n", req.locale);
+
+    for entry in &req.locale_overrides {
+        if entry.locale == req.locale {
+            continue;
+        }
+
+        match locales::ensure_available(runner, target, &entry.locale) {
+            Ok(()) => contents.push_str(&format!("{}={}\n", entry.category, entry.locale)),
+            Err(status) => {
+                warn!(category = %entry.category, locale = %entry.locale, "{}", status.message());
+                progress.warn(format!(
+                    "{} will follow {} instead: {}",
+                    entry.category,
+                    req.locale,
+                    status.message()
+                ));
+            }
+        }
     }
 
-    let mut entries = String::new();
-    if !req.root_password_hash.is_empty() {
-        entries.push_str(&format!("root:{}\n", req.root_password_hash));
+    fs::write(target.join("etc/locale.conf"), contents)?;
+    Ok(())
+}
+
+/// Write the hardware clock mode to /etc/adjtime, and the NTP servers to the
+/// ntpd-rs config when any were chosen
+fn write_clock(target: &Path, req: &InstallSystemRequest) -> Result<(), Status> {
+    // hwclock(8) format: drift line, last calibration, then the RTC mode
+    let mode = if req.rtc_local { "LOCAL" } else { "UTC" };
+    fs::write(target.join("etc/adjtime"), format!("0.0 0 0.0\n0\n{mode}\n"))?;
+
+    if !req.ntp_servers.is_empty() {
+        let path = target.join(NTPD_CONFIG);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, ntpd_config(&req.ntp_servers))?;
     }
-    if let Some(user) = &req.user {
-        entries.push_str(&format!("{}:{}\n", user.username, user.password_hash));
+
+    Ok(())
+}
+
+/// An ntpd-rs config using the given servers in place of the default pool
+fn ntpd_config(servers: &[String]) -> String {
+    let mut config = String::from("[observability]\nlog-level = \"info\"\n");
+    for server in servers {
+        config.push_str(&format!("\n[[source]]\nmode = \"server\"\naddress = \"{server}\"\n"));
     }
-    if !entries.is_empty() {
-        set_passwords(target, &entries)?;
+    config
+}
+
+/// An XKB layout or variant, or console keymap, name; empty leaves it unset
+fn is_keyboard_name(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '+' | '-'))
+}
+
+/// Write the console keymap and X11 layout, in the same form systemd-localed
+/// would, so `localectl status` on the installed system reports them
+fn write_keyboard(target: &Path, keyboard: &KeyboardLayout) -> Result<(), Status> {
+    if !keyboard.keymap.is_empty() {
+        fs::write(
+            target.join("etc/vconsole.conf"),
+            format!("KEYMAP={}\n", keyboard.keymap),
+        )?;
     }
 
-    write_fstab(target, req)?;
+    if !keyboard.layout.is_empty() {
+        let mut section = String::from("Section \"InputClass\"\n");
+        section.push_str("        Identifier \"system-keyboard\"\n");
+        section.push_str("        MatchIsKeyboard \"on\"\n");
+        section.push_str(&format!("        Option \"XkbLayout\" \"{}\"\n", keyboard.layout));
+        if !keyboard.variant.is_empty() {
+            section.push_str(&format!("        Option \"XkbVariant\" \"{}\"\n", keyboard.variant));
+        }
+        section.push_str("EndSection\n");
+
+        let xorg_dir = target.join(XORG_CONF_DIR);
+        fs::create_dir_all(&xorg_dir)?;
+        fs::write(xorg_dir.join("00-keyboard.conf"), section)?;
+    }
 
     Ok(())
 }
 
 /// Set account passwords from pre-computed crypt(3) hashes via chpasswd -e
-fn set_passwords(target: &Path, entries: &str) -> Result<(), Status> {
-    let mut child = Command::new("chroot")
-        .arg(target)
-        .args(["chpasswd", "-e"])
-        .stdin(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| Status::internal(format!("failed to spawn chpasswd: {e}")))?;
-
-    child
-        .stdin
-        .take()
-        .expect("stdin was piped")
-        .write_all(entries.as_bytes())?;
-
-    let output = child
-        .wait_with_output()
-        .map_err(|e| Status::internal(format!("chpasswd did not complete: {e}")))?;
+fn set_passwords(runner: &dyn CommandRunner, target: &Path, entries: &str) -> Result<(), Status> {
+    let output = runner
+        .output(
+            Command::new("chroot").arg(target).args(["chpasswd", "-e"]),
+            Some(entries.as_bytes()),
+        )
+        .map_err(|e| Status::internal(format!("failed to run chpasswd: {e}")))?;
 
     if !output.status.success() {
         return Err(Status::internal(format!(
@@ -463,11 +749,14 @@ fn set_passwords(target: &Path, entries: &str) -> Result<(), Status> {
 /// Read a single blkid tag value from a device, bypassing the cache: the
 /// partition was created and formatted moments ago, and a stale entry would
 /// put the previous layout's PARTUUID into the target's fstab.
-fn blkid(device: &str, tag: &str) -> Result<String, Status> {
-    let output = Command::new("blkid")
-        .args(["-c", "/dev/null", "-s", tag, "-o", "value"])
-        .arg(device)
-        .output()
+fn blkid(runner: &dyn CommandRunner, device: &str, tag: &str) -> Result<String, Status> {
+    let output = runner
+        .output(
+            Command::new("blkid")
+                .args(["-c", "/dev/null", "-s", tag, "-o", "value"])
+                .arg(device),
+            None,
+        )
         .map_err(|e| Status::internal(format!("failed to spawn blkid: {e}")))?;
 
     if !output.status.success() {
@@ -477,47 +766,36 @@ fn blkid(device: &str, tag: &str) -> Result<String, Status> {
     Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
-/// Run a long command forwarding its output lines as progress, keeping
+/// Run a long moss command forwarding its output lines as progress, keeping
 /// tail of recent lines for error reporting
-fn run_streaming(command: &mut Command, progress: &(dyn Fn(String) + Sync)) -> Result<(), Status> {
-    let mut child = command
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| Status::internal(format!("failed to spawn {:?}: {e}", command.get_program())))?;
-    let stdout = child.stdout.take().expect("stdout was piped");
-    let stderr = child.stderr.take().expect("stderr was piped");
-    let tail: Arc<Mutex<VecDeque<String>>> = Arc::new(Mutex::new(VecDeque::new()));
-    let record = |line: &str| {
-        let mut tail = tail.lock().unwrap();
-        if tail.len() >= 30 {
-            tail.pop_front();
-        }
-        tail.push_back(line.to_string());
-    };
-
-    thread::scope(|scope| {
-        scope.spawn(|| {
-            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
-                record(&line);
-                warn!("stderr: {line}");
+fn run_streaming(runner: &dyn CommandRunner, command: &mut Command, progress: &Progress) -> Result<(), Status> {
+    let tail: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+    let on_line = |stream: Stream, line: &str| {
+        {
+            let mut tail = tail.lock().unwrap();
+            if tail.len() >= 30 {
+                tail.pop_front();
             }
-        });
+            tail.push_back(line.to_string());
+        }
 
-        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
-            record(&line);
+        if stream == Stream::Stderr {
+            warn!("stderr: {line}");
+        }
 
-            let cleaned = clean_line(&line);
-            if !cleaned.is_empty() {
-                progress(cleaned);
-            }
+        let cleaned = clean_line(line);
+        if cleaned.is_empty() {
+            return;
+        }
+        match stream {
+            Stream::Stderr => progress.debug(cleaned),
+            Stream::Stdout => progress.moss(cleaned),
         }
-    });
+    };
 
-    let status = child
-        .wait()
-        .map_err(|e| Status::internal(format!("{:?} did not complete: {e}", command.get_program())))?;
+    let status = runner
+        .stream(command, &on_line)
+        .map_err(|e| Status::internal(format!("failed to run {:?}: {e}", command.get_program())))?;
 
     if !status.success() {
         let detail = tail.lock().unwrap().iter().cloned().collect::<Vec<_>>().join("\n");
@@ -558,9 +836,9 @@ fn clean_line(line: &str) -> String {
 
 /// Run a command to completion, mapping failure to a gRPC status carrying
 /// the command's stderr
-fn run(command: &mut Command) -> Result<(), Status> {
-    let output = command
-        .output()
+fn run(runner: &dyn CommandRunner, command: &mut Command) -> Result<(), Status> {
+    let output = runner
+        .output(command, None)
         .map_err(|e| Status::internal(format!("failed to spawn {:?}: {e}", command.get_program())))?;
 
     if !output.status.success() {
@@ -598,9 +876,7 @@ fn fstab_params(mountpoint: &str, fstype: &str, subvol: Option<&str>) -> (String
     }
 }
 
-fn write_fstab(target: &Path, request: &InstallSystemRequest) -> Result<(), Status> {
-    let mounts = resolve_mounts(&request.mounts)?;
-
+fn write_fstab(runner: &dyn CommandRunner, target: &Path, mounts: &[ResolvedMount]) -> Result<(), Status> {
     // A rootless fstable is worse than none: the initrd hands off to a system
     // that can neither remount / nor find /boot.
     if !mounts.iter().any(|mount| mount.mountpoint == "/") {
@@ -610,7 +886,7 @@ fn write_fstab(target: &Path, request: &InstallSystemRequest) -> Result<(), Stat
     let mut fstab = String::from("# /etc/fstab: static filesystem information.\n");
 
     for mount in mounts {
-        let partuuid = blkid(&mount.device, "PARTUUID")?;
+        let partuuid = blkid(runner, &mount.device, "PARTUUID")?;
         let (options, pass) = fstab_params(&mount.mountpoint, &mount.fstype, mount.subvol.as_deref());
 
         fstab.push_str(&format!(
@@ -625,7 +901,7 @@ fn write_fstab(target: &Path, request: &InstallSystemRequest) -> Result<(), Stat
 
 /// Probe each requested mount's filesystem and expand a btrfs root into the
 /// default @/@home subvolume layout. Sorted so parents precede children.
-fn resolve_mounts(mounts: &[TargetMount]) -> Result<Vec<ResolvedMount>, Status> {
+fn resolve_mounts(runner: &dyn CommandRunner, mounts: &[TargetMount]) -> Result<Vec<ResolvedMount>, Status> {
     let mut resolved = Vec::new();
 
     for mount in mounts {
@@ -635,7 +911,7 @@ fn resolve_mounts(mounts: &[TargetMount]) -> Result<Vec<ResolvedMount>, Status>
         resolved.push(ResolvedMount {
             device: mount.device.clone(),
             mountpoint: mount.mountpoint.clone(),
-            fstype: blkid(&mount.device, "TYPE")?,
+            fstype: blkid(runner, &mount.device, "TYPE")?,
             subvol: None,
         });
     }
@@ -646,3 +922,114 @@ fn resolve_mounts(mounts: &[TargetMount]) -> Result<Vec<ResolvedMount>, Status>
     resolved.sort_by_key(|mount| mount.mountpoint.len());
     Ok(resolved)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install_service::runner::Recording;
+    use std::{env, process};
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("lichen-{name}-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn keyboard_names_cannot_add_lines_to_their_files() {
+        let mut request = InstallSystemRequest {
+            root_password_hash: "$6$salt$hash".to_string(),
+            keyboard: Some(KeyboardLayout {
+                layout: "de".to_string(),
+                variant: "nodeadkeys".to_string(),
+                keymap: "de-latin1-nodeadkeys".to_string(),
+            }),
+            ..Default::default()
+        };
+        assert!(validate_request(&request).is_ok());
+
+        let mut keymap = request.clone();
+        keymap.keyboard.as_mut().unwrap().keymap = "de\nFONT=evil".to_string();
+        assert!(validate_request(&keymap).is_err());
+
+        request.keyboard.as_mut().unwrap().layout = "de\" Option \"XkbOptions\" \"terminate:ctrl_alt_bksp".to_string();
+        assert!(validate_request(&request).is_err());
+    }
+
+    #[test]
+    fn ntp_servers_are_validated_before_templating() {
+        assert!(is_valid_ntp_server("time.example.com"));
+        assert!(is_valid_ntp_server("192.0.2.1:123"));
+        assert!(is_valid_ntp_server("[2001:db8::1]:123"));
+        assert!(!is_valid_ntp_server(""));
+        assert!(!is_valid_ntp_server("evil\"\n[[source]]"));
+
+        let config = ntpd_config(&["a.example.com".to_string(), "b.example.com".to_string()]);
+        assert_eq!(config.matches("[[source]]").count(), 2);
+        assert!(config.contains("address = \"b.example.com\""));
+    }
+
+    #[test]
+    fn target_is_unmounted_in_reverse_even_when_work_fails() {
+        let runner = Recording::default();
+        let target = scratch("target");
+        let mount = |device: &str, mountpoint: &str| ResolvedMount {
+            device: device.to_string(),
+            mountpoint: mountpoint.to_string(),
+            fstype: "ext4".to_string(),
+            subvol: None,
+        };
+
+        let result = with_target(
+            &runner,
+            &target,
+            &[mount("/dev/sda2", "/"), mount("/dev/sda1", "/boot")],
+            &Progress::detached(progress::INSTALL_STAGES),
+            || Err(Status::internal("moss failed")),
+        );
+        let _ = fs::remove_dir_all(&target);
+
+        assert_eq!(result.unwrap_err().message(), "moss failed");
+        let commands = runner.commands();
+        assert!(commands[0].starts_with("mount /dev/sda2 "));
+        assert!(commands[1].starts_with("mount /dev/sda1 ") && commands[1].ends_with("/boot"));
+
+        let unmounts = commands
+            .iter()
+            .filter(|command| command.starts_with("umount"))
+            .collect::<Vec<_>>();
+        assert_eq!(unmounts.len(), 7, "both filesystems and the five virtual ones");
+        assert!(unmounts[0].ends_with("/sys"));
+        assert!(unmounts[5].ends_with("/boot"));
+        assert_eq!(commands.last().map(String::as_str), Some("sync"));
+    }
+
+    #[test]
+    fn fstab_is_written_from_probed_types_and_partuuids() {
+        let runner = Recording::default()
+            .reply("blkid -c /dev/null -s TYPE -o value /dev/sda2", "btrfs\n")
+            .reply("blkid -c /dev/null -s TYPE -o value /dev/sda1", "vfat\n")
+            .reply("blkid -c /dev/null -s PARTUUID -o value /dev/sda2", "2222\n")
+            .reply("blkid -c /dev/null -s PARTUUID -o value /dev/sda1", "1111\n");
+        let target = scratch("fstab");
+        fs::create_dir_all(target.join("etc")).unwrap();
+        let requested = [("/dev/sda1", "/boot"), ("/dev/sda2", "/")].map(|(device, mountpoint)| TargetMount {
+            device: device.to_string(),
+            mountpoint: mountpoint.to_string(),
+        });
+
+        let mounts = resolve_mounts(&runner, &requested).unwrap();
+        write_fstab(&runner, &target, &mounts).unwrap();
+        let fstab = fs::read_to_string(target.join("etc/fstab")).unwrap();
+        let _ = fs::remove_dir_all(&target);
+
+        assert_eq!(
+            fstab.lines().skip(1).collect::<Vec<_>>(),
+            [
+                "PARTUUID=2222 / btrfs defaults,subvol=@ 0 0",
+                "PARTUUID=1111 /boot vfat defaults,umask=0077 0 0",
+                "PARTUUID=2222 /home btrfs defaults,subvol=@home 0 0",
+            ]
+        );
+    }
+}
@@ -0,0 +1,487 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! User accounts on the target: validated before anything is written, then
+//! created with useradd inside the target root.
+
+use super::{run, runner::CommandRunner};
+use protocols::{
+    RESERVED_USERNAMES, is_valid_ssh_key,
+    lichen::install::{RootAccount, UserSpec},
+};
+use std::{
+    collections::HashSet,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+use tonic::Status;
+
+/// Groups every account joins, for local device access
+const STANDARD_GROUPS: &[&str] = &["audio", "render", "kvm", "input", "users"];
+/// Additional groups for administrators
+const ADMIN_GROUPS: &[&str] = &["adm", "wheel"];
+/// sshd drop-in inside the target root for key-only access
+const SSHD_DROPIN: &str = "etc/ssh/sshd_config.d/50-lichen.conf";
+/// Login shell for a disabled root account
+const NOLOGIN: &str = "/usr/bin/nologin";
+/// crypt(3) schemes accepted for account passwords: yescrypt, gost-yescrypt,
+/// scrypt, bcrypt, sha512-crypt and sha256-crypt, as libxcrypt implements
+/// them for pam_unix. This is a fixed list rather than a check of the
+/// target, which has no packages yet when requests are validated. Legacy
+/// DES and MD5 hashes are refused rather than installed.
+const SUPPORTED_HASH_PREFIXES: &[&str] = &["$y$", "$gy$", "$7$", "$2b$", "$6$", "$5$"];
+/// Lowest id useradd allocates to a regular account; those below belong to the system
+const FIRST_USER_ID: u32 = 1000;
+/// The overflow id of nobody and nogroup, and -1, which chown and setuid read as "unchanged"
+const SPECIAL_IDS: &[u32] = &[65534, u32::MAX];
+
+/// Reject accounts that useradd would refuse, or that would corrupt
+/// /etc/passwd or the chpasswd input, before the disk is touched
+pub(super) fn validate(users: &[UserSpec], ssh_keys_only: bool) -> Result<(), Status> {
+    let mut seen = HashSet::new();
+
+    // Key-only sshd with no keys is a machine nobody can log in to remotely
+    if ssh_keys_only && users.iter().all(|user| user.ssh_keys.is_empty()) {
+        return Err(Status::invalid_argument(
+            "disabling SSH password authentication requires at least one SSH key",
+        ));
+    }
+
+    for user in users {
+        if !is_valid_name(&user.username) {
+            return Err(Status::invalid_argument(format!(
+                "invalid username: {:?}",
+                user.username
+            )));
+        }
+        if RESERVED_USERNAMES.contains(&user.username.as_str()) {
+            return Err(Status::invalid_argument(format!(
+                "username {} is reserved for the system",
+                user.username
+            )));
+        }
+        if !seen.insert(user.username.as_str()) {
+            return Err(Status::invalid_argument(format!(
+                "duplicate username: {}",
+                user.username
+            )));
+        }
+        for (field, id) in [("uid", user.uid), ("gid", user.gid)] {
+            if let Some(id) = id.filter(|id| is_system_id(*id)) {
+                return Err(Status::invalid_argument(format!(
+                    "{field} {id} for {} is reserved for the system; use {FIRST_USER_ID} or above",
+                    user.username
+                )));
+            }
+        }
+        if let Some(group) = user.groups.iter().find(|group| !is_valid_name(group)) {
+            return Err(Status::invalid_argument(format!(
+                "invalid group for {}: {group:?}",
+                user.username
+            )));
+        }
+        if !is_supported_hash(&user.password_hash) {
+            return Err(Status::invalid_argument(format!(
+                "unsupported password hash scheme for {}",
+                user.username
+            )));
+        }
+        for (field, value) in [
+            ("real name", &user.real_name),
+            ("shell", &user.shell),
+            ("home", &user.home),
+        ] {
+            if value.contains([':', '\n']) {
+                return Err(Status::invalid_argument(format!(
+                    "invalid {field} for {}",
+                    user.username
+                )));
+            }
+        }
+        if let Some(key) = user.ssh_keys.iter().find(|key| !is_valid_ssh_key(key)) {
+            return Err(Status::invalid_argument(format!(
+                "invalid SSH key for {}: {key:?}",
+                user.username
+            )));
+        }
+        // The home is joined onto the target root and written to as root, so
+        // a `..` would reach outside it
+        for (field, value) in [("shell", &user.shell), ("home", &user.home)] {
+            if !value.is_empty() && !is_normal_absolute(value) {
+                return Err(Status::invalid_argument(format!(
+                    "{field} for {} must be a normalized absolute path",
+                    user.username
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// True for ids the system's own accounts use or that no account may have
+fn is_system_id(id: u32) -> bool {
+    id < FIRST_USER_ID || SPECIAL_IDS.contains(&id)
+}
+
+/// Reject root settings that would leave the machine with no way to
+/// administer it: root must keep a password, or an administrator with a
+/// password must be able to sudo
+pub(super) fn validate_root(root: RootAccount, password_hash: &str, users: &[UserSpec]) -> Result<(), Status> {
+    if root == RootAccount::Password && password_hash.is_empty() {
+        return Err(Status::invalid_argument("root password requested without a hash"));
+    }
+    if !password_hash.is_empty() && !is_supported_hash(password_hash) {
+        return Err(Status::invalid_argument("unsupported root password hash scheme"));
+    }
+
+    let root_usable = matches!(root, RootAccount::Password | RootAccount::Unspecified) && !password_hash.is_empty();
+    let has_admin = users.iter().any(|user| user.admin && !user.password_hash.is_empty());
+    if !root_usable && !has_admin {
+        return Err(Status::invalid_argument(
+            "the root account is not usable and no administrator account has a password",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Lock or disable root; a root password is set with the other accounts'
+pub(super) fn apply_root(runner: &dyn CommandRunner, target: &Path, root: RootAccount) -> Result<(), Status> {
+    match root {
+        RootAccount::Locked => run(
+            runner,
+            Command::new("chroot").arg(target).args(["passwd", "-l", "root"]),
+        ),
+        RootAccount::Disabled => run(
+            runner,
+            Command::new("chroot")
+                .arg(target)
+                .args(["usermod", "-L", "-s", NOLOGIN, "root"]),
+        ),
+        RootAccount::Password | RootAccount::Unspecified => Ok(()),
+    }
+}
+
+/// Create the account inside the target root
+pub(super) fn create(runner: &dyn CommandRunner, target: &Path, user: &UserSpec) -> Result<(), Status> {
+    // A dry run installs no shells to check for
+    if !user.shell.is_empty()
+        && !runner.is_dry_run()
+        && target
+            .join(user.shell.trim_start_matches('/'))
+            .symlink_metadata()
+            .is_err()
+    {
+        return Err(Status::failed_precondition(format!(
+            "shell {} for {} is not installed on the target",
+            user.shell, user.username
+        )));
+    }
+
+    // useradd refuses unknown supplementary groups; -f makes existing ones a no-op
+    for group in &user.groups {
+        run(
+            runner,
+            Command::new("chroot").arg(target).args(["groupadd", "-f", group]),
+        )?;
+    }
+
+    let mut useradd = Command::new("chroot");
+    useradd.arg(target).arg("useradd");
+    useradd.arg(if user.no_create_home { "-M" } else { "-m" });
+    useradd.args(["-G", &supplementary_groups(user).join(",")]);
+    useradd.args(["-c", &user.real_name]);
+
+    match user.gid {
+        // A fixed gid needs its group to exist first; -U would allocate another
+        Some(gid) => {
+            if !group_exists(runner, target, gid)? {
+                run(
+                    runner,
+                    Command::new("chroot")
+                        .arg(target)
+                        .args(["groupadd", "-g", &gid.to_string(), &user.username]),
+                )?;
+            }
+            useradd.args(["-g", &gid.to_string()]);
+        }
+        None => {
+            useradd.arg("-U");
+        }
+    }
+    if let Some(uid) = user.uid {
+        useradd.args(["-u", &uid.to_string()]);
+    }
+    if !user.shell.is_empty() {
+        useradd.args(["-s", &user.shell]);
+    }
+    if !user.home.is_empty() {
+        useradd.args(["-d", &user.home]);
+    }
+
+    run(runner, useradd.arg(&user.username))?;
+
+    if !user.ssh_keys.is_empty() {
+        write_authorized_keys(runner, target, user)?;
+    }
+
+    Ok(())
+}
+
+/// Write ~/.ssh/authorized_keys with the modes sshd's StrictModes demands,
+/// owned by the account itself. Ownership is applied from inside the target
+/// because the uid is only known to the target's passwd.
+fn write_authorized_keys(runner: &dyn CommandRunner, target: &Path, user: &UserSpec) -> Result<(), Status> {
+    let ssh_dir = home_dir(user).join(".ssh");
+    let host_dir = target.join(ssh_dir.strip_prefix("/").unwrap_or(&ssh_dir));
+    fs::create_dir_all(&host_dir)?;
+    fs::set_permissions(&host_dir, fs::Permissions::from_mode(0o700))?;
+
+    let keys_file = host_dir.join("authorized_keys");
+    let mut contents = user.ssh_keys.join("\n");
+    contents.push('\n');
+    fs::write(&keys_file, contents)?;
+    fs::set_permissions(&keys_file, fs::Permissions::from_mode(0o600))?;
+
+    run(
+        runner,
+        Command::new("chroot")
+            .arg(target)
+            .args(["chown", "-R", &format!("{}:", user.username)])
+            .arg(&ssh_dir),
+    )
+}
+
+/// Restrict sshd to public key authentication and refuse root logins
+pub(super) fn write_sshd_dropin(target: &Path) -> Result<(), Status> {
+    let path = target.join(SSHD_DROPIN);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut contents = String::from("# Written by lichen: key-only access\n");
+    contents.push_str("PasswordAuthentication no\n");
+    contents.push_str("KbdInteractiveAuthentication no\n");
+    contents.push_str("PermitRootLogin no\n");
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// The account's home directory inside the target
+fn home_dir(user: &UserSpec) -> PathBuf {
+    if user.home.is_empty() {
+        Path::new("/home").join(&user.username)
+    } else {
+        PathBuf::from(&user.home)
+    }
+}
+
+/// An absolute path with no `.` or `..` components, repeated or trailing
+/// slashes: one that names the same place however it is joined
+fn is_normal_absolute(path: &str) -> bool {
+    let path = Path::new(path);
+    let components = path.components();
+
+    path.is_absolute()
+        && components
+            .clone()
+            .all(|component| matches!(component, Component::RootDir | Component::Normal(_)))
+        && components.collect::<PathBuf>().as_os_str() == path.as_os_str()
+}
+
+/// A modular crypt string in an accepted scheme, and safe to pass to
+/// chpasswd
+fn is_supported_hash(hash: &str) -> bool {
+    SUPPORTED_HASH_PREFIXES.iter().any(|prefix| hash.starts_with(prefix)) && !hash.contains([':', '\n'])
+}
+
+/// The standard groups, the admin groups when flagged, then any extras
+fn supplementary_groups(user: &UserSpec) -> Vec<&str> {
+    let mut groups = STANDARD_GROUPS.to_vec();
+    if user.admin {
+        groups.extend(ADMIN_GROUPS);
+    }
+    for group in &user.groups {
+        if !groups.contains(&group.as_str()) {
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+/// True if the target already has a group with this gid
+fn group_exists(runner: &dyn CommandRunner, target: &Path, gid: u32) -> Result<bool, Status> {
+    runner
+        .output(
+            Command::new("chroot")
+                .arg(target)
+                .args(["getent", "group", &gid.to_string()]),
+            None,
+        )
+        .map(|output| output.status.success())
+        .map_err(|e| Status::internal(format!("failed to spawn getent: {e}")))
+}
+
+/// useradd's portable name rule: lowercase letters, digits, - and _,
+/// starting with a letter or _, at most 32 characters
+fn is_valid_name(name: &str) -> bool {
+    name.len() <= 32
+        && name
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_ascii_lowercase() || ch == '_')
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_' || ch == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str) -> UserSpec {
+        UserSpec {
+            username: name.to_string(),
+            real_name: "Jane Doe".to_string(),
+            password_hash: "$6$salt$hash".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn validate_rejects_unsafe_accounts() {
+        assert!(validate(&[user("jane"), user("john")], false).is_ok());
+        assert!(validate(&[user("jane"), user("jane")], false).is_err());
+        assert!(validate(&[user("root")], false).is_err());
+        assert!(validate(&[user("Jane")], false).is_err());
+
+        let mut gecos = user("jane");
+        gecos.real_name = "Jane:0:0".to_string();
+        assert!(validate(&[gecos], false).is_err());
+
+        let mut shell = user("jane");
+        shell.shell = "zsh".to_string();
+        assert!(validate(&[shell], false).is_err());
+    }
+
+    #[test]
+    fn system_names_and_ids_are_reserved() {
+        let message = |users: &[UserSpec]| validate(users, false).expect_err("reserved").message().to_string();
+
+        assert_eq!(message(&[user("root")]), "username root is reserved for the system");
+        assert_eq!(message(&[user("wheel")]), "username wheel is reserved for the system");
+        for group in STANDARD_GROUPS.iter().chain(ADMIN_GROUPS) {
+            assert!(RESERVED_USERNAMES.contains(group), "{group} is not reserved");
+        }
+
+        let mut jane = user("jane");
+        jane.uid = Some(1500);
+        jane.gid = Some(1500);
+        assert!(validate(std::slice::from_ref(&jane), false).is_ok());
+
+        for id in [0, 999, 65534, u32::MAX] {
+            jane.uid = Some(id);
+            assert!(message(std::slice::from_ref(&jane)).starts_with(&format!("uid {id} for jane is reserved")));
+        }
+        jane.uid = None;
+        jane.gid = Some(0);
+        assert!(message(&[jane]).starts_with("gid 0 for jane is reserved"));
+    }
+
+    #[test]
+    fn homes_and_shells_cannot_leave_the_target() {
+        let mut jane = user("jane");
+        jane.home = "/srv/jane".to_string();
+        jane.shell = "/usr/bin/zsh".to_string();
+        assert!(validate(std::slice::from_ref(&jane), false).is_ok());
+
+        for home in [
+            "/../../etc",
+            "/home/../etc",
+            "/home/./jane",
+            "/home//jane",
+            "/home/jane/",
+        ] {
+            jane.home = home.to_string();
+            assert!(validate(std::slice::from_ref(&jane), false).is_err(), "{home}");
+        }
+
+        jane.home = String::new();
+        jane.shell = "/usr/bin/../../bin/sh".to_string();
+        assert!(validate(&[jane], false).is_err());
+    }
+
+    #[test]
+    fn only_modern_hash_schemes_are_accepted() {
+        assert!(is_supported_hash("$y$j9T$salt$hash"));
+        assert!(is_supported_hash("$6$rounds=10000$salt$hash"));
+        assert!(!is_supported_hash("$1$salt$md5hash"), "md5-crypt is legacy");
+        assert!(!is_supported_hash("abJnggxhB/yWI"), "DES is legacy");
+        assert!(!is_supported_hash("$6$salt$hash\nroot:x"));
+
+        let mut md5 = user("jane");
+        md5.password_hash = "$1$salt$md5hash".to_string();
+        assert!(validate(&[md5], false).is_err());
+    }
+
+    #[test]
+    fn root_is_only_locked_with_an_administrator() {
+        let mut admin = user("jane");
+        admin.admin = true;
+
+        assert!(validate_root(RootAccount::Password, "$6$salt$hash", &[]).is_ok());
+        assert!(validate_root(RootAccount::Password, "", &[admin.clone()]).is_err());
+        assert!(validate_root(RootAccount::Locked, "", &[user("john")]).is_err());
+        assert!(validate_root(RootAccount::Disabled, "", &[user("john"), admin]).is_ok());
+        assert!(validate_root(RootAccount::Unspecified, "", &[user("john")]).is_err());
+    }
+
+    #[test]
+    fn ssh_keys_are_single_lines_and_required_for_key_only() {
+        assert!(
+            validate(&[user("jane")], true).is_err(),
+            "key-only without keys locks everyone out"
+        );
+
+        let mut jane = user("jane");
+        jane.ssh_keys = vec!["ssh-ed25519 AAAAC3Nza jane@laptop".to_string()];
+        assert!(validate(std::slice::from_ref(&jane), true).is_ok());
+
+        jane.ssh_keys = vec!["from=\"10.0.0.0/8\" ecdsa-sha2-nistp256 AAAAE2Vj".to_string()];
+        assert!(validate(std::slice::from_ref(&jane), false).is_ok());
+
+        jane.ssh_keys = vec!["ssh-ed25519 AAAA\nssh-rsa BBBB".to_string()];
+        assert!(validate(std::slice::from_ref(&jane), false).is_err());
+
+        for line in [
+            "garbage AAAA ssh-foo",
+            "ssh-ed25519",
+            "ssh-ed25519 not-base64!",
+            "some text with ssh-ed25519 AAAAC3Nza in it",
+            "from=\"10.0.0.0/8 ssh-ed25519 AAAAC3Nza",
+        ] {
+            jane.ssh_keys = vec![line.to_string()];
+            assert!(validate(std::slice::from_ref(&jane), false).is_err(), "{line}");
+        }
+
+        jane.ssh_keys = vec!["not a key".to_string()];
+        assert!(validate(&[jane], false).is_err());
+    }
+
+    #[test]
+    fn admins_join_wheel_and_extras_are_appended_once() {
+        let mut jane = user("jane");
+        assert!(!supplementary_groups(&jane).contains(&"wheel"));
+
+        jane.admin = true;
+        jane.groups = vec!["docker".to_string(), "users".to_string()];
+        let groups = supplementary_groups(&jane);
+        assert!(groups.contains(&"wheel"));
+        assert_eq!(groups.last(), Some(&"docker"));
+        assert_eq!(groups.iter().filter(|group| **group == "users").count(), 1);
+    }
+}
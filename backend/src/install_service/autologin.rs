@@ -0,0 +1,246 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Automatic login through whichever display manager the desktop selection
+//! installed. Existing configuration is edited in place, so packaged
+//! settings in the same files survive.
+
+use super::runner::CommandRunner;
+use protocols::lichen::install::{Autologin, DisplayManager};
+use std::{fs, path::Path};
+use tonic::Status;
+
+/// GDM's site configuration
+const GDM_CONFIG: &str = "etc/gdm/custom.conf";
+/// SDDM drop-in for the installer's settings
+const SDDM_DROPIN: &str = "etc/sddm.conf.d/50-lichen-autologin.conf";
+/// greetd configuration used by cosmic-greeter, then its packaged default
+const GREETD_CONFIGS: &[&str] = &["etc/greetd/cosmic-greeter.toml", "usr/share/greetd/cosmic-greeter.toml"];
+/// Session cosmic-greeter starts for a logged in user
+const COSMIC_SESSION: &str = "start-cosmic";
+/// Where desktops install their sessions' .desktop files, Wayland's first
+const SESSION_DIRS: &[&str] = &["usr/share/wayland-sessions", "usr/share/xsessions"];
+
+/// Configure the display manager to log the user straight in
+pub(super) fn configure(runner: &dyn CommandRunner, target: &Path, autologin: &Autologin) -> Result<(), Status> {
+    let user = autologin.user.as_str();
+
+    match autologin.display_manager() {
+        DisplayManager::Gdm => {
+            require_binary(runner, target, &["usr/sbin/gdm", "usr/bin/gdm"], "GDM")?;
+            let contents = fs::read_to_string(target.join(GDM_CONFIG)).unwrap_or_default();
+            let contents = upsert_section(
+                &contents,
+                "daemon",
+                &[("AutomaticLoginEnable", "True"), ("AutomaticLogin", user)],
+                |key, value| format!("{key}={value}"),
+            );
+            write(target, GDM_CONFIG, &contents)
+        }
+        DisplayManager::Sddm => {
+            require_binary(runner, target, &["usr/bin/sddm"], "SDDM")?;
+            // SDDM has no default session to autologin into: name the one the
+            // desktop installed, or leave it to SDDM's last used session
+            let session = installed_session(target);
+            let mut entries = vec![("User", user)];
+            if let Some(session) = &session {
+                entries.push(("Session", session.as_str()));
+            }
+            let contents = upsert_section("", "Autologin", &entries, |key, value| format!("{key}={value}"));
+            write(target, SDDM_DROPIN, &contents)
+        }
+        DisplayManager::CosmicGreeter => {
+            require_binary(runner, target, &["usr/bin/cosmic-greeter"], "cosmic-greeter")?;
+            // greetd has no drop-ins: start from the packaged config, which
+            // carries the greeter's own default_session
+            let contents = match GREETD_CONFIGS
+                .iter()
+                .find_map(|path| fs::read_to_string(target.join(path)).ok())
+            {
+                Some(contents) => contents,
+                None if runner.is_dry_run() => String::new(),
+                None => {
+                    return Err(Status::failed_precondition(
+                        "no greetd configuration for cosmic-greeter on the target",
+                    ));
+                }
+            };
+            let contents = upsert_section(
+                &contents,
+                "initial_session",
+                &[("command", COSMIC_SESSION), ("user", user)],
+                |key, value| format!("{key} = \"{value}\""),
+            );
+            write(target, GREETD_CONFIGS[0], &contents)
+        }
+        DisplayManager::Unspecified => Err(Status::invalid_argument("autologin requires a display manager")),
+    }
+}
+
+/// Fail early when the display manager the frontend expected is not
+/// installed. A dry run installs none to check for.
+fn require_binary(runner: &dyn CommandRunner, target: &Path, candidates: &[&str], name: &str) -> Result<(), Status> {
+    if runner.is_dry_run() || candidates.iter().any(|path| target.join(path).exists()) {
+        Ok(())
+    } else {
+        Err(Status::failed_precondition(format!(
+            "{name} is not installed on the target, cannot configure autologin"
+        )))
+    }
+}
+
+/// The first session the target's desktop installed, by .desktop file name
+fn installed_session(target: &Path) -> Option<String> {
+    SESSION_DIRS.iter().find_map(|dir| {
+        let mut sessions = fs::read_dir(target.join(dir))
+            .ok()?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "desktop" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect::<Vec<_>>();
+        sessions.sort();
+        sessions.into_iter().next()
+    })
+}
+
+/// Write a config file inside the target, creating its directory
+fn write(target: &Path, path: &str, contents: &str) -> Result<(), Status> {
+    let path = target.join(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Set keys within an INI-style `[section]`, replacing existing values,
+/// appending missing keys to the section, and adding the section if absent.
+/// Everything else in the file is kept as is.
+fn upsert_section(contents: &str, section: &str, entries: &[(&str, &str)], format: fn(&str, &str) -> String) -> String {
+    let header = format!("[{section}]");
+    let key_of = |line: &str| line.split_once('=').map(|(key, _)| key.trim().to_string());
+
+    let mut lines = contents.lines().map(str::to_string).collect::<Vec<_>>();
+    let Some(start) = lines.iter().position(|line| line.trim() == header) else {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(header);
+        lines.extend(entries.iter().map(|(key, value)| format(key, value)));
+        return lines.join("\n") + "\n";
+    };
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+
+    let mut missing = Vec::new();
+    for (key, value) in entries {
+        match (start + 1..end).find(|index| key_of(&lines[*index]).as_deref() == Some(*key)) {
+            Some(index) => lines[index] = format(key, value),
+            None => missing.push(format(key, value)),
+        }
+    }
+
+    // Insert after the section's last non-blank line, before any spacing
+    let insert_at = (start + 1..end)
+        .rev()
+        .find(|index| !lines[*index].trim().is_empty())
+        .map_or(start + 1, |index| index + 1);
+    lines.splice(insert_at..insert_at, missing);
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{install_service::runner::Recording, simulate};
+    use std::{env, process};
+
+    fn ini(key: &str, value: &str) -> String {
+        format!("{key}={value}")
+    }
+
+    #[test]
+    fn upsert_keeps_unrelated_settings() {
+        let gdm = "# GDM configuration\n[daemon]\nWaylandEnable=false\nAutomaticLogin=old\n\n[security]\n";
+        let updated = upsert_section(
+            gdm,
+            "daemon",
+            &[("AutomaticLoginEnable", "True"), ("AutomaticLogin", "jane")],
+            ini,
+        );
+
+        assert_eq!(
+            updated,
+            "# GDM configuration\n[daemon]\nWaylandEnable=false\nAutomaticLogin=jane\nAutomaticLoginEnable=True\n\n[security]\n"
+        );
+    }
+
+    #[test]
+    fn upsert_adds_missing_sections() {
+        assert_eq!(
+            upsert_section("", "Autologin", &[("User", "jane")], ini),
+            "[Autologin]\nUser=jane\n"
+        );
+
+        let greetd = "[terminal]\nvt = 1\n\n[default_session]\ncommand = \"cosmic-greeter-start\"\n";
+        let updated = upsert_section(greetd, "initial_session", &[("user", "jane")], |key, value| {
+            format!("{key} = \"{value}\"")
+        });
+        assert!(updated.starts_with(greetd));
+        assert!(updated.ends_with("\n\n[initial_session]\nuser = \"jane\"\n"));
+    }
+
+    #[test]
+    fn sddm_logs_in_to_the_session_the_desktop_installed() {
+        let target = env::temp_dir().join(format!("lichen-sddm-{}", process::id()));
+        let autologin = Autologin {
+            user: "jane".to_string(),
+            display_manager: DisplayManager::Sddm as i32,
+        };
+
+        configure(&simulate::runner(), &target, &autologin).unwrap();
+        assert_eq!(
+            fs::read_to_string(target.join(SDDM_DROPIN)).unwrap(),
+            "[Autologin]\nUser=jane\n"
+        );
+
+        for session in [
+            "usr/share/xsessions/plasmax11.desktop",
+            "usr/share/wayland-sessions/plasma.desktop",
+        ] {
+            write(&target, session, "").unwrap();
+        }
+        configure(&simulate::runner(), &target, &autologin).unwrap();
+        assert_eq!(
+            fs::read_to_string(target.join(SDDM_DROPIN)).unwrap(),
+            "[Autologin]\nUser=jane\nSession=plasma\n"
+        );
+
+        fs::remove_dir_all(target).unwrap();
+    }
+
+    #[test]
+    fn dry_runs_configure_display_managers_they_did_not_install() {
+        let target = env::temp_dir().join(format!("lichen-autologin-{}", process::id()));
+        fs::create_dir_all(&target).unwrap();
+        let autologin = Autologin {
+            user: "jane".to_string(),
+            display_manager: DisplayManager::CosmicGreeter as i32,
+        };
+
+        assert!(configure(&Recording::default(), &target, &autologin).is_err());
+        configure(&simulate::runner(), &target, &autologin).expect("a dry run has nothing to check");
+        let greetd = fs::read_to_string(target.join(GREETD_CONFIGS[0])).unwrap();
+        assert!(greetd.ends_with("[initial_session]\ncommand = \"start-cosmic\"\nuser = \"jane\"\n"));
+
+        fs::remove_dir_all(target).unwrap();
+    }
+}
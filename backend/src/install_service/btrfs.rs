@@ -5,7 +5,7 @@
 //! btrfs specific install handling: the default @/@home subvolume layout.
 
 use super::{blkid, run};
-use crate::install_service::ResolvedMount;
+use crate::install_service::{ResolvedMount, runner::CommandRunner};
 use std::{path::Path, process::Command};
 use tonic::Status;
 
@@ -39,28 +39,34 @@ pub(super) fn expand_subvolumes(mounts: &mut Vec<ResolvedMount>) {
 }
 
 /// True if the device holds a btrfs filesystem.
-pub(super) fn is_btrfs(device: &str) -> Result<bool, Status> {
-    Ok(blkid(device, "TYPE")? == "btrfs")
+pub(super) fn is_btrfs(runner: &dyn CommandRunner, device: &str) -> Result<bool, Status> {
+    Ok(blkid(runner, device, "TYPE")? == "btrfs")
 }
 
 /// Mount a freshly-formatted btrfs root, create the @ and @home subvolumes,
 /// then unmount so the real subvolume mounts can take over.
-pub(super) fn create_subvolumes(target: &Path, device: &str) -> Result<(), Status> {
-    run(Command::new("mount").args(["-o", "subvolid=5"]).arg(device).arg(target))?;
+pub(super) fn create_subvolumes(runner: &dyn CommandRunner, target: &Path, device: &str) -> Result<(), Status> {
+    run(
+        runner,
+        Command::new("mount").args(["-o", "subvolid=5"]).arg(device).arg(target),
+    )?;
 
     let result = (|| -> Result<(), Status> {
         for subvol in [ROOT_SUBVOL, HOME_SUBVOL] {
             let path = target.join(subvol);
             if !path.exists() {
-                run(Command::new("btrfs").args(["subvolume", "create"]).arg(path))?;
+                run(runner, Command::new("btrfs").args(["subvolume", "create"]).arg(path))?;
             }
         }
-        run(Command::new("btrfs")
-            .args(["subvolume", "set-default"])
-            .arg(target.join(ROOT_SUBVOL)))?;
+        run(
+            runner,
+            Command::new("btrfs")
+                .args(["subvolume", "set-default"])
+                .arg(target.join(ROOT_SUBVOL)),
+        )?;
         Ok(())
     })();
 
-    let _ = run(Command::new("umount").arg(target));
+    let _ = run(runner, Command::new("umount").arg(target));
     result
 }
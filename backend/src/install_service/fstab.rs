@@ -0,0 +1,112 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reading an existing installation's fstab back into the filesystems it
+//! mounts, resolved to their devices.
+
+use protocols::lichen::install::ExistingMount;
+use std::{fs, path::PathBuf};
+
+/// One line of an fstab, before its source is resolved to a device
+#[derive(Debug, PartialEq)]
+struct FstabEntry<'a> {
+    source: &'a str,
+    mountpoint: &'a str,
+    fstype: &'a str,
+    subvol: Option<&'a str>,
+}
+
+/// The mounted filesystems of an fstab, skipping swap and pseudo filesystems
+fn parse_fstab(fstab: &str) -> Vec<FstabEntry<'_>> {
+    fstab
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (source, mountpoint, fstype) = (fields.next()?, fields.next()?, fields.next()?);
+            let subvol = fields
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .find_map(|option| option.strip_prefix("subvol="))
+                .map(|subvol| subvol.trim_start_matches('/'));
+
+            mountpoint.starts_with('/').then_some(FstabEntry {
+                source,
+                mountpoint,
+                fstype,
+                subvol,
+            })
+        })
+        .collect()
+}
+
+/// The device an fstab source names, through the udev symlinks
+fn resolve_source(source: &str) -> Option<String> {
+    let link = match source.split_once('=') {
+        Some(("PARTUUID", value)) => PathBuf::from("/dev/disk/by-partuuid").join(value),
+        Some(("UUID", value)) => PathBuf::from("/dev/disk/by-uuid").join(value),
+        Some(("PARTLABEL", value)) => PathBuf::from("/dev/disk/by-partlabel").join(value),
+        Some(("LABEL", value)) => PathBuf::from("/dev/disk/by-label").join(value),
+        Some(_) => return None,
+        None => PathBuf::from(source),
+    };
+
+    fs::canonicalize(link).ok().map(|device| device.display().to_string())
+}
+
+/// The filesystems an fstab mounts. Every source must resolve, as a partial
+/// picture of an installation is not one it can be mounted from.
+pub(super) fn mounts(fstab: &str) -> Option<Vec<ExistingMount>> {
+    parse_fstab(fstab)
+        .into_iter()
+        .map(|entry| {
+            Some(ExistingMount {
+                device: resolve_source(entry.source)?,
+                mountpoint: entry.mountpoint.to_string(),
+                fstype: entry.fstype.to_string(),
+                subvol: entry.subvol.map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_installers_fstab() {
+        let fstab = "# /etc/fstab: static filesystem information.\n\
+                     PARTUUID=aaaa / btrfs defaults,subvol=@ 0 0\n\
+                     PARTUUID=bbbb /efi vfat defaults,umask=0077 0 0\n\
+                     PARTUUID=aaaa /home btrfs defaults,subvol=/@home 0 0\n\
+                     /dev/sda4 none swap sw 0 0\n";
+
+        assert_eq!(
+            parse_fstab(fstab),
+            [
+                FstabEntry {
+                    source: "PARTUUID=aaaa",
+                    mountpoint: "/",
+                    fstype: "btrfs",
+                    subvol: Some("@"),
+                },
+                FstabEntry {
+                    source: "PARTUUID=bbbb",
+                    mountpoint: "/efi",
+                    fstype: "vfat",
+                    subvol: None,
+                },
+                FstabEntry {
+                    source: "PARTUUID=aaaa",
+                    mountpoint: "/home",
+                    fstype: "btrfs",
+                    subvol: Some("@home"),
+                },
+            ]
+        );
+    }
+}
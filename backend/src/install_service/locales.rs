@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Locale availability on the target: the live medium's `localectl
+//! list-locales` says nothing about what `moss sync` put on the target, and a
+//! locale.conf naming a missing locale silently leaves every session in C.
+
+use super::{run, runner::CommandRunner};
+use std::{path::Path, process::Command};
+use tonic::Status;
+use tracing::info;
+
+/// Make sure the locale exists on the target, compiling it with localedef
+/// from the target's own i18n sources when it was not shipped prebuilt.
+pub(super) fn ensure_available(runner: &dyn CommandRunner, target: &Path, locale: &str) -> Result<(), Status> {
+    if is_available(runner, target, locale)? {
+        return Ok(());
+    }
+
+    info!(locale, "Locale missing from the target, compiling with localedef");
+
+    let (input, charmap) = localedef_sources(locale);
+    let mut localedef = Command::new("chroot");
+    localedef.arg(target).args(["localedef", "-i", &input]);
+    if let Some(charmap) = &charmap {
+        localedef.args(["-f", charmap]);
+    }
+    localedef.arg(locale);
+
+    run(runner, &mut localedef).map_err(|e| {
+        Status::failed_precondition(format!(
+            "locale {locale} is not available on the target and could not be compiled: {}",
+            e.message()
+        ))
+    })?;
+
+    // localedef exits zero on some warnings without writing anything usable
+    if !is_available(runner, target, locale)? {
+        return Err(Status::failed_precondition(format!(
+            "locale {locale} is still unavailable on the target after localedef"
+        )));
+    }
+
+    Ok(())
+}
+
+/// True if `locale -a` inside the target lists the locale
+fn is_available(runner: &dyn CommandRunner, target: &Path, locale: &str) -> Result<bool, Status> {
+    // A dry run installs nothing for `locale -a` to list
+    if runner.is_dry_run() {
+        return Ok(true);
+    }
+
+    let output = runner
+        .output(Command::new("chroot").arg(target).args(["locale", "-a"]), None)
+        .map_err(|e| Status::internal(format!("failed to spawn locale -a: {e}")))?;
+
+    if !output.status.success() {
+        return Err(Status::internal("locale -a failed on the target"));
+    }
+
+    let wanted = normalize(locale);
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|line| normalize(line.trim()) == wanted))
+}
+
+/// glibc normalizes the codeset when listing: "en_US.UTF-8" is reported as
+/// "en_US.utf8", so compare in that form
+fn normalize(locale: &str) -> String {
+    let (name, modifier) = match locale.split_once('@') {
+        Some((name, modifier)) => (name, Some(modifier)),
+        None => (locale, None),
+    };
+    let mut normalized = match name.split_once('.') {
+        Some((base, codeset)) => {
+            let codeset = codeset
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>()
+                .to_ascii_lowercase();
+            format!("{base}.{codeset}")
+        }
+        None => name.to_string(),
+    };
+
+    if let Some(modifier) = modifier {
+        normalized.push('@');
+        normalized.push_str(modifier);
+    }
+
+    normalized
+}
+
+/// The localedef input definition and charmap for a locale name:
+/// "de_DE.UTF-8@euro" is built from the "de_DE@euro" definition and the
+/// "UTF-8" charmap.
+fn localedef_sources(locale: &str) -> (String, Option<String>) {
+    let (name, modifier) = match locale.split_once('@') {
+        Some((name, modifier)) => (name, Some(modifier)),
+        None => (locale, None),
+    };
+    let (base, charmap) = match name.split_once('.') {
+        Some((base, codeset)) => (base, Some(codeset.to_string())),
+        None => (name, None),
+    };
+
+    let input = match modifier {
+        Some(modifier) => format!("{base}@{modifier}"),
+        None => base.to_string(),
+    };
+
+    (input, charmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_matches_locale_listing() {
+        assert_eq!(normalize("en_US.UTF-8"), normalize("en_US.utf8"));
+        assert_eq!(normalize("de_DE.UTF-8@euro"), "de_DE.utf8@euro");
+        assert_eq!(normalize("C"), "C");
+    }
+
+    #[test]
+    fn localedef_sources_split_modifier_and_charmap() {
+        assert_eq!(
+            localedef_sources("de_DE.UTF-8@euro"),
+            ("de_DE@euro".to_string(), Some("UTF-8".to_string()))
+        );
+        assert_eq!(
+            localedef_sources("en_GB.UTF-8"),
+            ("en_GB".to_string(), Some("UTF-8".to_string()))
+        );
+        assert_eq!(localedef_sources("en_US"), ("en_US".to_string(), None));
+    }
+}
@@ -0,0 +1,129 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Describing what is already on the attached disks, so a frontend can name
+//! the operating systems it would overwrite.
+
+use super::{probe_read, runner::CommandRunner};
+use crate::simulate;
+use nix::sys::statvfs::statvfs;
+use protocols::lichen::install::ProbedPartition;
+use std::{fs, path::Path, process::Command};
+use tonic::Status;
+
+/// Partition type of an EFI System Partition: the GPT type GUID, or the MBR id
+const ESP_TYPES: &[&str] = &["c12a7328-f81f-11d2-ba4b-00a0c93ec93b", "0xef"];
+
+/// Where an installation's root may sit inside a filesystem: its top level,
+/// or the @ subvolume of a btrfs layout
+const ROOT_CANDIDATES: &[&str] = &["", "@"];
+
+/// Probe every partition. Already mounted ones are described from blkid
+/// alone; the rest are mounted read-only to look inside.
+pub(super) fn probe_disks(runner: &dyn CommandRunner) -> Result<Vec<ProbedPartition>, Status> {
+    let mounted = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    let mut partitions = Vec::new();
+
+    for device in &simulate::block_devices()? {
+        for partition in device.partitions() {
+            let node = partition.device.display().to_string();
+            let mut probed = ProbedPartition {
+                device: node.clone(),
+                fstype: tag(runner, &node, "TYPE", false),
+                label: tag(runner, &node, "LABEL", false),
+                uuid: tag(runner, &node, "UUID", false),
+                esp: tag(runner, &node, "PART_ENTRY_TYPE", true)
+                    .is_some_and(|kind| ESP_TYPES.contains(&kind.to_lowercase().as_str())),
+                mounted: mounted.lines().any(|line| line.starts_with(&format!("{node} "))),
+                ..Default::default()
+            };
+
+            if !probed.mounted && probed.fstype.is_some() {
+                probe_read(runner, &node, |root| look_inside(root, &mut probed));
+            }
+            partitions.push(probed);
+        }
+    }
+
+    Ok(partitions)
+}
+
+/// Fill in space, operating system and loader entries from a mounted filesystem
+fn look_inside(root: &Path, probed: &mut ProbedPartition) {
+    if let Ok(stat) = statvfs(root) {
+        let fragment = stat.fragment_size() as u64;
+        probed.used_bytes = Some((stat.blocks() as u64 - stat.blocks_free() as u64) * fragment);
+        probed.free_bytes = Some(stat.blocks_available() as u64 * fragment);
+    }
+
+    probed.os = ROOT_CANDIDATES
+        .iter()
+        .flat_map(|candidate| ["etc/os-release", "usr/lib/os-release"].map(|path| root.join(candidate).join(path)))
+        .find_map(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| os_name(&contents))
+        .or_else(|| {
+            if root.join("Windows/System32/ntoskrnl.exe").exists() {
+                Some("Windows".to_string())
+            } else if root.join("EFI/Microsoft/Boot/bootmgfw.efi").exists() {
+                Some("Windows Boot Manager".to_string())
+            } else {
+                None
+            }
+        });
+
+    if let Ok(entries) = fs::read_dir(root.join("loader/entries")) {
+        probed.loader_entries = entries
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".conf"))
+            .collect();
+        probed.loader_entries.sort();
+    }
+}
+
+/// The name os-release gives the system, preferring its PRETTY_NAME
+fn os_name(os_release: &str) -> Option<String> {
+    let field = |key: &str| {
+        os_release.lines().find_map(|line| {
+            let value = line.trim().strip_prefix(key)?.strip_prefix('=')?;
+            let value = value.trim_matches(['"', '\'']);
+            (!value.is_empty()).then(|| value.to_string())
+        })
+    };
+
+    field("PRETTY_NAME").or_else(|| field("NAME"))
+}
+
+/// A blkid tag of the partition, unset when it has none. Partition table
+/// tags such as PART_ENTRY_TYPE are only reported by a low-level probe.
+fn tag(runner: &dyn CommandRunner, device: &str, tag: &str, low_level: bool) -> Option<String> {
+    let mut command = Command::new("blkid");
+    if low_level {
+        command.arg("-p");
+    }
+    command.args(["-c", "/dev/null", "-s", tag, "-o", "value"]).arg(device);
+    let output = runner.output(&mut command, None).ok()?;
+
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn os_name_prefers_pretty_name() {
+        let fedora = "NAME=\"Fedora Linux\"\nVERSION_ID=41\nPRETTY_NAME=\"Fedora Linux 41 (Workstation Edition)\"\n";
+        assert_eq!(
+            os_name(fedora).as_deref(),
+            Some("Fedora Linux 41 (Workstation Edition)")
+        );
+
+        let minimal = "NAME=AerynOS\nID=aerynos\n";
+        assert_eq!(os_name(minimal).as_deref(), Some("AerynOS"));
+
+        assert_eq!(os_name("ID=unknown\n"), None);
+    }
+}
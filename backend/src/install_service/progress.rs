@@ -0,0 +1,299 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Progress of a streamed install or repair
+//!
+//! An operation moves through a fixed list of stages. Every update names the
+//! stage, its step among the operation's stages and, where moss reports it,
+//! how far through the stage the operation is. Keep-alive ticks repeat the
+//! current stage with an empty message, so the stream never idles while moss
+//! is quiet.
+
+use protocols::lichen::install::{InstallProgress, InstallStage, ProgressLevel};
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+/// The stages of an install. moss writes the boot entries as part of the
+/// sync, before the target is configured.
+pub(super) const INSTALL_STAGES: &[InstallStage] = &[
+    InstallStage::Mounting,
+    InstallStage::RepoRefresh,
+    InstallStage::PackageFetch,
+    InstallStage::PackageInstall,
+    InstallStage::Bootloader,
+    InstallStage::Configure,
+    InstallStage::Unmount,
+];
+
+/// The stages of a repair, which regenerates boot entries last
+pub(super) const REPAIR_STAGES: &[InstallStage] = &[
+    InstallStage::Mounting,
+    InstallStage::RepoRefresh,
+    InstallStage::PackageFetch,
+    InstallStage::PackageInstall,
+    InstallStage::Configure,
+    InstallStage::Bootloader,
+    InstallStage::Unmount,
+];
+
+/// Reports an operation's progress to its stream
+pub(super) struct Progress {
+    stages: &'static [InstallStage],
+    current: Mutex<InstallStage>,
+    tx: mpsc::Sender<Result<InstallProgress, Status>>,
+}
+
+impl Progress {
+    /// A reporter whose updates go nowhere
+    #[cfg(test)]
+    pub fn detached(stages: &'static [InstallStage]) -> Self {
+        Self {
+            stages,
+            current: Mutex::new(InstallStage::Unspecified),
+            tx: mpsc::channel(1).0,
+        }
+    }
+
+    /// Move on to `stage`, announcing it with `message`
+    pub fn stage(&self, stage: InstallStage, message: impl Into<String>) {
+        *self.current.lock().unwrap() = stage;
+        self.send(ProgressLevel::Info, message.into(), None);
+    }
+
+    /// Report something that went wrong without failing the operation
+    pub fn warn(&self, message: impl Into<String>) {
+        self.send(ProgressLevel::Warning, message.into(), None);
+    }
+
+    /// Report a line of raw command output
+    pub fn debug(&self, message: impl Into<String>) {
+        self.send(ProgressLevel::Debug, message.into(), None);
+    }
+
+    /// Report a line of moss output, following the stage and fraction it names
+    pub fn moss(&self, line: String) {
+        let (stage, fraction) = moss_line(&line);
+        if let Some(stage) = stage {
+            self.reach(stage);
+        }
+        self.send(ProgressLevel::Info, line, fraction);
+    }
+
+    /// Move on to `stage` only if it comes later than the current one: moss
+    /// output is matched loosely, and must never send progress backwards.
+    /// Stages after configuration are the backend's own, as a repair's boot
+    /// entries are, so moss mentioning them does not jump ahead.
+    fn reach(&self, stage: InstallStage) {
+        let mut current = self.current.lock().unwrap();
+        if self.step(stage) > self.step(*current) && self.step(stage) < self.step(InstallStage::Configure) {
+            *current = stage;
+        }
+    }
+
+    /// Position of a stage among the operation's, from 1; 0 if it has none
+    fn step(&self, stage: InstallStage) -> u32 {
+        self.stages
+            .iter()
+            .position(|candidate| *candidate == stage)
+            .map_or(0, |index| index as u32 + 1)
+    }
+
+    fn update(&self, level: ProgressLevel, message: String, fraction: Option<f64>) -> InstallProgress {
+        let stage = *self.current.lock().unwrap();
+        InstallProgress {
+            message,
+            finished: false,
+            stage: stage as i32,
+            step: self.step(stage),
+            total_steps: self.stages.len() as u32,
+            fraction,
+            level: level as i32,
+        }
+    }
+
+    fn send(&self, level: ProgressLevel, message: String, fraction: Option<f64>) {
+        let _ = self.tx.blocking_send(Ok(self.update(level, message, fraction)));
+    }
+}
+
+/// Run `work` on its own thread, streaming its progress through `stages` and
+/// then `complete` as the final update, or the error it failed with
+pub(super) fn stream(
+    complete: &'static str,
+    stages: &'static [InstallStage],
+    work: impl FnOnce(&Progress) -> Result<(), Status> + Send + 'static,
+) -> ReceiverStream<Result<InstallProgress, Status>> {
+    let (tx, rx) = mpsc::channel(64);
+    let progress = Arc::new(Progress {
+        stages,
+        current: Mutex::new(InstallStage::Unspecified),
+        tx,
+    });
+    let done = Arc::new(AtomicBool::new(false));
+
+    // Keep-alive ticks so the stream never idles, even while moss is quiet
+    {
+        let progress = progress.clone();
+        let done = done.clone();
+
+        thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_secs(10));
+
+                let update = progress.update(ProgressLevel::Debug, String::new(), None);
+                if progress.tx.blocking_send(Ok(update)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    thread::spawn(move || {
+        let result = work(&progress);
+
+        done.store(true, Ordering::Relaxed);
+        match result {
+            Ok(()) => {
+                let _ = progress.tx.blocking_send(Ok(InstallProgress {
+                    finished: true,
+                    step: stages.len() as u32,
+                    fraction: Some(1.0),
+                    ..progress.update(ProgressLevel::Info, complete.to_string(), None)
+                }));
+            }
+            Err(status) => {
+                progress.send(ProgressLevel::Error, status.message().to_string(), None);
+                let _ = progress.tx.blocking_send(Err(status));
+            }
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
+
+/// The stage a line of moss output belongs to and how far through it moss
+/// is, as far as either can be told
+fn moss_line(line: &str) -> (Option<InstallStage>, Option<f64>) {
+    let lower = line.to_lowercase();
+    let mentions = |words: &[&str]| words.iter().any(|word| lower.contains(word));
+
+    let stage = if mentions(&["fetch", "download"]) {
+        Some(InstallStage::PackageFetch)
+    } else if mentions(&["install", "unpack", "extract", "blit"]) {
+        Some(InstallStage::PackageInstall)
+    } else if mentions(&["boot", "blsforme"]) {
+        Some(InstallStage::Bootloader)
+    } else {
+        None
+    };
+
+    (stage, fraction(line))
+}
+
+/// How far along a line says it is: a `done/total` count such as "(3/120)",
+/// sizes such as "12.5 MiB / 340 MiB", or a percentage
+fn fraction(line: &str) -> Option<f64> {
+    let counted = line.match_indices('/').find_map(|(at, _)| {
+        let done = quantity_before(&line[..at])?;
+        let total = quantity_after(&line[at + 1..])?;
+        (total > 0.0 && done <= total).then(|| done / total)
+    });
+
+    counted.or_else(|| {
+        let at = line.find('%')?;
+        let percent = quantity_before(&line[..at])?;
+        (percent <= 100.0).then(|| percent / 100.0)
+    })
+}
+
+/// The number, with any size unit, that `text` ends with
+fn quantity_before(text: &str) -> Option<f64> {
+    let text = text.trim_end();
+    let rest = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = &text[rest.len()..];
+
+    let rest = rest.trim_end();
+    let number = &rest[rest.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.').len()..];
+
+    Some(number.parse::<f64>().ok()? * scale(unit))
+}
+
+/// The number, with any size unit, that `text` starts with
+fn quantity_after(text: &str) -> Option<f64> {
+    let text = text.trim_start();
+    let rest = text.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let number = &text[..text.len() - rest.len()];
+
+    let rest = rest.trim_start();
+    let unit = &rest[..rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_alphabetic()).len()];
+
+    Some(number.parse::<f64>().ok()? * scale(unit))
+}
+
+/// Bytes per size unit. A word that merely follows a count, such as
+/// "packages", is no unit at all.
+fn scale(unit: &str) -> f64 {
+    match unit {
+        "KiB" | "KB" | "kB" | "K" => 1024.0,
+        "MiB" | "MB" | "M" => 1024.0 * 1024.0,
+        "GiB" | "GB" | "G" => 1024.0 * 1024.0 * 1024.0,
+        _ => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moss_lines_give_stage_and_fraction() {
+        assert_eq!(
+            moss_line("Fetching glibc (3/12)"),
+            (Some(InstallStage::PackageFetch), Some(0.25))
+        );
+        assert_eq!(
+            moss_line("Downloading 12.5 MiB / 50 MiB"),
+            (Some(InstallStage::PackageFetch), Some(0.25))
+        );
+        assert_eq!(
+            moss_line("Installing systemd-boot 40%"),
+            (Some(InstallStage::PackageInstall), Some(0.4))
+        );
+        assert_eq!(
+            moss_line("Synchronizing boot entries"),
+            (Some(InstallStage::Bootloader), None)
+        );
+
+        // Paths and counts that cannot be a fraction are not taken for one
+        assert_eq!(fraction("Writing /etc/fstab"), None);
+        assert_eq!(fraction("12 MiB/3"), None);
+        assert_eq!(fraction("resolved 40 packages"), None);
+    }
+
+    #[test]
+    fn moss_only_leads_the_stages_before_configuration() {
+        let boot = "Synchronizing boot entries".to_string();
+
+        let install = Progress::detached(INSTALL_STAGES);
+        install.stage(InstallStage::PackageFetch, "Installing packages");
+        install.moss(boot.clone());
+        assert_eq!(install.update(ProgressLevel::Info, String::new(), None).step, 5);
+
+        let repair = Progress::detached(REPAIR_STAGES);
+        repair.stage(InstallStage::PackageFetch, "Installing packages");
+        repair.moss(boot);
+        assert_eq!(repair.update(ProgressLevel::Info, String::new(), None).step, 3);
+        repair.stage(InstallStage::Configure, "Regenerating fstab");
+        assert_eq!(repair.update(ProgressLevel::Info, String::new(), None).step, 5);
+    }
+}
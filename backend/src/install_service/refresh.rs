@@ -0,0 +1,338 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Refreshing a previous installation in place: its root and boot
+//! filesystems are recreated while /home, on its own partition or as the
+//! btrfs @home subvolume, is kept along with the accounts' files. Any other
+//! filesystem, such as a /srv partition, is left alone and mounted again.
+
+use super::{btrfs, fstab, run, runner::CommandRunner};
+use crate::simulate;
+use protocols::{
+    REFRESHED_MOUNTPOINTS,
+    lichen::install::{ExistingMount, HomeOwner},
+};
+use std::{fs, os::unix::fs::MetadataExt, path::Path, process::Command};
+use tonic::Status;
+
+/// Where a kept /home is briefly mounted read-only to read its owners
+const HOME_PROBE_MOUNT: &str = "/run/lichen/probe-home";
+/// Where an EFI system partition is briefly mounted read-only to look for
+/// other operating systems' loaders
+const ESP_PROBE_MOUNT: &str = "/run/lichen/probe-esp";
+/// Directories under EFI/ that an installation of ours writes: the
+/// fallback loader, systemd-boot and unified kernel images
+const OWN_LOADERS: &[&str] = &["boot", "systemd", "linux"];
+
+/// The filesystems an installation's fstab mounts, if its /home could be
+/// kept through a refresh
+pub(super) fn existing_mounts(contents: &str) -> Option<Vec<ExistingMount>> {
+    let mounts = fstab::mounts(contents)?;
+    keepable_home(&mounts).ok()?;
+    Some(mounts)
+}
+
+/// The root and /home mounts, when /home survives recreating everything
+/// else: on a partition of its own, or as @home beside an @ root
+fn keepable_home(mounts: &[ExistingMount]) -> Result<(&ExistingMount, &ExistingMount), Status> {
+    let find = |mountpoint: &str| mounts.iter().find(|mount| mount.mountpoint == mountpoint);
+    let root = find("/").ok_or_else(|| Status::failed_precondition("the installation has no root filesystem"))?;
+    let home = find("/home").ok_or_else(|| Status::failed_precondition("the installation has no separate /home"))?;
+
+    // Targets are mounted without subvolume options, apart from the @/@home
+    // layout derived for a btrfs root, so no other subvolume can be mounted
+    let derived = |mount: &ExistingMount| match mount.mountpoint.as_str() {
+        "/" => mount.subvol.as_deref() == Some(btrfs::ROOT_SUBVOL),
+        "/home" => mount.device == root.device && mount.subvol.as_deref() == Some(btrfs::HOME_SUBVOL),
+        _ => false,
+    };
+    if let Some(mount) = mounts.iter().find(|mount| mount.subvol.is_some() && !derived(mount)) {
+        return Err(Status::failed_precondition(format!(
+            "{} is mounted from a btrfs subvolume the installer does not create",
+            mount.mountpoint
+        )));
+    }
+
+    let shares_home = mounts
+        .iter()
+        .any(|mount| mount.mountpoint != "/home" && mount.device == home.device);
+    if shares_home && !derived(home) {
+        return Err(Status::failed_precondition(format!(
+            "/home on {} shares its filesystem with other mounts, so it cannot be kept",
+            home.device
+        )));
+    }
+
+    Ok((root, home))
+}
+
+/// Read who owns each directory in the kept /home, mounted read-only
+pub(super) fn home_owners(runner: &dyn CommandRunner, home: &ExistingMount) -> Result<Vec<HomeOwner>, Status> {
+    let probe = &simulate::path(HOME_PROBE_MOUNT);
+    fs::create_dir_all(probe)?;
+
+    let mut options = String::from("ro");
+    if let Some(subvol) = &home.subvol {
+        options.push_str(&format!(",subvol={subvol}"));
+    }
+    run(
+        runner,
+        Command::new("mount")
+            .args(["-o", &options])
+            .arg(&home.device)
+            .arg(probe),
+    )?;
+
+    let owners = (|| -> Result<Vec<HomeOwner>, Status> {
+        let mut owners = Vec::new();
+        for entry in fs::read_dir(probe)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() && metadata.uid() != 0 {
+                owners.push(HomeOwner {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    uid: metadata.uid(),
+                    gid: metadata.gid(),
+                });
+            }
+        }
+        owners.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(owners)
+    })();
+
+    let _ = run(runner, Command::new("umount").arg(probe));
+    owners
+}
+
+/// DESTRUCTIVE: recreate the root and boot filesystems of the installation.
+/// A btrfs root sharing its filesystem with @home has only its @ subvolume
+/// recreated; anything else is formatted again with the type it had. An EFI
+/// system partition holding another operating system's loader is refused.
+pub(super) fn refresh(runner: &dyn CommandRunner, target: &Path, mounts: &[ExistingMount]) -> Result<(), Status> {
+    let (root, home) = keepable_home(mounts)?;
+
+    for mount in mounts {
+        if !runner.device_exists(Path::new(&mount.device)) {
+            return Err(Status::not_found(format!("no such device: {}", mount.device)));
+        }
+    }
+
+    let recreated = recreated(mounts, home)?;
+    for mount in recreated.iter().filter(|mount| mount.fstype == "vfat") {
+        let foreign = foreign_loaders(&esp_loaders(runner, mount)?);
+        if !foreign.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "the EFI system partition {} also boots {}, which recreating it would erase",
+                mount.device,
+                foreign.join(", ")
+            )));
+        }
+    }
+
+    let mut formatted: Vec<&str> = Vec::new();
+    for mount in recreated {
+        // btrfs subvolumes put several mounts on one filesystem
+        if !formatted.contains(&mount.device.as_str()) {
+            run(runner, &mut mkfs(&mount.device, &mount.fstype)?)?;
+            formatted.push(&mount.device);
+        }
+    }
+
+    if root.device == home.device {
+        recreate_root_subvolume(runner, target, root)?;
+    }
+
+    Ok(())
+}
+
+/// The mounts whose filesystems a refresh formats: root and boot, unless
+/// the root is a btrfs filesystem shared with @home. Anything else kept must
+/// not live on one of them, or it would be erased all the same.
+fn recreated<'a>(mounts: &'a [ExistingMount], home: &ExistingMount) -> Result<Vec<&'a ExistingMount>, Status> {
+    let (recreated, kept): (Vec<_>, Vec<_>) = mounts
+        .iter()
+        .partition(|mount| REFRESHED_MOUNTPOINTS.contains(&mount.mountpoint.as_str()));
+    let recreated = recreated
+        .into_iter()
+        .filter(|mount| mount.device != home.device)
+        .collect::<Vec<_>>();
+
+    if let Some((mount, shared)) = kept.iter().find_map(|mount| {
+        recreated
+            .iter()
+            .find(|other| other.device == mount.device)
+            .map(|shared| (mount, shared))
+    }) {
+        return Err(Status::failed_precondition(format!(
+            "{} shares its filesystem with {}, which is recreated",
+            mount.mountpoint, shared.mountpoint
+        )));
+    }
+
+    Ok(recreated)
+}
+
+/// The directory names under EFI/ on an EFI system partition, read with it
+/// mounted read-only
+fn esp_loaders(runner: &dyn CommandRunner, esp: &ExistingMount) -> Result<Vec<String>, Status> {
+    let probe = &simulate::path(ESP_PROBE_MOUNT);
+    fs::create_dir_all(probe)?;
+    run(
+        runner,
+        Command::new("mount").args(["-o", "ro"]).arg(&esp.device).arg(probe),
+    )?;
+
+    let loaders = fs::read_dir(probe.join("EFI"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let _ = run(runner, Command::new("umount").arg(probe));
+    Ok(loaders)
+}
+
+/// The loaders that are not ours; FAT is case-insensitive
+fn foreign_loaders(loaders: &[String]) -> Vec<String> {
+    loaders
+        .iter()
+        .filter(|loader| !OWN_LOADERS.contains(&loader.to_lowercase().as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Delete the root subvolume, nested subvolumes and all, then create the
+/// @/@home layout again around the kept @home
+fn recreate_root_subvolume(runner: &dyn CommandRunner, target: &Path, root: &ExistingMount) -> Result<(), Status> {
+    fs::create_dir_all(target)?;
+    run(
+        runner,
+        Command::new("mount")
+            .args(["-o", "subvolid=5"])
+            .arg(&root.device)
+            .arg(target),
+    )?;
+
+    let deleted = run(
+        runner,
+        Command::new("btrfs")
+            .args(["subvolume", "delete", "--recursive"])
+            .arg(target.join(btrfs::ROOT_SUBVOL)),
+    );
+
+    let _ = run(runner, Command::new("umount").arg(target));
+    deleted?;
+
+    btrfs::create_subvolumes(runner, target, &root.device)
+}
+
+/// A forced mkfs for the filesystem type, as blkid names it
+fn mkfs(device: &str, fstype: &str) -> Result<Command, Status> {
+    let (program, args): (&str, &[&str]) = match fstype {
+        "vfat" => ("mkfs.fat", &["-F", "32"]),
+        "ext4" => ("mkfs.ext4", &["-F"]),
+        "xfs" => ("mkfs.xfs", &["-f"]),
+        "btrfs" => ("mkfs.btrfs", &["-f"]),
+        "f2fs" => ("mkfs.f2fs", &["-f"]),
+        "bcachefs" => ("bcachefs", &["format", "-f"]),
+        _ => {
+            return Err(Status::failed_precondition(format!(
+                "cannot recreate {fstype} on {device}"
+            )));
+        }
+    };
+
+    let mut command = Command::new(program);
+    command.args(args).arg(device);
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(device: &str, mountpoint: &str, fstype: &str, subvol: Option<&str>) -> ExistingMount {
+        ExistingMount {
+            device: device.to_string(),
+            mountpoint: mountpoint.to_string(),
+            fstype: fstype.to_string(),
+            subvol: subvol.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn home_is_kept_only_when_separate_from_root() {
+        let partition = [
+            mount("/dev/sda2", "/", "xfs", None),
+            mount("/dev/sda3", "/home", "xfs", None),
+        ];
+        assert!(keepable_home(&partition).is_ok());
+
+        let subvolumes = [
+            mount("/dev/sda2", "/", "btrfs", Some("@")),
+            mount("/dev/sda2", "/home", "btrfs", Some("@home")),
+        ];
+        assert!(keepable_home(&subvolumes).is_ok());
+
+        let directory = [
+            mount("/dev/sda2", "/", "xfs", None),
+            mount("/dev/sda2", "/home", "xfs", None),
+        ];
+        assert!(keepable_home(&directory).is_err());
+
+        let rootless = [mount("/dev/sda3", "/home", "xfs", None)];
+        assert!(keepable_home(&rootless).is_err());
+
+        let foreign = [
+            mount("/dev/sda2", "/", "btrfs", Some("root")),
+            mount("/dev/sda2", "/home", "btrfs", Some("home")),
+        ];
+        assert!(
+            keepable_home(&foreign).is_err(),
+            "only the installer's layout is mounted again"
+        );
+    }
+
+    #[test]
+    fn only_root_and_boot_are_recreated() {
+        let mounts = [
+            mount("/dev/sda1", "/boot/efi", "vfat", None),
+            mount("/dev/sda2", "/", "xfs", None),
+            mount("/dev/sda3", "/home", "xfs", None),
+            mount("/dev/sdb1", "/srv", "ext4", None),
+        ];
+        let recreated = recreated(&mounts, &mounts[2]).unwrap();
+        assert_eq!(
+            recreated
+                .iter()
+                .map(|mount| mount.mountpoint.as_str())
+                .collect::<Vec<_>>(),
+            ["/boot/efi", "/"]
+        );
+
+        let subvolumes = [
+            mount("/dev/sda2", "/", "btrfs", Some("@")),
+            mount("/dev/sda2", "/home", "btrfs", Some("@home")),
+        ];
+        assert!(recreated(&subvolumes, &subvolumes[1]).unwrap().is_empty());
+
+        let shared = [
+            mount("/dev/sda2", "/", "ext4", None),
+            mount("/dev/sda2", "/srv", "ext4", None),
+            mount("/dev/sda3", "/home", "ext4", None),
+        ];
+        assert!(recreated(&shared, &shared[2]).is_err());
+    }
+
+    #[test]
+    fn other_systems_loaders_are_found_on_the_esp() {
+        let loaders = ["BOOT", "systemd", "Linux", "Microsoft"].map(str::to_string);
+        assert_eq!(foreign_loaders(&loaders), ["Microsoft"]);
+        assert!(foreign_loaders(&loaders[..3]).is_empty());
+    }
+}
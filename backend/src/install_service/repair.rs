@@ -0,0 +1,211 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Repairing an existing installation in place: it is mounted as its own
+//! fstab mounts it and synced against its recorded system-model again. An
+//! fstab being regenerated is not trusted: the root and boot mounts are
+//! derived from the root device and its disk instead.
+
+use super::{
+    ResolvedMount, TARGET_MOUNT, blkid, btrfs, discover_models, fstab, probe_read, progress::Progress,
+    regenerate_machine_id, run, runner::CommandRunner, sync_packages, with_target, write_fstab,
+};
+use crate::simulate;
+use protocols::lichen::install::{InstallStage, RepairRequest};
+use std::{fs, process::Command};
+use tonic::Status;
+
+/// GPT type of an EFI system partition
+const ESP_TYPE: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+/// GPT type of an extended boot loader partition
+const XBOOTLDR_TYPE: &str = "bc13c2ff-59e6-4262-a352-b275fd6f7172";
+
+/// The installation's filesystems. They are read from the fstab on its root
+/// device, or derived from the device when the fstab is to be regenerated,
+/// keeping whatever else the fstab mounts if it can still be read. Only a
+/// root a previous installation left a model on is accepted.
+pub(super) fn installation_mounts(
+    runner: &dyn CommandRunner,
+    root_device: &str,
+    regenerate_fstab: bool,
+) -> Result<Vec<ResolvedMount>, Status> {
+    if !discover_models(runner)?.iter().any(|model| model.device == root_device) {
+        return Err(Status::not_found(format!("no installation found on {root_device}")));
+    }
+
+    let intact = probe_read(runner, root_device, |probe| {
+        fs::read_to_string(probe.join("etc/fstab")).ok()
+    })
+    .flatten()
+    .and_then(|contents| fstab::mounts(&contents))
+    .filter(|mounts| mounts.iter().any(|mount| mount.mountpoint == "/"))
+    .map(|mounts| {
+        mounts
+            .into_iter()
+            .map(|mount| ResolvedMount {
+                device: mount.device,
+                mountpoint: mount.mountpoint,
+                fstype: mount.fstype,
+                subvol: mount.subvol,
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut mounts = match (regenerate_fstab, intact) {
+        (false, Some(mounts)) => mounts,
+        (false, None) => {
+            return Err(Status::failed_precondition(format!(
+                "the fstab on {root_device} is missing, has no root filesystem or names one that was not found; \
+                 regenerate it instead"
+            )));
+        }
+        (true, intact) => {
+            let mut mounts = derived_mounts(runner, root_device)?;
+            for mount in intact.into_iter().flatten() {
+                if !mounts.iter().any(|derived| derived.mountpoint == mount.mountpoint) {
+                    mounts.push(mount);
+                }
+            }
+            mounts
+        }
+    };
+    mounts.sort_by_key(|mount| mount.mountpoint.len());
+
+    Ok(mounts)
+}
+
+/// The mounts an installation on `root_device` was made with: the root,
+/// expanded into the @/@home layout when btrfs, and the boot partitions
+/// found on the same disk
+fn derived_mounts(runner: &dyn CommandRunner, root_device: &str) -> Result<Vec<ResolvedMount>, Status> {
+    let mut mounts = vec![ResolvedMount {
+        device: root_device.to_string(),
+        mountpoint: "/".to_string(),
+        fstype: blkid(runner, root_device, "TYPE")?,
+        subvol: None,
+    }];
+    btrfs::expand_subvolumes(&mut mounts);
+
+    let siblings = simulate::block_devices()?
+        .iter()
+        .map(|device| {
+            device
+                .partitions()
+                .iter()
+                .map(|partition| partition.device.display().to_string())
+                .collect::<Vec<_>>()
+        })
+        .find(|partitions| partitions.iter().any(|partition| partition == root_device))
+        .unwrap_or_default();
+
+    let mut typed = Vec::new();
+    for partition in siblings.iter().filter(|partition| *partition != root_device) {
+        typed.push((partition.clone(), partition_type(runner, partition)?));
+    }
+    for (device, mountpoint) in boot_mounts(&typed) {
+        mounts.push(ResolvedMount {
+            fstype: blkid(runner, &device, "TYPE")?,
+            device,
+            mountpoint: mountpoint.to_string(),
+            subvol: None,
+        });
+    }
+
+    Ok(mounts)
+}
+
+/// Where the boot partitions among `partitions`, with their GPT types, are
+/// mounted: the ESP at /efi beside an XBOOTLDR at /boot, or at /boot alone
+fn boot_mounts(partitions: &[(String, String)]) -> Vec<(String, &'static str)> {
+    let find = |wanted: &str| {
+        partitions
+            .iter()
+            .find(|(_, part_type)| part_type.eq_ignore_ascii_case(wanted))
+            .map(|(device, _)| device.clone())
+    };
+
+    match (find(ESP_TYPE), find(XBOOTLDR_TYPE)) {
+        (Some(esp), Some(xbootldr)) => vec![(esp, "/efi"), (xbootldr, "/boot")],
+        (Some(esp), None) => vec![(esp, "/boot")],
+        (None, Some(xbootldr)) => vec![(xbootldr, "/boot")],
+        (None, None) => Vec::new(),
+    }
+}
+
+/// The GPT partition type GUID, probed from the partition table itself
+fn partition_type(runner: &dyn CommandRunner, device: &str) -> Result<String, Status> {
+    let output = runner
+        .output(
+            Command::new("blkid")
+                .args(["-p", "-s", "PART_ENTRY_TYPE", "-o", "value"])
+                .arg(device),
+            None,
+        )
+        .map_err(|e| Status::internal(format!("failed to spawn blkid: {e}")))?;
+
+    // A partition without a filesystem blkid knows exits non-zero, and has
+    // no boot role either
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Mount the installation, sync it against its system-model and regenerate
+/// whatever the request asks for
+pub(super) fn repair(
+    runner: &dyn CommandRunner,
+    request: &RepairRequest,
+    mounts: &[ResolvedMount],
+    progress: &Progress,
+) -> Result<(), Status> {
+    let target = &simulate::path(TARGET_MOUNT);
+    fs::create_dir_all(target)?;
+
+    with_target(runner, target, mounts, progress, || {
+        sync_packages(runner, target, progress)?;
+
+        if request.regenerate_machine_id {
+            progress.stage(InstallStage::Configure, "Regenerating machine-id");
+            regenerate_machine_id(runner, target)?;
+        }
+        if request.regenerate_fstab {
+            progress.stage(InstallStage::Configure, "Regenerating fstab");
+            write_fstab(runner, target, mounts)?;
+        }
+        // A sync that changes nothing leaves the boot entries as they were,
+        // so a damaged ESP needs blsforme run against it explicitly
+        if request.regenerate_boot {
+            progress.stage(InstallStage::Bootloader, "Regenerating boot entries");
+            run(
+                runner,
+                Command::new("moss").arg("-D").arg(target).args(["boot", "sync"]),
+            )?;
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boot_partitions_are_found_by_type() {
+        let partitions = [
+            ("/dev/sda1", ESP_TYPE.to_uppercase()),
+            ("/dev/sda2", XBOOTLDR_TYPE.to_string()),
+            ("/dev/sda4", "0fc63daf-8483-4772-8e79-3d69d8477de4".to_string()),
+        ]
+        .map(|(device, part_type)| (device.to_string(), part_type));
+
+        assert_eq!(
+            boot_mounts(&partitions),
+            [("/dev/sda1".to_string(), "/efi"), ("/dev/sda2".to_string(), "/boot")]
+        );
+        assert_eq!(
+            boot_mounts(&[partitions[0].clone(), partitions[2].clone()]),
+            [("/dev/sda1".to_string(), "/boot")]
+        );
+        assert!(boot_mounts(&partitions[2..]).is_empty());
+    }
+}
@@ -0,0 +1,351 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! How the install service runs commands
+//!
+//! Every mount, moss, chroot and blkid call goes through a [`CommandRunner`],
+//! so the pipeline can run for real, as a dry run that only logs, or against
+//! a recording fake in tests. The service wraps its runner in [`Audited`],
+//! which keeps each command and how it ended for the audit log.
+
+use protocols::lichen::install::AuditEntry;
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::info;
+
+/// Which of a command's output streams a line was printed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Runs the commands an install is made of
+pub trait CommandRunner: fmt::Debug + Send + Sync {
+    /// Run a command to completion, feeding it `stdin` when given, and
+    /// capture its output
+    fn output(&self, command: &mut Command, stdin: Option<&[u8]>) -> io::Result<Output>;
+
+    /// Run a command, handing each line it prints to `line` as it arrives
+    fn stream(&self, command: &mut Command, line: &(dyn Fn(Stream, &str) + Sync)) -> io::Result<ExitStatus>;
+
+    /// True when commands are only logged, not run
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+
+    /// Whether a device node the commands would be run against exists
+    fn device_exists(&self, device: &Path) -> bool {
+        device.exists()
+    }
+}
+
+/// Runs commands on the system
+#[derive(Debug, Default)]
+pub struct System;
+
+impl CommandRunner for System {
+    fn output(&self, command: &mut Command, stdin: Option<&[u8]>) -> io::Result<Output> {
+        let Some(stdin) = stdin else {
+            return command.output();
+        };
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child.stdin.take().expect("stdin was piped").write_all(stdin)?;
+        child.wait_with_output()
+    }
+
+    fn stream(&self, command: &mut Command, line: &(dyn Fn(Stream, &str) + Sync)) -> io::Result<ExitStatus> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout was piped");
+        let stderr = child.stderr.take().expect("stderr was piped");
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for text in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    line(Stream::Stderr, &text);
+                }
+            });
+            for text in BufReader::new(stdout).lines().map_while(Result::ok) {
+                line(Stream::Stdout, &text);
+            }
+        });
+
+        child.wait()
+    }
+}
+
+/// Logs commands instead of running them, reporting success. Commands whose
+/// output the install depends on are given made-up output by `answer`.
+#[derive(Debug)]
+pub struct DryRun {
+    answer: fn(&Command) -> Option<String>,
+    /// Pause before each streamed line, to pace output like the real command
+    pace: Duration,
+}
+
+impl DryRun {
+    pub fn new(answer: fn(&Command) -> Option<String>, pace: Duration) -> Self {
+        Self { answer, pace }
+    }
+}
+
+impl Default for DryRun {
+    fn default() -> Self {
+        Self::new(|_| None, Duration::ZERO)
+    }
+}
+
+impl CommandRunner for DryRun {
+    fn output(&self, command: &mut Command, _stdin: Option<&[u8]>) -> io::Result<Output> {
+        info!("dry run: {}", describe(command));
+
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: (self.answer)(command).unwrap_or_default().into_bytes(),
+            stderr: Vec::new(),
+        })
+    }
+
+    fn stream(&self, command: &mut Command, line: &(dyn Fn(Stream, &str) + Sync)) -> io::Result<ExitStatus> {
+        info!("dry run: {}", describe(command));
+
+        for text in (self.answer)(command).unwrap_or_default().lines() {
+            thread::sleep(self.pace);
+            line(Stream::Stdout, text);
+        }
+
+        Ok(ExitStatus::from_raw(0))
+    }
+
+    fn is_dry_run(&self) -> bool {
+        true
+    }
+
+    // Dry runs work on mock devices, which have no nodes
+    fn device_exists(&self, _device: &Path) -> bool {
+        true
+    }
+}
+
+/// A runner that keeps an audit entry for every command it runs
+#[derive(Debug)]
+pub struct Audited {
+    inner: Box<dyn CommandRunner>,
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl Audited {
+    pub fn new(inner: Box<dyn CommandRunner>) -> Self {
+        Self {
+            inner,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Every command run so far, oldest first
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    fn record<T>(
+        &self,
+        command: Vec<String>,
+        run: impl FnOnce() -> io::Result<T>,
+        status: fn(&T) -> ExitStatus,
+    ) -> io::Result<T> {
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        let result = run();
+
+        let (exit_code, error) = match &result {
+            Ok(value) => {
+                let status = status(value);
+                let signal = status.signal().map(|signal| format!("killed by signal {signal}"));
+                (status.code(), signal)
+            }
+            Err(e) => (None, Some(e.to_string())),
+        };
+        self.entries.lock().unwrap().push(AuditEntry {
+            command,
+            exit_code,
+            error,
+            dry_run: self.inner.is_dry_run(),
+            started_at_ms: started_at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_millis() as u64),
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+
+        result
+    }
+}
+
+impl CommandRunner for Audited {
+    fn output(&self, command: &mut Command, stdin: Option<&[u8]>) -> io::Result<Output> {
+        self.record(
+            command_line(command),
+            || self.inner.output(command, stdin),
+            |output| output.status,
+        )
+    }
+
+    fn stream(&self, command: &mut Command, line: &(dyn Fn(Stream, &str) + Sync)) -> io::Result<ExitStatus> {
+        self.record(
+            command_line(command),
+            || self.inner.stream(command, line),
+            |status| *status,
+        )
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.inner.is_dry_run()
+    }
+
+    fn device_exists(&self, device: &Path) -> bool {
+        self.inner.device_exists(device)
+    }
+}
+
+/// The program followed by its arguments
+fn command_line(command: &Command) -> Vec<String> {
+    [command.get_program()]
+        .into_iter()
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// The command line, as it would be typed
+pub fn describe(command: &Command) -> String {
+    command_line(command).join(" ")
+}
+
+/// Records the command lines it is asked to run instead of running them.
+/// Each succeeds with no output unless a reply was given for a prefix of it,
+/// and every device exists.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct Recording {
+    commands: Mutex<Vec<String>>,
+    replies: Vec<(String, i32, String)>,
+}
+
+#[cfg(test)]
+impl Recording {
+    /// Have commands starting with `prefix` print `stdout`
+    pub fn reply(mut self, prefix: &str, stdout: &str) -> Self {
+        self.replies.push((prefix.to_owned(), 0, stdout.to_owned()));
+        self
+    }
+
+    /// Have commands starting with `prefix` exit with `code`
+    pub fn fail(mut self, prefix: &str, code: i32) -> Self {
+        self.replies.push((prefix.to_owned(), code, String::new()));
+        self
+    }
+
+    /// The command lines run so far, oldest first
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    fn respond(&self, command: &Command) -> (ExitStatus, String) {
+        let line = describe(command);
+        self.commands.lock().unwrap().push(line.clone());
+
+        let (code, stdout) = self
+            .replies
+            .iter()
+            .find(|(prefix, ..)| line.starts_with(prefix.as_str()))
+            .map_or((0, String::new()), |(_, code, stdout)| (*code, stdout.clone()));
+        // A wait status carries the exit code in its second byte
+        (ExitStatus::from_raw(code << 8), stdout)
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for Recording {
+    fn output(&self, command: &mut Command, _stdin: Option<&[u8]>) -> io::Result<Output> {
+        let (status, stdout) = self.respond(command);
+        Ok(Output {
+            status,
+            stdout: stdout.into_bytes(),
+            stderr: Vec::new(),
+        })
+    }
+
+    fn stream(&self, command: &mut Command, line: &(dyn Fn(Stream, &str) + Sync)) -> io::Result<ExitStatus> {
+        let (status, stdout) = self.respond(command);
+        for text in stdout.lines() {
+            line(Stream::Stdout, text);
+        }
+        Ok(status)
+    }
+
+    fn device_exists(&self, _device: &Path) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audits_exit_codes_and_spawn_failures() {
+        let audited = Audited::new(Box::new(System));
+        let _ = audited.output(&mut Command::new("/nonexistent/lichen-test"), None);
+        assert_eq!(audited.entries()[0].command, ["/nonexistent/lichen-test"]);
+        assert_eq!(audited.entries()[0].exit_code, None);
+        assert!(audited.entries()[0].error.is_some());
+
+        let audited = Audited::new(Box::new(Recording::default().fail("umount", 32)));
+        audited
+            .output(Command::new("mount").args(["/dev/sda2", "/target"]), None)
+            .unwrap();
+        audited.output(Command::new("umount").arg("/target"), None).unwrap();
+
+        let entries = audited.entries();
+        assert_eq!(entries[0].command, ["mount", "/dev/sda2", "/target"]);
+        assert_eq!(entries[0].exit_code, Some(0));
+        assert_eq!(entries[1].exit_code, Some(32));
+        assert!(entries.iter().all(|entry| !entry.dry_run && entry.error.is_none()));
+    }
+
+    #[test]
+    fn dry_runs_are_answered_and_marked() {
+        let audited = Audited::new(Box::new(DryRun::new(
+            |command| (command.get_program() == "blkid").then(|| "ext4\n".to_owned()),
+            Duration::ZERO,
+        )));
+
+        let output = audited.output(Command::new("blkid").arg("/dev/sda2"), None).unwrap();
+        assert_eq!(output.stdout, b"ext4\n");
+        assert!(
+            audited
+                .output(&mut Command::new("mkfs.ext4"), None)
+                .unwrap()
+                .stdout
+                .is_empty()
+        );
+        assert!(audited.entries().iter().all(|entry| entry.dry_run));
+    }
+}
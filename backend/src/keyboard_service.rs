@@ -0,0 +1,166 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Keyboard service: XKB layout and console keymap discovery, plus switching
+//! the live session so that later input matches the chosen layout.
+
+use std::{collections::BTreeMap, fs, sync::Arc};
+
+use lichen_macros::authorized;
+use protocols::lichen::keyboard::{
+    KeyboardLayout, Layout, LayoutVariant, ListKeymapsResponse, ListLayoutsResponse, keyboard_server,
+};
+use tokio::process::Command;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use crate::auth::AuthService;
+
+/// The xkeyboard-config rules listing, which names every layout and variant
+/// alongside its human readable description
+const XKB_RULES_LIST: &str = "/usr/share/X11/xkb/rules/base.lst";
+
+/// Service represents the keyboard service implementation
+#[derive(Debug)]
+pub struct Service {
+    auth: Arc<AuthService>,
+}
+
+/// Creates a new Keyboard gRPC server instance using the default Service implementation
+pub fn service(auth: Arc<AuthService>) -> keyboard_server::KeyboardServer<Service> {
+    keyboard_server::KeyboardServer::new(Service { auth })
+}
+
+#[tonic::async_trait]
+impl keyboard_server::Keyboard for Service {
+    /// Lists the XKB layouts, sorted by description
+    async fn list_layouts(&self, _request: Request<()>) -> Result<Response<ListLayoutsResponse>, Status> {
+        let contents = fs::read_to_string(XKB_RULES_LIST)
+            .map_err(|e| Status::unavailable(format!("failed to read {XKB_RULES_LIST}: {e}")))?;
+        let layouts = parse_rules_list(&contents);
+
+        Ok(Response::new(ListLayoutsResponse { layouts }))
+    }
+
+    /// Lists the console keymaps known to localectl
+    async fn list_keymaps(&self, _request: Request<()>) -> Result<Response<ListKeymapsResponse>, Status> {
+        let output = Command::new("localectl").arg("list-keymaps").output().await?;
+        if !output.status.success() {
+            return Err(Status::internal("localectl list-keymaps failed"));
+        }
+
+        let keymaps = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Ok(Response::new(ListKeymapsResponse { keymaps }))
+    }
+
+    /// Apply the layout to the live system's console and X11 configuration
+    #[authorized("com.aerynos.lichen.keyboard.set-live")]
+    async fn set_live_layout(&self, request: Request<KeyboardLayout>) -> Result<Response<()>, Status> {
+        let layout = request.into_inner();
+        info!(
+            layout = %layout.layout,
+            variant = %layout.variant,
+            keymap = %layout.keymap,
+            "Switching live keyboard layout"
+        );
+
+        // --no-convert, because each half is configured explicitly: letting
+        // localed derive one from the other would overwrite the user's choice
+        if !layout.keymap.is_empty() {
+            run_localectl(&["set-keymap", "--no-convert", &layout.keymap]).await?;
+        }
+        if !layout.layout.is_empty() {
+            run_localectl(&["set-x11-keymap", "--no-convert", &layout.layout, "", &layout.variant]).await?;
+        }
+
+        Ok(Response::new(()))
+    }
+}
+
+/// Run localectl, mapping failure to a gRPC status carrying its stderr
+async fn run_localectl(args: &[&str]) -> Result<(), Status> {
+    let output = Command::new("localectl").args(args).output().await?;
+
+    if !output.status.success() {
+        return Err(Status::internal(format!(
+            "localectl {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Parse the `! layout` and `! variant` sections of an xkeyboard-config
+/// rules listing. Variant lines carry their parent as `layout: description`.
+fn parse_rules_list(contents: &str) -> Vec<Layout> {
+    let mut layouts: BTreeMap<String, Layout> = BTreeMap::new();
+    let mut section = "";
+
+    for line in contents.lines() {
+        if let Some(name) = line.strip_prefix('!') {
+            section = name.trim();
+            continue;
+        }
+
+        let Some((name, description)) = line.trim().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let description = description.trim();
+
+        match section {
+            "layout" => {
+                layouts.insert(
+                    name.to_string(),
+                    Layout {
+                        name: name.to_string(),
+                        description: description.to_string(),
+                        variants: Vec::new(),
+                    },
+                );
+            }
+            "variant" => {
+                if let Some((parent, description)) = description.split_once(':')
+                    && let Some(layout) = layouts.get_mut(parent)
+                {
+                    layout.variants.push(LayoutVariant {
+                        name: name.to_string(),
+                        description: description.trim().to_string(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut layouts = layouts.into_values().collect::<Vec<_>>();
+    layouts.sort_by(|a, b| a.description.cmp(&b.description));
+    layouts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_list_groups_variants_under_layouts() {
+        let listing = "! model\n  pc105           Generic 105-key PC\n\n! layout\n  us              English (US)\n  de              German\n\n! variant\n  dvorak          us: English (Dvorak)\n  nodeadkeys      de: German (no dead keys)\n  orphan          zz: Nothing\n\n! option\n  grp             Switching to another layout\n";
+        let layouts = parse_rules_list(listing);
+
+        assert_eq!(layouts.len(), 2, "models and options are not layouts");
+        assert_eq!(layouts[0].name, "us");
+        assert_eq!(layouts[0].description, "English (US)");
+        assert_eq!(layouts[0].variants[0].name, "dvorak");
+        assert_eq!(layouts[0].variants[0].description, "English (Dvorak)");
+        assert_eq!(layouts[1].name, "de");
+        assert_eq!(layouts[1].variants.len(), 1);
+    }
+}
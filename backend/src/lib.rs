@@ -8,13 +8,17 @@
 // Err variant that can be shrank
 #![allow(clippy::result_large_err)]
 mod builtin_strategies;
+mod busy;
 
 pub mod auth;
 pub mod disk_service;
 pub mod install_service;
+pub mod keyboard_service;
 pub mod locales_service;
 pub mod plans;
 pub mod provisioner_service;
+pub mod simulate;
 pub mod system_service;
+pub mod timezone_service;
 
 pub use lichen_macros::authorized;
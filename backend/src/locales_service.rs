@@ -66,6 +66,32 @@ impl locales_server::Locales for Service {
         Ok(Response::new(response))
     }
 
+    /// Lists the UTF-8 locales usable for regional formats, ordered by
+    /// territory since users pick formats by where they are, not by language
+    async fn list_format_locales(&self, _request: Request<()>) -> Result<Response<ListLocalesResponse>, tonic::Status> {
+        let mut locales: Vec<Locale> = self
+            .locale_codes
+            .iter()
+            .filter_map(|code| self.registry.locale(code))
+            .map(Locale::from)
+            .filter(|locale| {
+                locale.codeset.as_deref().is_some_and(|codeset| {
+                    codeset.eq_ignore_ascii_case("utf-8") || codeset.eq_ignore_ascii_case("utf8")
+                })
+            })
+            .collect();
+
+        locales.sort_by(|a, b| {
+            let territory = |locale: &Locale| locale.territory.as_ref().map(|t| t.display_name.clone());
+            territory(a)
+                .cmp(&territory(b))
+                .then_with(|| a.display_name.cmp(&b.display_name))
+        });
+
+        let response = ListLocalesResponse { locales };
+        Ok(Response::new(response))
+    }
+
     /// Gets the locale details for a specific locale
     async fn get_locale(&self, _request: Request<GetLocaleRequest>) -> Result<Response<Locale>, tonic::Status> {
         let request = _request.into_inner();
@@ -13,7 +13,12 @@ use std::sync::Arc;
 use std::{env, fs::File};
 
 use backend::auth::{AuthService, uds_interceptor};
-use backend::{disk_service, install_service, locales_service, provisioner_service, system_service};
+use backend::install_service::runner::{self, CommandRunner};
+use backend::{
+    disk_service, install_service, keyboard_service, locales_service, provisioner_service, simulate, system_service,
+    timezone_service,
+};
+use clap::Parser;
 use color_eyre::eyre::bail;
 use nix::libc::geteuid;
 use tokio::net::UnixListener;
@@ -29,6 +34,17 @@ use tracing::info;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{EnvFilter, Layer, fmt::format::Format, layer::SubscriberExt, util::SubscriberInitExt};
 
+/// Command line arguments for the backend
+#[derive(Parser)]
+#[command(about = "Privileged backend for the Lichen installer")]
+struct Args {
+    /// Work against mock disks and log the commands an install would run
+    /// instead of running them. Needs no privileges: set LICHEN_SOCKET to a
+    /// writable path for the frontend and backend alike.
+    #[arg(long)]
+    simulate: bool,
+}
+
 /// Configures color-eyre for enhanced error handling and reporting
 ///
 /// Sets up error hooks with metadata about the environment and package version
@@ -109,30 +125,45 @@ async fn signal_handler(mut recv: UnboundedReceiver<()>) {
 /// shutdown on termination signals.
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
     setup_eyre();
 
-    // Ensure we're euid 0
-    let euid = unsafe { geteuid() };
-    match euid {
-        0 => (),
-        _ => bail!("This service must be run as root"),
+    // Ensure we're euid 0, unless nothing is really going to be touched
+    if args.simulate {
+        simulate::enable();
+    } else {
+        let euid = unsafe { geteuid() };
+        match euid {
+            0 => (),
+            _ => bail!("This service must be run as root"),
+        }
     }
 
     configure_tracing()?;
 
+    let socket = protocols::socket_path();
+
     // Remove the old socket if it exists
-    let _ = std::fs::remove_file("/run/lichen.sock");
+    let _ = std::fs::remove_file(&socket);
 
-    let listener = UnixListener::bind("/run/lichen.sock")?;
+    let listener = UnixListener::bind(&socket)?;
     // Make it writable by everyone
-    let _ = std::fs::set_permissions("/run/lichen.sock", std::fs::Permissions::from_mode(0o666));
+    let _ = std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o666));
 
     let uds_stream = UnixListenerStream::new(listener);
     let (send, recv) = unbounded_channel();
 
     let auth = Arc::new(AuthService::new());
+    let runner: Box<dyn CommandRunner> = if args.simulate {
+        Box::new(simulate::runner())
+    } else {
+        Box::new(runner::System)
+    };
 
-    info!("🚀 Serving on /run/lichen.sock");
+    if args.simulate {
+        info!("Simulating: mock disks only, no commands are run");
+    }
+    info!("🚀 Serving on {socket}");
 
     Server::builder()
         .layer(interceptor(uds_interceptor))
@@ -140,7 +171,9 @@ async fn main() -> Result<()> {
         .add_service(locales_service::service(auth.clone()).await?)
         .add_service(system_service::service(auth.clone(), send))
         .add_service(provisioner_service::service(auth.clone()).await?)
-        .add_service(install_service::service(auth.clone()))
+        .add_service(install_service::service(auth.clone(), runner))
+        .add_service(keyboard_service::service(auth.clone()))
+        .add_service(timezone_service::service(auth.clone()))
         .serve_with_incoming_shutdown(uds_stream, signal_handler(recv))
         .await?;
 
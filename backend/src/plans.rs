@@ -8,6 +8,7 @@
 //! built from, so it can never be stored or cross an await point. Every
 //! function here runs synchronously and returns owned protobuf messages.
 
+use crate::simulate;
 use disks::BlockDevice;
 use partitioning::{
     Formatter, GptAttributes, PartitionAttributes, gpt::partition_types::OperatingSystem, planner::Change,
@@ -86,6 +87,15 @@ pub(crate) fn apply_strategy(
             .map_err(|err| Status::internal(format!("simulation failed for {disk}: {err}")))?;
     }
 
+    // Simulating, the plan goes no further than the checks above
+    if simulate::active() {
+        for (device, filesystem) in &plan.filesystems {
+            simulate::log(&Formatter::new(filesystem.clone()).force().format(device));
+            simulate::format(device, &filesystem_to_proto(filesystem).filesystem_type);
+        }
+        return Ok(plan_to_proto(plan));
+    }
+
     // Validate every disk before mutating any of them: failing on the second
     // disk of a multi-disk plan would leave the first one already wiped and
     // the user with no installed system and no way back.
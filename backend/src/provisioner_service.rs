@@ -3,7 +3,7 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::{auth::AuthService, builtin_strategies, plans};
+use crate::{auth::AuthService, builtin_strategies, busy::Usage, plans, simulate};
 use disks::BlockDevice;
 use lichen_macros::authorized;
 use protocols::lichen::storage::provisioner::{
@@ -57,7 +57,7 @@ impl Service {
             return Err(Status::invalid_argument("no disks provided"));
         }
 
-        let mut devices = BlockDevice::discover()?;
+        let mut devices = simulate::block_devices()?;
         devices.retain(|device| requested.iter().any(|path| Path::new(path) == device.device()));
 
         // Count-match, not subset-match: this is the last gate before an
@@ -69,6 +69,21 @@ impl Service {
 
         Ok(devices)
     }
+
+    /// Refuse disks the live system is using: wiping one would take the
+    /// session's own mounts, swap or boot medium with it
+    fn ensure_unused(devices: &[BlockDevice]) -> Result<(), Status> {
+        let usage = Usage::read();
+        for device in devices {
+            if let Some(reason) = usage.disk(device) {
+                return Err(Status::failed_precondition(format!(
+                    "{} is in use by the live system: {reason}",
+                    device.device().display()
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[tonic::async_trait]
@@ -130,6 +145,7 @@ impl provisioner_server::Provisioner for Service {
 
         let plan = tokio::task::block_in_place(|| {
             let devices = self.selected_devices(&req.disks)?;
+            Self::ensure_unused(&devices)?;
             plans::apply_strategy(&self.builtin_strategies, &req.strategy, &devices)
         })?;
 
@@ -0,0 +1,197 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Simulation: running the whole installer without privileges
+//!
+//! With `--simulate` the backend sees mock disks in place of the system's,
+//! applies a strategy by simulating it only and installs with a dry run
+//! runner, logging every command instead of running it. Paths the backend
+//! mounts at live under a scratch directory, so the files an install writes
+//! land somewhere harmless and can be inspected afterwards. The filesystems a
+//! plan would create are remembered, so dry run blkid lookups agree with it.
+
+use crate::install_service::runner::{self, DryRun};
+use disks::{BlockDevice, mock::MockDisk};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tracing::info;
+
+/// Size of the mock disk offered in place of the system's
+const MOCK_DISK_SIZE: u64 = 64 * 1024 * 1024 * 1024;
+
+/// Pause between synthetic progress lines, so a frontend sees a stream
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(150);
+
+/// Packages the synthetic `moss sync` fetches and installs
+const PACKAGES: &[&str] = &[
+    "glibc",
+    "systemd",
+    "linux-desktop",
+    "moss",
+    "bash",
+    "coreutils",
+    "util-linux",
+    "shadow",
+    "networkmanager",
+    "gnome-shell",
+];
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Filesystem type by device node, for each filesystem a plan would create
+static FORMATTED: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Turn simulation on for the rest of the process, starting from a clean
+/// scratch directory
+pub fn enable() {
+    ACTIVE.store(true, Ordering::Relaxed);
+    let _ = fs::remove_dir_all(scratch());
+}
+
+/// Whether the backend is simulating
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Where an absolute backend path lives: as given, or under the scratch
+/// directory while simulating
+pub fn path(path: &str) -> PathBuf {
+    if active() {
+        scratch().join(path.trim_start_matches('/'))
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+fn scratch() -> PathBuf {
+    env::temp_dir().join("lichen-simulate")
+}
+
+/// The block devices to work with: mock disks while simulating, otherwise
+/// those discovered on the system
+pub fn block_devices() -> io::Result<Vec<BlockDevice>> {
+    if active() {
+        Ok(vec![BlockDevice::mock_device(MockDisk::new(MOCK_DISK_SIZE))])
+    } else {
+        BlockDevice::discover()
+    }
+}
+
+/// The runner installs are simulated with
+pub fn runner() -> DryRun {
+    DryRun::new(answer, PROGRESS_INTERVAL)
+}
+
+/// Log a command in place of running it
+pub fn log(command: &Command) {
+    info!("simulate: would run {}", runner::describe(command));
+}
+
+/// Remember the filesystem a plan would create on `device`
+pub fn format(device: &Path, filesystem_type: &str) {
+    // mkfs.fat's filesystems are reported as vfat by blkid
+    let fstype = match filesystem_type {
+        "fat32" => "vfat",
+        other => other,
+    };
+    FORMATTED
+        .lock()
+        .unwrap()
+        .insert(device.display().to_string(), fstype.to_owned());
+}
+
+/// Made-up output for the commands whose output an install depends on: blkid
+/// lookups and the progress `moss sync` prints
+fn answer(command: &Command) -> Option<String> {
+    let args = command.get_args().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>();
+
+    match command.get_program().to_str()? {
+        "blkid" => {
+            let tag = args.iter().skip_while(|arg| *arg != "-s").nth(1)?;
+            Some(blkid(args.last()?, tag))
+        }
+        "moss" if args.first().is_some_and(|arg| arg == "sync") => Some(sync_output()),
+        _ => None,
+    }
+}
+
+/// The value blkid would report for a tag: the filesystem type the plan
+/// gave the device, and stable made-up identifiers
+fn blkid(device: &str, tag: &str) -> String {
+    match tag {
+        "TYPE" => FORMATTED
+            .lock()
+            .unwrap()
+            .get(device)
+            .cloned()
+            .unwrap_or_else(|| "ext4".to_owned()),
+        "UUID" | "PARTUUID" => uuid(device, tag),
+        _ => String::new(),
+    }
+}
+
+/// A UUID derived from the device and tag, the same on every lookup
+fn uuid(device: &str, tag: &str) -> String {
+    let half = |salt: u8| {
+        let mut hasher = DefaultHasher::new();
+        (device, tag, salt).hash(&mut hasher);
+        hasher.finish()
+    };
+    let (high, low) = (half(0), half(1));
+
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// What `moss sync` prints as it fetches and installs
+fn sync_output() -> String {
+    let total = PACKAGES.len();
+    let mut output = String::new();
+
+    for verb in ["Fetching", "Installing"] {
+        for (index, package) in PACKAGES.iter().enumerate() {
+            output.push_str(&format!("{verb} {package} ({}/{total})\n", index + 1));
+        }
+    }
+    output.push_str("Blitting filesystem\n");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn made_up_identifiers_are_stable_and_distinct() {
+        let mut lookup = Command::new("blkid");
+        lookup.args(["-c", "/dev/null", "-s", "PARTUUID", "-o", "value", "/dev/mock0p1"]);
+        let partuuid = answer(&lookup).expect("blkid is answered");
+        assert_eq!(partuuid.len(), 36);
+        assert_eq!(partuuid, blkid("/dev/mock0p1", "PARTUUID"));
+        assert_ne!(partuuid, blkid("/dev/mock0p2", "PARTUUID"));
+        assert_ne!(partuuid, blkid("/dev/mock0p1", "UUID"));
+
+        format(Path::new("/dev/mock0p1"), "fat32");
+        assert_eq!(blkid("/dev/mock0p1", "TYPE"), "vfat");
+        assert_eq!(blkid("/dev/mock0p9", "TYPE"), "ext4");
+    }
+}
@@ -0,0 +1,232 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Timezones service: canonical zones from the live system's tzdata, grouped
+//! by region and country, with a default proposed without any geo-IP lookup.
+
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
+
+use protocols::lichen::timezones::{
+    Country, ListTimezonesRequest, ListTimezonesResponse, Region, Timezone, timezones_server,
+};
+use tonic::{Request, Response, Status};
+
+use crate::auth::AuthService;
+
+/// Root of the live system's tz database
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+/// The canonical zone table: one row per zone that has differed since 1970,
+/// so deprecated aliases and backward links never appear
+const ZONE_TABLE: &str = "zone1970.tab";
+/// ISO 3166 country code to name mapping shipped alongside the zone table
+const COUNTRY_TABLE: &str = "iso3166.tab";
+/// The universal fallback, which zone1970.tab does not list
+const UTC: &str = "UTC";
+
+/// Service represents the timezones service implementation
+#[derive(Debug)]
+pub struct Service {
+    _auth: Arc<AuthService>,
+}
+
+/// Creates a new Timezones gRPC server instance using the default Service implementation
+pub fn service(auth: Arc<AuthService>) -> timezones_server::TimezonesServer<Service> {
+    timezones_server::TimezonesServer::new(Service { _auth: auth })
+}
+
+#[tonic::async_trait]
+impl timezones_server::Timezones for Service {
+    /// Lists the canonical zones and proposes a default for the territory
+    async fn list_timezones(
+        &self,
+        request: Request<ListTimezonesRequest>,
+    ) -> Result<Response<ListTimezonesResponse>, Status> {
+        let request = request.into_inner();
+        let zoneinfo = Path::new(ZONEINFO);
+        let zones = fs::read_to_string(zoneinfo.join(ZONE_TABLE))
+            .map_err(|e| Status::unavailable(format!("failed to read {ZONE_TABLE}: {e}")))?;
+        // Names are cosmetic; a missing table only costs the display
+        let countries = fs::read_to_string(zoneinfo.join(COUNTRY_TABLE)).unwrap_or_default();
+
+        let rows = parse_zone_table(&zones);
+        let live_zone = fs::read_link("/etc/localtime")
+            .ok()
+            .and_then(|link| zone_from_link(&link.to_string_lossy()));
+        let proposed = propose(&rows, &request.territory, live_zone.as_deref());
+        let regions = group(&rows, &parse_country_table(&countries));
+
+        Ok(Response::new(ListTimezonesResponse { regions, proposed }))
+    }
+}
+
+/// One row of zone1970.tab
+struct ZoneRow {
+    countries: Vec<String>,
+    zone: String,
+    comment: String,
+}
+
+/// Parse zone1970.tab: country codes, coordinates, zone and optional comment
+fn parse_zone_table(contents: &str) -> Vec<ZoneRow> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let countries = fields.next()?;
+            let _coordinates = fields.next()?;
+            let zone = fields.next()?;
+
+            Some(ZoneRow {
+                countries: countries.split(',').map(str::to_string).collect(),
+                zone: zone.to_string(),
+                comment: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse iso3166.tab into a code to name map
+fn parse_country_table(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(code, name)| (code.to_string(), name.trim().to_string()))
+        .collect()
+}
+
+/// The zone an /etc/localtime symlink points at, if it is canonical enough to
+/// have a zoneinfo path at all
+fn zone_from_link(link: &str) -> Option<String> {
+    link.split_once("zoneinfo/")
+        .map(|(_, zone)| zone.trim_start_matches("posix/").to_string())
+        .filter(|zone| !zone.is_empty())
+}
+
+/// Propose a default: the first (most populous) zone of the locale's
+/// territory, else the live system's zone if it is canonical, else UTC. A
+/// zone a territory shares is only taken when none is its own: zone1970.tab
+/// lists `CH,DE,LI Europe/Zurich` ahead of Europe/Berlin.
+fn propose(rows: &[ZoneRow], territory: &str, live_zone: Option<&str>) -> String {
+    let is_territory = |code: &String| code.eq_ignore_ascii_case(territory);
+    if let Some(row) = rows
+        .iter()
+        .find(|row| row.countries.first().is_some_and(is_territory))
+        .or_else(|| rows.iter().find(|row| row.countries.iter().any(is_territory)))
+    {
+        return row.zone.clone();
+    }
+
+    live_zone
+        .filter(|zone| *zone == UTC || rows.iter().any(|row| row.zone == *zone))
+        .unwrap_or(UTC)
+        .to_string()
+}
+
+/// Group zones by region, then by country. A zone shared between countries
+/// is offered under each of them, and UTC gets a region of its own.
+fn group(rows: &[ZoneRow], names: &BTreeMap<String, String>) -> Vec<Region> {
+    let mut regions: BTreeMap<String, BTreeMap<String, Vec<Timezone>>> = BTreeMap::new();
+
+    for row in rows {
+        let region = row.zone.split_once('/').map_or(row.zone.as_str(), |(region, _)| region);
+
+        for code in &row.countries {
+            regions
+                .entry(region.to_string())
+                .or_default()
+                .entry(code.clone())
+                .or_default()
+                .push(Timezone {
+                    name: row.zone.clone(),
+                    comment: row.comment.clone(),
+                });
+        }
+    }
+
+    let mut grouped = regions
+        .into_iter()
+        .map(|(name, countries)| {
+            let mut countries = countries
+                .into_iter()
+                .map(|(code, zones)| Country {
+                    name: names.get(&code).cloned().unwrap_or_else(|| code.clone()),
+                    code,
+                    zones,
+                })
+                .collect::<Vec<_>>();
+            countries.sort_by(|a, b| a.name.cmp(&b.name));
+
+            Region { name, countries }
+        })
+        .collect::<Vec<_>>();
+
+    grouped.push(Region {
+        name: UTC.to_string(),
+        countries: vec![Country {
+            code: String::new(),
+            name: "Coordinated Universal Time".to_string(),
+            zones: vec![Timezone {
+                name: UTC.to_string(),
+                comment: String::new(),
+            }],
+        }],
+    });
+
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONES: &str = "# comment\nCH,DE,LI\t+4723+00832\tEurope/Zurich\nDE\t+5230+01322\tEurope/Berlin\tmost of Germany\nGB,GG,IM,JE\t+513030-0000731\tEurope/London\nUS\t+404251-0740023\tAmerica/New_York\tEastern (most areas)\nUS\t+340308-1181434\tAmerica/Los_Angeles\tPacific\n";
+
+    #[test]
+    fn proposes_from_territory_then_live_zone() {
+        let rows = parse_zone_table(ZONES);
+
+        assert_eq!(propose(&rows, "US", None), "America/New_York");
+        assert_eq!(propose(&rows, "gb", None), "Europe/London");
+        assert_eq!(propose(&rows, "DE", None), "Europe/Berlin", "Germany's own zone wins");
+        assert_eq!(propose(&rows, "LI", None), "Europe/Zurich");
+        assert_eq!(propose(&rows, "", Some("America/Los_Angeles")), "America/Los_Angeles");
+        assert_eq!(
+            propose(&rows, "", Some("US/Pacific")),
+            "UTC",
+            "aliases are never proposed"
+        );
+        assert_eq!(propose(&rows, "ZZ", None), "UTC");
+    }
+
+    #[test]
+    fn groups_by_region_and_country() {
+        let rows = parse_zone_table(ZONES);
+        let names = parse_country_table("# comment\nDE\tGermany\nUS\tUnited States\n");
+        let regions = group(&rows, &names);
+
+        let europe = regions.iter().find(|region| region.name == "Europe").expect("Europe");
+        let germany = europe
+            .countries
+            .iter()
+            .find(|country| country.code == "DE")
+            .expect("Germany");
+        assert_eq!(germany.name, "Germany");
+        assert_eq!(germany.zones.len(), 2, "shared zones are listed under every country");
+
+        let america = regions.iter().find(|region| region.name == "America").expect("America");
+        assert_eq!(america.countries[0].zones[1].comment, "Pacific");
+        assert_eq!(regions.last().map(|region| region.name.as_str()), Some("UTC"));
+    }
+
+    #[test]
+    fn localtime_links_resolve_to_zones() {
+        assert_eq!(
+            zone_from_link("../usr/share/zoneinfo/Europe/London").as_deref(),
+            Some("Europe/London")
+        );
+        assert_eq!(zone_from_link("/etc/nowhere"), None);
+    }
+}
@@ -5,16 +5,18 @@
 //! Command line arguments and turning them into an imported model
 
 use crate::{
-    install_model::{apply_install_model, apply_system_model, is_install_model, parse_error_detail},
+    install_model::{self, apply_install_model, apply_system_model, is_install_model},
     selections::mandatory,
+    unattended::Image,
 };
-use clap::{CommandFactory, Parser, error::ErrorKind};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum, error::ErrorKind};
 use color_eyre::Result;
-use installer::Model;
-use kdl::KdlError;
+use installer::{Model, PasswordHashing};
+use models::{Mode, diff::Diff};
+use protocols::lichen::storage::disks::ImageFormat;
 use std::{
     collections::BTreeSet,
-    fs,
+    fs, io,
     path::{Path, PathBuf},
 };
 
@@ -27,16 +29,134 @@ pub struct Args {
     /// Import the packages from a system-model.kdl
     #[arg(short, long, value_name = "PATH")]
     system_model: Option<PathBuf>,
+    /// Scheme for hashing the passwords entered
+    #[arg(long, value_enum, default_value_t = HashScheme::Yescrypt)]
+    password_hash: HashScheme,
+    /// sha512-crypt rounds, with --password-hash sha512
+    #[arg(long, default_value_t = sha_crypt::ROUNDS_DEFAULT, value_name = "ROUNDS")]
+    sha512_rounds: usize,
+    /// Install from the install-model without prompting, for provisioning
+    #[arg(long, requires_all = ["install_model", "yes_wipe"])]
+    unattended: bool,
+    /// Allow an unattended install to erase this disk; must be the model's disk
+    #[arg(long, value_name = "DISK", requires = "unattended")]
+    yes_wipe: Option<PathBuf>,
+    /// Install from the install-model into a new image file instead of a disk
+    #[arg(long, value_name = "PATH", requires_all = ["install_model", "size"], conflicts_with = "unattended")]
+    image: Option<PathBuf>,
+    /// Size of the image's disk, in bytes or with a K, M, G or T suffix
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "image")]
+    size: Option<u64>,
+    /// Format to write the image in
+    #[arg(long, value_enum, default_value_t = ImageKind::Raw)]
+    image_format: ImageKind,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Tools run instead of the installer
+#[derive(Subcommand)]
+pub enum Command {
+    /// Work with install-model and system-model documents
+    #[command(subcommand)]
+    Model(ModelCommand),
+    /// Sync an existing installation against its system-model again
+    Repair {
+        /// Root partition of the installation; asked for when several are found
+        #[arg(long, value_name = "DEVICE")]
+        device: Option<String>,
+        /// Also write /etc/fstab again, from the root device and its disk
+        #[arg(long)]
+        fstab: bool,
+        /// Also write the boot entries again
+        #[arg(long)]
+        boot: bool,
+        /// Also give the installation a new machine-id
+        #[arg(long)]
+        machine_id: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ModelCommand {
+    /// Report what adopting the second model would change from the first
+    Diff {
+        #[arg(value_name = "A")]
+        before: PathBuf,
+        #[arg(value_name = "B")]
+        after: PathBuf,
+    },
+}
+
+/// Image formats selectable on the command line
+#[derive(Clone, Copy, ValueEnum)]
+enum ImageKind {
+    Raw,
+    Qcow2,
+}
+
+/// Password hashing schemes selectable on the command line
+#[derive(Clone, Copy, ValueEnum)]
+enum HashScheme {
+    Yescrypt,
+    Sha512,
 }
 
 impl Args {
+    /// The disk an unattended install may erase, if running unattended
+    pub fn unattended(&self) -> Option<&Path> {
+        self.yes_wipe.as_deref().filter(|_| self.unattended)
+    }
+
+    /// The image file to install into, if installing into one
+    pub fn image(&self) -> Result<Option<Image>, clap::Error> {
+        let (Some(path), Some(size)) = (&self.image, self.size) else {
+            return Ok(None);
+        };
+        // The backend creates the file, so a relative path would land in its
+        // working directory rather than ours. The directory is resolved to its
+        // canonical path, as the loop device will report it.
+        let path =
+            canonical_parent(path).map_err(|e| invalid_value(format!("cannot resolve {}: {e}", path.display())))?;
+
+        Ok(Some(Image {
+            path,
+            size,
+            format: match self.image_format {
+                ImageKind::Raw => ImageFormat::Raw,
+                ImageKind::Qcow2 => ImageFormat::Qcow2,
+            },
+        }))
+    }
+
+    /// The tool requested instead of the installer, if any
+    pub fn subcommand(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
+    /// The password hashing scheme for this session
+    pub fn password_hashing(&self) -> PasswordHashing {
+        match self.password_hash {
+            HashScheme::Yescrypt => PasswordHashing::Yescrypt,
+            HashScheme::Sha512 => PasswordHashing::Sha512 {
+                rounds: self.sha512_rounds,
+            },
+        }
+    }
+
     /// Load an imported model from whichever documents were given.
     pub fn model(&self) -> Result<Option<Model>, clap::Error> {
         if self.install_model.is_none() && self.system_model.is_none() {
             return Ok(None);
         }
 
-        let mut model = Model::default();
+        // Nothing is there to catch what an unattended install would skip,
+        // and nothing to ask for what the documents leave out
+        let (mode, mut model) = if self.unattended || self.image.is_some() {
+            (Mode::Strict, install_model::blank())
+        } else {
+            (Mode::Lenient, Model::default())
+        };
 
         if let Some(path) = &self.install_model {
             let contents = read_document(path)?;
@@ -48,7 +168,11 @@ impl Args {
                 )));
             }
 
-            apply_install_model(&mut model, &contents).map_err(|e| parse_failure(path, &e))?;
+            // Import what this installer understands, but never lose the
+            // rest silently: a newer lichen may have written it
+            for warning in apply_install_model(&mut model, &contents, mode).map_err(|e| parse_failure(path, &e))? {
+                tracing::warn!("{}: {warning}", path.display());
+            }
         }
 
         if let Some(path) = &self.system_model {
@@ -61,7 +185,9 @@ impl Args {
                 )));
             }
 
-            apply_system_model(&mut model, &contents).map_err(|e| parse_failure(path, &e))?;
+            for warning in apply_system_model(&mut model, &contents, mode).map_err(|e| parse_failure(path, &e))? {
+                tracing::warn!("{}: {warning}", path.display());
+            }
         }
 
         model.imported = true;
@@ -70,6 +196,67 @@ impl Args {
     }
 }
 
+/// A path to a file that does not exist yet, with its directory resolved
+fn canonical_parent(path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    Ok(fs::canonicalize(parent)?.join(name))
+}
+
+/// Compare two model documents, either an install-model or a bare
+/// system-model, warning on stderr about anything skipped in reading them
+pub fn diff_models(before: &Path, after: &Path) -> Result<Diff, clap::Error> {
+    let load = |path: &Path| -> Result<Model, clap::Error> {
+        let contents = read_document(path)?;
+        let mut model = Model::default();
+
+        let warnings = if is_install_model(&contents).map_err(|e| parse_failure(path, &e))? {
+            apply_install_model(&mut model, &contents, Mode::Lenient)
+        } else {
+            apply_system_model(&mut model, &contents, Mode::Lenient)
+        }
+        .map_err(|e| parse_failure(path, &e))?;
+
+        for warning in warnings {
+            eprintln!("warning: {}: {warning}", path.display());
+        }
+        Ok(model)
+    };
+
+    Ok(install_model::diff(&load(before)?, &load(after)?))
+}
+
+/// A size in bytes, or in KiB, MiB, GiB or TiB with a K, M, G or T suffix
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, shift) = match value.char_indices().last() {
+        Some((at, unit)) if unit.is_ascii_alphabetic() => {
+            let shift = match unit.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => return Err(format!("unknown size suffix {unit}; use K, M, G or T")),
+            };
+            (&value[..at], shift)
+        }
+        _ => (value, 0),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(1 << shift))
+        .filter(|bytes| *bytes > 0)
+        .ok_or_else(|| format!("invalid size: {value}"))
+}
+
 /// An imported model never installs less than a bootable system
 fn ensure_mandatory(model: &mut Model) -> Result<(), clap::Error> {
     let mut packages: BTreeSet<String> = model.software.packages.iter().cloned().collect();
@@ -84,16 +271,40 @@ fn read_document(path: &Path) -> Result<String, clap::Error> {
         .map_err(|e| Args::command().error(ErrorKind::Io, format!("failed to read {}: {e}", path.display())))
 }
 
-/// A KDL parse failure, with the location within the document
-fn parse_failure(path: &Path, error: &KdlError) -> clap::Error {
-    invalid_value(format!(
-        "failed to parse {}: {}",
-        path.display(),
-        parse_error_detail(error)
-    ))
+/// A document that could not be read, with the location or field at fault
+fn parse_failure(path: &Path, error: &models::Error) -> clap::Error {
+    invalid_value(format!("failed to parse {}: {error}", path.display()))
 }
 
 /// A legit flag error, not to emit a debug error
 fn invalid_value(message: String) -> clap::Error {
     Args::command().error(ErrorKind::InvalidValue, message)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_image_sizes() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("32G"), Ok(32 << 30));
+        assert_eq!(parse_size("1t"), Ok(1 << 40));
+        assert!(parse_size("0G").is_err());
+        assert!(parse_size("32X").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("99999999T").is_err(), "overflow is rejected");
+    }
+
+    #[test]
+    fn image_paths_are_resolved_as_sysfs_reports_them() {
+        let resolved = canonical_parent(Path::new("../out.img")).unwrap();
+        assert_eq!(resolved, fs::canonicalize("..").unwrap().join("out.img"));
+        assert_eq!(
+            canonical_parent(Path::new("out.img")).unwrap(),
+            fs::canonicalize(".").unwrap().join("out.img")
+        );
+        assert!(canonical_parent(Path::new("/nonexistent/lichen/out.img")).is_err());
+    }
+}
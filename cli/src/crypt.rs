@@ -0,0 +1,133 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Password hashing
+//!
+//! sha512-crypt is computed in-process; yescrypt goes through the live
+//! system's libxcrypt, the same library pam_unix verifies it with on the
+//! target.
+
+use installer::PasswordHashing;
+use sha_crypt::{Sha512Params, sha512_simple};
+use std::{
+    ffi::{CStr, CString, c_char, c_int, c_ulong, c_void},
+    ptr,
+};
+
+/// Size of libxcrypt's `struct crypt_data`
+const CRYPT_DATA_SIZE: usize = 32768;
+/// libxcrypt's CRYPT_GENSALT_OUTPUT_SIZE
+const GENSALT_OUTPUT_SIZE: usize = 192;
+
+#[link(name = "crypt")]
+unsafe extern "C" {
+    fn crypt_gensalt_rn(
+        prefix: *const c_char,
+        count: c_ulong,
+        rbytes: *const c_char,
+        nrbytes: c_int,
+        output: *mut c_char,
+        output_size: c_int,
+    ) -> *mut c_char;
+
+    fn crypt_rn(phrase: *const c_char, setting: *const c_char, data: *mut c_void, size: c_int) -> *mut c_char;
+}
+
+/// Hash a password with the chosen scheme, returning the crypt(3) string
+pub fn hash(plain: &str, hashing: PasswordHashing) -> Result<String, String> {
+    match hashing {
+        PasswordHashing::Yescrypt => yescrypt(plain),
+        PasswordHashing::Sha512 { rounds } => {
+            let params = Sha512Params::new(rounds).map_err(|_| format!("invalid sha512-crypt rounds: {rounds}"))?;
+            sha512_simple(plain, &params).map_err(|_| "failed to hash password".to_string())
+        }
+    }
+}
+
+/// yescrypt at libxcrypt's default cost, salted from the OS random source
+fn yescrypt(plain: &str) -> Result<String, String> {
+    let prefix = c"$y$";
+    let mut setting = vec![0 as c_char; GENSALT_OUTPUT_SIZE];
+
+    // SAFETY: the buffer outlives the call and its size is passed alongside;
+    // a null rbytes asks libxcrypt for its own random bytes. On success the
+    // buffer holds a NUL-terminated setting.
+    let setting = unsafe {
+        if crypt_gensalt_rn(
+            prefix.as_ptr(),
+            0,
+            ptr::null(),
+            0,
+            setting.as_mut_ptr(),
+            GENSALT_OUTPUT_SIZE as c_int,
+        )
+        .is_null()
+        {
+            return Err("libxcrypt does not support yescrypt".to_string());
+        }
+        CStr::from_ptr(setting.as_ptr()).to_owned()
+    };
+
+    crypt(plain, &setting)
+}
+
+/// Hash a password with a crypt(3) setting, or check it against a hash by
+/// passing the hash as the setting
+fn crypt(plain: &str, setting: &CStr) -> Result<String, String> {
+    let phrase = CString::new(plain).map_err(|_| "passwords cannot contain NUL".to_string())?;
+    let mut data = vec![0u8; CRYPT_DATA_SIZE];
+
+    // SAFETY: the strings and buffer outlive the call and the buffer's size
+    // is passed alongside; the result points into the buffer.
+    let hashed = unsafe {
+        let hashed = crypt_rn(
+            phrase.as_ptr(),
+            setting.as_ptr(),
+            data.as_mut_ptr().cast(),
+            CRYPT_DATA_SIZE as c_int,
+        );
+        if hashed.is_null() {
+            return Err("failed to hash password".to_string());
+        }
+        CStr::from_ptr(hashed).to_string_lossy().into_owned()
+    };
+
+    // libxcrypt signals failure with a "*" string rather than null
+    if hashed.starts_with('*') {
+        return Err("failed to hash password".to_string());
+    }
+    Ok(hashed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether libxcrypt accepts `plain` for `hashed`, as pam_unix checks it
+    fn verifies(plain: &str, hashed: &str) -> bool {
+        crypt(plain, &CString::new(hashed).unwrap()).is_ok_and(|again| again == hashed)
+    }
+
+    #[test]
+    fn yescrypt_hashes_round_trip() {
+        let hashed = hash("correct horse", PasswordHashing::Yescrypt).unwrap();
+        assert!(hashed.starts_with("$y$"), "{hashed}");
+        assert!(verifies("correct horse", &hashed));
+        assert!(!verifies("battery staple", &hashed));
+        assert_ne!(
+            hashed,
+            hash("correct horse", PasswordHashing::Yescrypt).unwrap(),
+            "salted"
+        );
+    }
+
+    #[test]
+    fn sha512_hashes_round_trip() {
+        let hashed = hash("correct horse", PasswordHashing::Sha512 { rounds: 5000 }).unwrap();
+        assert!(hashed.starts_with("$6$"), "{hashed}");
+        assert!(verifies("correct horse", &hashed));
+        assert!(!verifies("battery staple", &hashed));
+        assert!(hash("nul\0", PasswordHashing::Yescrypt).is_err());
+    }
+}
@@ -13,7 +13,9 @@ use protocols::lichen::osinfo::OsInfo;
 
 pub mod accounts;
 pub mod desktop;
+pub mod keyboard;
 pub mod locale;
+pub mod progress;
 pub mod storage;
 pub mod summary;
 pub mod timezone;
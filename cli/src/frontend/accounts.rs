@@ -4,15 +4,21 @@
 
 //! Account configuration step
 //!
-//! Passwords are hashed with sha512-crypt immediately after confirmation;
-//! only the hashes enter the model.
+//! Passwords are checked against the password policy, then hashed with the
+//! session's scheme immediately after confirmation; only the hashes enter
+//! the model. SSH public keys are stored verbatim.
 
+use crate::crypt;
 use crate::{CliStep, FrontendStep};
-use installer::{DisplayInfo, Icon, Installer, Model, StepError, User, register_step};
-use sha_crypt::{ROUNDS_DEFAULT, Sha512Params, sha512_simple};
+use installer::{
+    DisplayInfo, Icon, Installer, Model, PasswordHashing, RootAccount, StepError, User, password::PasswordPolicy,
+    register_step,
+};
+use protocols::{RESERVED_USERNAMES, is_valid_ssh_key};
+use std::{fs, path::Path};
 
 pub async fn run(_installer: &Installer, model: &mut Model) -> Result<(), StepError> {
-    if model.accounts.root_password_hash.is_some() && model.accounts.user.is_some() {
+    if model.accounts.root.is_some() && !model.accounts.users.is_empty() {
         let keep = cliclack::confirm("Keep the imported account settings?")
             .initial_value(true)
             .interact()
@@ -22,11 +28,79 @@ pub async fn run(_installer: &Installer, model: &mut Model) -> Result<(), StepEr
             return Ok(());
         }
     }
-    let root_password = ask_password("root")?;
-    model.accounts.root_password_hash = Some(hash_password(&root_password)?);
+    model.accounts.users.clear();
+    loop {
+        // The first account is the machine owner's, so it defaults to admin
+        let user = ask_user(
+            &model.accounts.users,
+            model.accounts.users.is_empty(),
+            model.accounts.hashing,
+        )?;
+        model.accounts.users.push(user);
+
+        let another = cliclack::confirm("Add another user?")
+            .initial_value(false)
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+        if !another {
+            break;
+        }
+    }
+
+    model.accounts.ssh_keys_only = false;
+    if model.accounts.users.iter().any(|user| !user.ssh_keys.is_empty()) {
+        model.accounts.ssh_keys_only = cliclack::confirm("Disable SSH password authentication and root login?")
+            .initial_value(false)
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+    }
+
+    model.accounts.autologin = false;
+    if model.software.display_manager().is_some() {
+        model.accounts.autologin = cliclack::confirm(format!(
+            "Log {} in automatically at boot?",
+            model.accounts.users[0].username
+        ))
+        .initial_value(false)
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
+    }
+
+    model.accounts.root = Some(ask_root(model)?);
+
+    Ok(())
+}
+
+/// Root is asked for after the users, so locking it is only offered when an
+/// administrator exists to reach it through sudo
+fn ask_root(model: &Model) -> Result<RootAccount, StepError> {
+    let mode = if model.accounts.has_admin_path() {
+        cliclack::select("How should the root account be set up?")
+            .item("password", "Set a root password", "")
+            .item("locked", "Lock root", "administrators use sudo")
+            .item("disabled", "Disable root", "locked, with no login shell")
+            .initial_value("password")
+            .interact()
+            .map_err(|_| StepError::UserAborted)?
+    } else {
+        let _ = cliclack::log::info("No administrator was created, so root needs a password");
+        "password"
+    };
 
+    match mode {
+        "locked" => Ok(RootAccount::Locked),
+        "disabled" => Ok(RootAccount::Disabled),
+        _ => Ok(RootAccount::Password(hash_password(
+            &ask_password("root")?,
+            model.accounts.hashing,
+        )?)),
+    }
+}
+
+fn ask_user(existing: &[User], admin_default: bool, hashing: PasswordHashing) -> Result<User, StepError> {
+    let taken = existing.iter().map(|user| user.username.clone()).collect::<Vec<_>>();
     let username: String = cliclack::input("Username for the new user")
-        .validate(|input: &String| {
+        .validate(move |input: &String| {
             let starts_ok = input
                 .chars()
                 .next()
@@ -36,6 +110,10 @@ pub async fn run(_installer: &Installer, model: &mut Model) -> Result<(), StepEr
                 .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_' || ch == '-');
             if input.is_empty() || input.len() > 32 || !starts_ok || !rest_ok {
                 Err("use lowercase letters, digits, - and _; start with a letter or _; max 32 chars")
+            } else if RESERVED_USERNAMES.contains(&input.as_str()) {
+                Err("that username is reserved for the system")
+            } else if taken.contains(input) {
+                Err("that username is already taken")
             } else {
                 Ok(())
             }
@@ -44,32 +122,138 @@ pub async fn run(_installer: &Installer, model: &mut Model) -> Result<(), StepEr
         .map_err(|_| StepError::UserAborted)?;
     let real_name: String = cliclack::input("Real name")
         .required(false)
+        .validate(|input: &String| {
+            if input.contains(':') {
+                Err("the real name cannot contain ':'")
+            } else {
+                Ok(())
+            }
+        })
         .interact()
         .map_err(|_| StepError::UserAborted)?;
     let user_pass = ask_password(&username)?;
 
-    model.accounts.user = Some(User {
-        username,
-        real_name,
-        password_hash: hash_password(&user_pass)?,
-    });
+    let mut user = User::new(username, real_name, hash_password(&user_pass, hashing)?);
+    user.admin = cliclack::confirm(format!("Make {} an administrator?", user.username))
+        .initial_value(admin_default)
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
 
-    Ok(())
+    let customize = cliclack::confirm("Customize the login shell and groups?")
+        .initial_value(false)
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
+    if customize {
+        user.shell = cliclack::input("Login shell (leave empty for the default)")
+            .required(false)
+            .validate(|input: &String| {
+                if input.is_empty() || input.starts_with('/') {
+                    Ok(())
+                } else {
+                    Err("use an absolute path, e.g. /usr/bin/zsh")
+                }
+            })
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+        let groups: String = cliclack::input("Extra groups, separated by spaces")
+            .required(false)
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+        user.groups = groups.split_whitespace().map(str::to_string).collect();
+    }
+
+    let add_keys = cliclack::confirm(format!("Add SSH public keys for {}?", user.username))
+        .initial_value(false)
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
+    if add_keys {
+        user.ssh_keys = ask_ssh_keys()?;
+    }
+
+    Ok(user)
+}
+
+/// Collect keys until an empty entry; each entry is a pasted key or the path
+/// of a file of keys, such as a copied ~/.ssh/id_ed25519.pub
+fn ask_ssh_keys() -> Result<Vec<String>, StepError> {
+    let mut keys = Vec::new();
+
+    loop {
+        let entry: String = cliclack::input("SSH public key or path to a key file (leave empty to finish)")
+            .required(false)
+            .validate(|input: &String| read_ssh_keys(input).map(|_| ()))
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+        if entry.trim().is_empty() {
+            return Ok(keys);
+        }
+
+        for key in read_ssh_keys(&entry).map_err(|e| StepError::Failed(e.to_string()))? {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        let _ = cliclack::log::info(format!("{} SSH keys added", keys.len()));
+    }
+}
+
+/// Keys from a pasted line or a file path; blank lines and comments in files
+/// are skipped
+fn read_ssh_keys(input: &str) -> Result<Vec<String>, &'static str> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let path = Path::new(input);
+    let keys = if path.is_file() {
+        fs::read_to_string(path)
+            .map_err(|_| "could not read the key file")?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    } else {
+        vec![input.to_string()]
+    };
+
+    if keys.is_empty() || !keys.iter().all(|key| is_valid_ssh_key(key)) {
+        return Err("not an OpenSSH public key, nor a file of them");
+    }
+
+    Ok(keys)
 }
 
+/// Ask for a password twice. Policy errors are shown inline and must be
+/// fixed; warnings are shown once and the user may keep the password.
 fn ask_password(who: &str) -> Result<String, StepError> {
     loop {
+        let policy = PasswordPolicy::default();
+        let username = who.to_string();
         let first = cliclack::password(format!("Password for {who}"))
             .mask('*')
-            .validate(|input: &String| {
-                if input.is_empty() {
-                    Err("password cannot be empty")
-                } else {
-                    Ok(())
-                }
+            .validate(move |input: &String| match policy.rejection(input, &username) {
+                Some(finding) => Err(finding.message),
+                None => Ok(()),
             })
             .interact()
             .map_err(|_| StepError::UserAborted)?;
+
+        let warnings = PasswordPolicy::default().evaluate(&first, who);
+        if !warnings.is_empty() {
+            for finding in &warnings {
+                let _ = cliclack::log::warning(format!("Weak password: {}", finding.message));
+            }
+            let keep = cliclack::confirm("Use this password anyway?")
+                .initial_value(false)
+                .interact()
+                .map_err(|_| StepError::UserAborted)?;
+            if !keep {
+                continue;
+            }
+        }
+
         let second = cliclack::password(format!("Confirm password for {who}"))
             .mask('*')
             .interact()
@@ -83,11 +267,8 @@ fn ask_password(who: &str) -> Result<String, StepError> {
     }
 }
 
-fn hash_password(plain: &str) -> Result<String, StepError> {
-    let params = Sha512Params::new(ROUNDS_DEFAULT)
-        .map_err(|_| StepError::Failed("invalid password hashing parameters".to_string()))?;
-
-    sha512_simple(plain, &params).map_err(|_| StepError::Failed("failed to hash password".to_string()))
+fn hash_password(plain: &str, hashing: PasswordHashing) -> Result<String, StepError> {
+    crypt::hash(plain, hashing).map_err(StepError::Failed)
 }
 
 register_step! {
@@ -98,7 +279,7 @@ register_step! {
         CliStep {
             info: DisplayInfo {
                 title: "Accounts".to_string(),
-                description: "Set the root password and create your user".to_string(),
+                description: "Create user accounts and set up root".to_string(),
                 icon: Some(Icon::Emoji("👤".to_string())),
             },
             step: FrontendStep::Accounts,
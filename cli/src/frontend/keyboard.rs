@@ -0,0 +1,132 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Keyboard layout step
+//!
+//! The chosen layout is applied to the live session straight away, so the
+//! passwords typed in the accounts step are the ones the target will expect.
+
+use crate::{CliStep, FrontendStep};
+use installer::{DisplayInfo, Icon, Installer, Model, StepError, register_step};
+use protocols::lichen::keyboard::KeyboardLayout;
+
+pub async fn run(installer: &Installer, model: &mut Model) -> Result<(), StepError> {
+    let mut keyboard = installer.keyboard().await?;
+
+    if model.imported && !model.keyboard.layout.is_empty() {
+        let _ = cliclack::log::info(format!("Using imported keyboard layout {}", model.keyboard.layout));
+    } else {
+        let layouts = keyboard.list_layouts(()).await?.into_inner().layouts;
+        let items = layouts
+            .iter()
+            .map(|layout| (layout.name.clone(), layout.description.clone(), layout.name.clone()))
+            .collect::<Vec<_>>();
+        let picked: String = cliclack::select("Select your keyboard layout")
+            .items(&items)
+            .initial_value(model.keyboard.layout.clone())
+            .filter_mode()
+            .set_size(12)
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+
+        let variant = match layouts.iter().find(|layout| layout.name == picked) {
+            Some(layout) if !layout.variants.is_empty() => {
+                let mut items = vec![(String::new(), layout.description.clone(), "default".to_string())];
+                items.extend(
+                    layout
+                        .variants
+                        .iter()
+                        .map(|variant| (variant.name.clone(), variant.description.clone(), variant.name.clone())),
+                );
+
+                cliclack::select("Select the layout variant")
+                    .items(&items)
+                    .filter_mode()
+                    .set_size(12)
+                    .interact()
+                    .map_err(|_| StepError::UserAborted)?
+            }
+            _ => String::new(),
+        };
+
+        model.keyboard.keymap = match keyboard.list_keymaps(()).await {
+            Ok(response) => {
+                let keymaps = response.into_inner().keymaps;
+                match console_keymap(&keymaps, &picked, &variant) {
+                    Some(keymap) => keymap,
+                    None => {
+                        let items = keymaps
+                            .iter()
+                            .map(|keymap| (keymap.clone(), keymap.clone(), ""))
+                            .collect::<Vec<_>>();
+
+                        cliclack::select("Select the console keymap")
+                            .items(&items)
+                            .filter_mode()
+                            .set_size(12)
+                            .interact()
+                            .map_err(|_| StepError::UserAborted)?
+                    }
+                }
+            }
+            // Without the console's keymaps (no localectl, say), the layout's own name is the best guess
+            Err(e) => {
+                let _ = cliclack::log::warning(format!(
+                    "Could not list the console keymaps, using {picked}: {}",
+                    e.message()
+                ));
+                picked.clone()
+            }
+        };
+
+        tracing::info!("Selected keyboard layout {picked} ({variant})");
+        model.keyboard.layout = picked;
+        model.keyboard.variant = variant;
+    }
+
+    // Not fatal: the install itself is unaffected, only what the live session types
+    if let Err(e) = keyboard
+        .set_live_layout(KeyboardLayout {
+            layout: model.keyboard.layout.clone(),
+            variant: model.keyboard.variant.clone(),
+            keymap: model.keyboard.keymap.clone(),
+        })
+        .await
+    {
+        let _ = cliclack::log::warning(format!(
+            "Could not switch the live session's keyboard layout: {}",
+            e.message()
+        ));
+    }
+
+    Ok(())
+}
+
+/// The console keymap matching an XKB layout and variant, if the console has one
+fn console_keymap(keymaps: &[String], layout: &str, variant: &str) -> Option<String> {
+    if !variant.is_empty() {
+        let combined = format!("{layout}-{variant}");
+        if keymaps.contains(&combined) {
+            return Some(combined);
+        }
+    }
+
+    keymaps.iter().find(|keymap| *keymap == layout).cloned()
+}
+
+register_step! {
+    id: "keyboard",
+    author: "AerynOS Developers",
+    description: "Select the keyboard layout",
+    create: || Box::new(
+        CliStep {
+            info: DisplayInfo {
+                title: "Keyboard".to_string(),
+                description: "Adjust the keyboard layout".to_string(),
+                icon: Some(Icon::Emoji("⌨️".to_string())),
+            },
+            step: FrontendStep::Keyboard,
+        }
+    )
+}
@@ -30,6 +30,37 @@ pub async fn run(installer: &Installer, model: &mut Model) -> Result<(), StepErr
     tracing::info!("Selected locale {picked}");
     model.region.language = picked;
 
+    let same_formats = cliclack::confirm("Use the same locale for dates, numbers, currency and measurements?")
+        .initial_value(true)
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
+
+    model.region.formats = Default::default();
+    if !same_formats {
+        let format_list = locales.list_format_locales(()).await?.into_inner();
+        let display_list = format_list
+            .locales
+            .iter()
+            .map(|l| {
+                let territory = l.territory.as_ref().map(|t| t.display_name.clone()).unwrap_or_default();
+                (l.name.clone(), l.display_name.clone(), territory)
+            })
+            .collect::<Vec<_>>();
+
+        let formats: String = cliclack::select("Select your regional formats")
+            .items(&display_list)
+            .initial_value(model.region.language.clone())
+            .filter_mode()
+            .set_size(12)
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+
+        tracing::info!("Selected regional formats {formats}");
+        if formats != model.region.language {
+            model.region.formats.set_all(&formats);
+        }
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,167 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Rendering the progress of an install or repair
+//!
+//! Each update names the stage the backend is in, its step among the stages
+//! and, where moss reports it, how far through the stage it is; together
+//! those drive a progress bar. Every line is kept as well, so the log can be
+//! shown when the operation fails and warnings are repeated once it ends.
+
+use protocols::lichen::install::{InstallProgress, InstallStage, ProgressLevel};
+use std::fmt::Display;
+
+/// Resolution of the progress bar
+const BAR_LENGTH: u64 = 1000;
+
+/// How many log lines are shown when an operation fails
+const LOG_TAIL: usize = 20;
+
+/// A progress bar with the log of the operation behind it
+pub struct View {
+    bar: cliclack::ProgressBar,
+    position: u64,
+    log: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl View {
+    pub fn start(message: impl Display) -> Self {
+        let bar = cliclack::progress_bar(BAR_LENGTH);
+        bar.start(message);
+
+        Self {
+            bar,
+            position: 0,
+            log: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, update: &InstallProgress) {
+        // Fractions are parsed from moss output, so the bar only moves forward
+        let position = (overall(update) * BAR_LENGTH as f64) as u64;
+        if position > self.position {
+            self.position = position;
+            self.bar.set_position(position);
+        }
+
+        if update.message.is_empty() {
+            return;
+        }
+        self.log.push(describe(update));
+
+        match update.level() {
+            ProgressLevel::Info => self
+                .bar
+                .set_message(format!("{}: {}", stage_name(update.stage()), update.message)),
+            ProgressLevel::Warning => self.warnings.push(update.message.clone()),
+            ProgressLevel::Debug | ProgressLevel::Error => {}
+        }
+    }
+
+    /// Finish successfully, then repeat any warnings
+    pub fn stop(self, message: impl Display) {
+        self.bar.stop(message);
+        for warning in &self.warnings {
+            let _ = cliclack::log::warning(warning);
+        }
+    }
+
+    /// Finish with a failure, then show the end of the log leading up to it
+    pub fn error(self, message: impl Display) {
+        self.bar.error(message);
+        if !self.log.is_empty() {
+            let tail = &self.log[self.log.len().saturating_sub(LOG_TAIL)..];
+            let _ = cliclack::note("Log", tail.join("\n"));
+        }
+    }
+}
+
+/// The line printed for an update when output is plain text: none for
+/// keep-alives and raw command output
+pub fn plain(update: &InstallProgress) -> Option<String> {
+    (!update.message.is_empty() && update.level() != ProgressLevel::Debug).then(|| describe(update))
+}
+
+/// An update as a log line, such as "[3/7] Fetching packages: glibc (3/12)"
+fn describe(update: &InstallProgress) -> String {
+    let severity = match update.level() {
+        ProgressLevel::Info => "",
+        ProgressLevel::Debug => "debug: ",
+        ProgressLevel::Warning => "warning: ",
+        ProgressLevel::Error => "error: ",
+    };
+
+    match update.stage() {
+        InstallStage::Unspecified => format!("{severity}{}", update.message),
+        stage => format!(
+            "[{}/{}] {}: {severity}{}",
+            update.step,
+            update.total_steps,
+            stage_name(stage),
+            update.message
+        ),
+    }
+}
+
+/// How far through the whole operation an update is, from 0 to 1: the steps
+/// already done, plus the fraction of the current one
+fn overall(update: &InstallProgress) -> f64 {
+    let within = update.fraction.unwrap_or(0.0).clamp(0.0, 1.0);
+    if update.total_steps == 0 {
+        return within;
+    }
+
+    let done = update.step.saturating_sub(1).min(update.total_steps) as f64;
+    ((done + within) / update.total_steps as f64).min(1.0)
+}
+
+fn stage_name(stage: InstallStage) -> &'static str {
+    match stage {
+        InstallStage::Unspecified => "Preparing",
+        InstallStage::Mounting => "Mounting",
+        InstallStage::RepoRefresh => "Refreshing repositories",
+        InstallStage::PackageFetch => "Fetching packages",
+        InstallStage::PackageInstall => "Installing packages",
+        InstallStage::Configure => "Configuring",
+        InstallStage::Bootloader => "Installing the bootloader",
+        InstallStage::Unmount => "Unmounting",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(stage: InstallStage, step: u32, fraction: Option<f64>, level: ProgressLevel) -> InstallProgress {
+        InstallProgress {
+            message: "glibc (3/12)".to_string(),
+            stage: stage as i32,
+            step,
+            total_steps: 4,
+            fraction,
+            level: level as i32,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn updates_place_the_bar_and_describe_themselves() {
+        let fetching = update(InstallStage::PackageFetch, 3, Some(0.5), ProgressLevel::Info);
+        assert_eq!(overall(&fetching), 0.625);
+        assert_eq!(
+            plain(&fetching).as_deref(),
+            Some("[3/4] Fetching packages: glibc (3/12)")
+        );
+
+        let warning = update(InstallStage::Configure, 4, None, ProgressLevel::Warning);
+        assert_eq!(overall(&warning), 0.75);
+        assert_eq!(describe(&warning), "[4/4] Configuring: warning: glibc (3/12)");
+
+        let output = update(InstallStage::Mounting, 1, Some(7.0), ProgressLevel::Debug);
+        assert_eq!(overall(&output), 0.25, "fractions beyond the stage are clamped");
+        assert_eq!(plain(&output), None);
+    }
+}
@@ -9,15 +9,28 @@
 //! partitioning strategy, and apply it.
 
 use crate::{CliStep, FrontendStep, install_model, selections};
-use installer::{DisplayInfo, Icon, Installer, Model, StepError, register_step};
-use protocols::lichen::{
-    osinfo::OsInfo,
-    storage::{
-        disks::{Disk, ListDisksRequest},
-        provisioner::{StrategyDefinition, StrategyPlan, TryStrategyRequest},
+use installer::{DiskId, DisplayInfo, Icon, Installer, Model, StepError, register_step};
+use protocols::{
+    REFRESHED_MOUNTPOINTS,
+    lichen::{
+        install::{ExistingMount, HomeOwner, ProbedPartition},
+        osinfo::OsInfo,
+        storage::{
+            disks::{Disk, DiskHealth, ListDisksRequest, WatchDisksRequest},
+            provisioner::{StrategyDefinition, StrategyPlan, TryStrategyRequest},
+        },
     },
 };
-use std::{collections::BTreeSet, env, path::Path};
+use std::{
+    collections::BTreeSet,
+    env,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use tokio::task::JoinHandle;
 
 /// Root filesystem choices as strategy id suffixes, first entry is default
 const FILESYSTEM_CHOICES: &[(&str, &str, &str)] = &[
@@ -35,37 +48,7 @@ const FILESYSTEM_PACKAGES: &[(&str, &[&str])] = &[
 ];
 
 pub async fn run(info: &OsInfo, installer: &Installer, model: &mut Model) -> Result<(), StepError> {
-    // Grab the list of disks. Loopback devices stay hidden unless explicitly
-    // requested, which allows safe end-to-end testing against a losetup disk.
-    let mut client = installer.disks().await?;
-    let exclude_loopback = env::var_os("LICHEN_INCLUDE_LOOPBACK").is_none();
-    let disks = client
-        .list_disks(ListDisksRequest { exclude_loopback })
-        .await?
-        .into_inner();
-    let renderable_devices = disks
-        .disks
-        .iter()
-        .enumerate()
-        .map(|(index, disk)| (index, render_disk(disk), "".to_string()))
-        .collect::<Vec<_>>();
-    let os_name = info
-        .metadata
-        .as_ref()
-        .and_then(|meta| meta.identity.as_ref())
-        .map(|ident| ident.display.clone())
-        .unwrap_or("Unknown OS".into());
-    let initial_index = disks
-        .disks
-        .iter()
-        .position(|disk| disk.device == model.storage.disk)
-        .unwrap_or(0);
-    let selected_index = cliclack::select(format!("What disk would you like to install {os_name} on?"))
-        .items(&renderable_devices)
-        .initial_value(initial_index)
-        .interact()
-        .map_err(|_| StepError::UserAborted)?;
-    let selected_disk = disks.disks.get(selected_index).ok_or(StepError::UserAborted)?;
+    let selected_disk = &choose_disk(info, installer, model).await?;
 
     tracing::info!("Selected disk: {:?}", selected_disk.device);
 
@@ -124,6 +107,8 @@ pub async fn run(info: &OsInfo, installer: &Installer, model: &mut Model) -> Res
         })
         .collect::<Vec<_>>();
     let refresh_index = viable.len();
+    let keep_home_index = viable.len() + 1;
+    let home_keepable = discovered.as_ref().is_some_and(|found| !found.mounts.is_empty());
 
     if discovered.is_some() {
         items.push((
@@ -132,39 +117,74 @@ pub async fn run(info: &OsInfo, installer: &Installer, model: &mut Model) -> Res
             "Reinstall using the settings and package selection found on the disk".to_string(),
         ));
     }
+    if home_keepable {
+        items.push((
+            keep_home_index,
+            "Refresh OS, keep /home",
+            "Reinstall with the settings found, erasing root and boot but keeping /home".to_string(),
+        ));
+    }
 
     let initial_choice = if model.imported {
         display
             .iter()
             .position(|&idx| base_strategy_id(&viable[idx].0.id) == base_strategy_id(&model.storage.strategy_id))
             .unwrap_or(0)
+    } else if home_keepable {
+        keep_home_index
     } else if discovered.is_some() {
         refresh_index
     } else {
         0
     };
 
-    let choice = cliclack::select("How should the disk be partitioned?")
-        .items(&items)
-        .initial_value(initial_choice)
-        .interact()
-        .map_err(|_| StepError::UserAborted)?;
+    let choice = loop {
+        let choice = cliclack::select("How should the disk be partitioned?")
+            .items(&items)
+            .initial_value(initial_choice)
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+
+        if (choice == refresh_index || choice == keep_home_index)
+            && let Some(discovered_model) = &discovered
+        {
+            let mut refreshed = install_model::from_kdl(&discovered_model.contents).map_err(|e| {
+                StepError::Failed(format!("failed to parse model from {}: {e}", discovered_model.device))
+            })?;
+            refreshed.imported = true;
+
+            let mut packages: BTreeSet<String> = refreshed.software.packages.iter().cloned().collect();
+            packages.extend(selections::mandatory(&refreshed.software.selection)?);
+            refreshed.software.packages = packages.into_iter().collect();
+
+            // Declining goes back to the partitioning choice
+            if !confirm_refresh(model, &refreshed, &discovered_model.device)? {
+                continue;
+            }
+            *model = refreshed;
+        }
+
+        break choice;
+    };
 
-    if choice == refresh_index
+    if choice == keep_home_index
         && let Some(discovered_model) = &discovered
     {
-        *model = install_model::from_kdl(&discovered_model.contents).map_err(|e| {
-            StepError::Failed(format!(
-                "failed to parse model from {}: {}",
-                discovered_model.device,
-                install_model::parse_error_detail(&e)
-            ))
-        })?;
-        model.imported = true;
-
-        let mut packages: BTreeSet<String> = model.software.packages.iter().cloned().collect();
-        packages.extend(selections::mandatory(&model.software.selection)?);
-        model.software.packages = packages.into_iter().collect();
+        let mounts = discovered_model.mounts.clone();
+        adopt_home_owners(model, &discovered_model.home_owners);
+
+        cliclack::note(
+            format!("Planned changes for {}", selected_disk.device),
+            render_refresh(&mounts),
+        )
+        .map_err(|_| StepError::UserAborted)?;
+
+        model.storage.disk = selected_disk.device.clone();
+        model.storage.disk_id = DiskId::of(selected_disk);
+        model.storage.disk_display = render_disk(selected_disk);
+        model.storage.plan = None;
+        model.storage.refresh = Some(mounts);
+        return Ok(());
     }
 
     let (strategy, plan) = if choice == refresh_index {
@@ -191,14 +211,48 @@ pub async fn run(info: &OsInfo, installer: &Installer, model: &mut Model) -> Res
     .map_err(|_| StepError::UserAborted)?;
 
     model.storage.disk = selected_disk.device.clone();
+    model.storage.disk_id = DiskId::of(selected_disk);
     model.storage.disk_display = render_disk(selected_disk);
     model.storage.strategy_id = strategy.id.clone();
     model.storage.strategy_name = strategy.name.clone();
     model.storage.plan = Some(plan.clone());
+    model.storage.refresh = None;
 
     Ok(())
 }
 
+/// Give the accounts whose home directory is kept the ids owning it, so the
+/// recreated accounts own their files again; useradd must not create it
+fn adopt_home_owners(model: &mut Model, owners: &[HomeOwner]) {
+    for user in &mut model.accounts.users {
+        let directory = if user.home.is_empty() {
+            Some(user.username.as_str())
+        } else {
+            user.home.strip_prefix("/home/")
+        };
+        let Some(owner) = owners.iter().find(|owner| Some(owner.name.as_str()) == directory) else {
+            continue;
+        };
+
+        user.uid = Some(owner.uid);
+        user.gid = Some(owner.gid);
+        user.create_home = false;
+    }
+}
+
+/// Show what adopting the discovered settings would change, and confirm it
+fn confirm_refresh(current: &Model, refreshed: &Model, device: &str) -> Result<bool, StepError> {
+    let diff = install_model::diff(current, refreshed);
+
+    cliclack::note(format!("Settings found on {device}"), diff.to_string().trim_end())
+        .map_err(|_| StepError::UserAborted)?;
+
+    cliclack::confirm("Refresh the OS with these settings?")
+        .initial_value(true)
+        .interact()
+        .map_err(|_| StepError::UserAborted)
+}
+
 /// A strategy id with any filesystem-variant suffix removed
 fn base_strategy_id(id: &str) -> &str {
     FILESYSTEM_CHOICES
@@ -286,13 +340,211 @@ fn select_filesystem(
     Ok(available[picked].0.clone())
 }
 
+/// Ask which disk to install onto, listing the disks again until one is
+/// picked from a list that is still current
+async fn choose_disk(info: &OsInfo, installer: &Installer, model: &Model) -> Result<Disk, StepError> {
+    loop {
+        if let Some(disk) = offer_disks(info, installer, model).await? {
+            return Ok(disk);
+        }
+    }
+}
+
+/// Offer the disks once. `None` when the user asked for a rescan, or the
+/// disks changed before the answer came.
+async fn offer_disks(info: &OsInfo, installer: &Installer, model: &Model) -> Result<Option<Disk>, StepError> {
+    // Grab the list of disks. Loopback devices stay hidden unless explicitly
+    // requested, which allows safe end-to-end testing against a losetup disk.
+    let mut client = installer.disks().await?;
+    let exclude_loopback = env::var_os("LICHEN_INCLUDE_LOOPBACK").is_none();
+    let disks = client
+        .list_disks(ListDisksRequest { exclude_loopback })
+        .await?
+        .into_inner();
+    // Name what would be overwritten; a probe failure only loses the hints
+    let probed = match installer.install().await?.probe_disks(()).await {
+        Ok(response) => response.into_inner().partitions,
+        Err(status) => {
+            tracing::warn!("cannot probe existing partitions: {}", status.message());
+            Vec::new()
+        }
+    };
+    // Only watch once probing is done: it mounts each partition in turn,
+    // which would otherwise be reported as the disks changing
+    let watch = DiskWatch::start(installer, exclude_loopback, &disks.disks).await;
+    let renderable_devices = disks
+        .disks
+        .iter()
+        .enumerate()
+        .map(|(index, disk)| {
+            // The backend refuses to provision a busy disk; say why up front
+            let hint = match &disk.busy {
+                Some(reason) => format!("in use: {reason}"),
+                None => existing_systems(disk, &probed),
+            };
+            (index, render_disk(disk), hint)
+        })
+        .chain([(disks.disks.len(), "Rescan disks".to_string(), "".to_string())])
+        .collect::<Vec<_>>();
+    let os_name = info
+        .metadata
+        .as_ref()
+        .and_then(|meta| meta.identity.as_ref())
+        .map(|ident| ident.display.clone())
+        .unwrap_or("Unknown OS".into());
+    // An imported model names its disk by a stable id where it can; the
+    // device node it recorded may now belong to another disk
+    let recorded = match &model.storage.disk_id {
+        Some(id) => {
+            &id.resolve(&disks.disks)
+                .map_err(|e| StepError::Failed(format!("cannot find the install-model's disk: {e}")))?
+                .device
+        }
+        None => &model.storage.disk,
+    };
+    let initial_index = disks
+        .disks
+        .iter()
+        .position(|disk| &disk.device == recorded)
+        .unwrap_or(0);
+    let selected_index = cliclack::select(format!("What disk would you like to install {os_name} on?"))
+        .items(&renderable_devices)
+        .initial_value(initial_index)
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
+
+    if selected_index == disks.disks.len() {
+        return Ok(None);
+    }
+    // The disk picked may have gone, or be another disk under the same node
+    if watch.changed() {
+        cliclack::log::info("The disks changed while choosing; listing them again")
+            .map_err(|_| StepError::UserAborted)?;
+        return Ok(None);
+    }
+
+    disks
+        .disks
+        .get(selected_index)
+        .cloned()
+        .map(Some)
+        .ok_or(StepError::UserAborted)
+}
+
+/// Watches for the listed disks changing, such as a USB disk plugged in
+/// while the question is open; stops watching when dropped
+struct DiskWatch {
+    changed: Arc<AtomicBool>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl DiskWatch {
+    /// Watch from the disks as listed. A backend without the watch only
+    /// loses the check.
+    async fn start(installer: &Installer, exclude_loopback: bool, known: &[Disk]) -> Self {
+        let changed = Arc::new(AtomicBool::new(false));
+        let request = WatchDisksRequest {
+            exclude_loopback,
+            known: known.to_vec(),
+        };
+
+        let stream = match installer.disks().await {
+            Ok(mut client) => client.watch_disks(request).await.map_err(|status| status.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let task = match stream {
+            Ok(response) => {
+                let mut stream = response.into_inner();
+                let changed = changed.clone();
+                Some(tokio::spawn(async move {
+                    if let Ok(Some(event)) = stream.message().await {
+                        tracing::debug!("Disk event: {:?}", event.kind());
+                        changed.store(true, Ordering::Relaxed);
+                    }
+                }))
+            }
+            Err(e) => {
+                tracing::debug!("cannot watch disks: {e}");
+                None
+            }
+        };
+
+        Self { changed, task }
+    }
+
+    fn changed(&self) -> bool {
+        self.changed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for DiskWatch {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
 fn render_disk(disk: &Disk) -> String {
-    format!(
+    let mut rendered = format!(
         "{} - {} - {}",
         disk.device,
         disk.model.as_ref().unwrap_or(&"Unknown".into()),
         disk.display_size,
-    )
+    );
+
+    // A USB stick makes a slow, short-lived root, and a failing drive will
+    // lose the installation: neither should be picked without noticing
+    if disk.transport.as_deref() == Some("usb") {
+        rendered.push_str(" [USB]");
+    } else if disk.removable {
+        rendered.push_str(" [removable]");
+    }
+    match disk.health() {
+        DiskHealth::Failing => rendered.push_str(&format!(
+            " [FAILING: {}]",
+            disk.health_detail
+                .as_deref()
+                .unwrap_or("SMART reports the disk is failing")
+        )),
+        DiskHealth::Warning => rendered.push_str(" [SMART errors logged]"),
+        DiskHealth::Passed | DiskHealth::Unknown => {}
+    }
+
+    rendered
+}
+
+/// The operating systems found on a disk's partitions, e.g.
+/// "Windows on /dev/nvme0n1p3"
+fn existing_systems(disk: &Disk, probed: &[ProbedPartition]) -> String {
+    probed
+        .iter()
+        .filter(|partition| disk.partitions.iter().any(|part| part.device == partition.device))
+        .filter_map(|partition| Some(format!("{} on {}", partition.os.as_ref()?, partition.device)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// What a refresh keeping /home keeps and what it erases
+pub fn render_refresh(mounts: &[ExistingMount]) -> String {
+    let describe = |mount: &ExistingMount| match &mount.subvol {
+        Some(subvol) => format!(
+            "  {} on {} ({} subvolume {subvol})\n",
+            mount.mountpoint, mount.device, mount.fstype
+        ),
+        None => format!("  {} on {} ({})\n", mount.mountpoint, mount.device, mount.fstype),
+    };
+    let (erased, kept): (Vec<_>, Vec<_>) = mounts
+        .iter()
+        .partition(|mount| REFRESHED_MOUNTPOINTS.contains(&mount.mountpoint.as_str()));
+
+    let mut out = String::from("Kept, with its files:\n");
+    kept.into_iter().for_each(|mount| out.push_str(&describe(mount)));
+    out.push_str("\nErased and recreated:\n");
+    erased.into_iter().for_each(|mount| out.push_str(&describe(mount)));
+    out.push_str("\nThe disk is not repartitioned.\n");
+
+    out
 }
 
 pub fn render_plan(plan: &StrategyPlan) -> String {
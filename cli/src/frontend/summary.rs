@@ -2,66 +2,293 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use super::storage;
+use super::{progress, storage};
 use crate::{CliStep, FrontendStep, install_model};
-use installer::{DisplayInfo, Icon, Installer, Model, StepError, register_step};
+use installer::{DisplayInfo, DisplayManager, Icon, Installer, Model, RootAccount, StepError, register_step};
 use protocols::lichen::{
-    install::{InstallSystemRequest, RepoSpec, TargetMount, UserSpec, WriteSystemModelRequest},
+    install::{
+        self, Autologin, InstallProgress, InstallSystemRequest, LocaleOverride, RefreshTargetRequest, RepoSpec,
+        TargetMount, UserSpec, WriteSystemModelRequest,
+    },
+    keyboard::KeyboardLayout,
     storage::provisioner::ApplyStrategyRequest,
 };
 
 pub async fn run(installer: &Installer, model: &mut Model) -> Result<(), StepError> {
     storage::ensure_filesystem_packages(model);
 
-    let plan = model
-        .storage
-        .plan
-        .as_ref()
-        .ok_or_else(|| StepError::Failed("no storage configuration was selected".to_string()))?;
+    let storage_changes = match (&model.storage.refresh, &model.storage.plan) {
+        (Some(mounts), _) => storage::render_refresh(mounts),
+        (None, Some(plan)) => storage::render_plan(plan),
+        (None, None) => return Err(StepError::Failed("no storage configuration was selected".to_string())),
+    };
     let mut text = String::new();
 
     text.push_str(&format!("Target disk:  {}\n", model.storage.disk_display));
     text.push_str(&format!("Strategy:     {}\n", model.storage.strategy_name));
     text.push_str(&format!("Locale:       {}\n", model.region.language));
-    text.push_str(&format!("Timezone:     {}\n", model.region.timezone));
+    if model.region.formats.is_overridden() {
+        let formats = model
+            .region
+            .formats
+            .categories()
+            .filter_map(|(category, locale)| locale.map(|locale| format!("{category}={locale}")))
+            .collect::<Vec<_>>();
+        text.push_str(&format!("Formats:      {}\n", formats.join(", ")));
+    }
+    text.push_str(&format!(
+        "Timezone:     {} (hardware clock: {})\n",
+        model.region.timezone,
+        if model.region.rtc_local { "local time" } else { "UTC" }
+    ));
+    if !model.region.ntp_servers.is_empty() {
+        text.push_str(&format!("NTP servers:  {}\n", model.region.ntp_servers.join(", ")));
+    }
+    text.push_str(&format!(
+        "Keyboard:     {}{} (console: {})\n",
+        model.keyboard.layout,
+        if model.keyboard.variant.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", model.keyboard.variant)
+        },
+        model.keyboard.keymap,
+    ));
     text.push_str(&format!(
         "Desktop:      {} ({} packages)\n",
         model.software.selection,
         model.software.packages.len()
     ));
 
-    match &model.accounts.user {
-        Some(user) => text.push_str(&format!("User: {} ({})\n", user.username, user.real_name)),
-        None => text.push_str("User: not configured\n"),
+    if model.accounts.users.is_empty() {
+        text.push_str("User: not configured\n");
+    }
+    for user in &model.accounts.users {
+        text.push_str(&format!(
+            "User: {} ({}){}\n",
+            user.username,
+            user.real_name,
+            if user.admin { ", administrator" } else { "" }
+        ));
+        if !user.ssh_keys.is_empty() {
+            text.push_str(&format!("  SSH keys: {}\n", user.ssh_keys.len()));
+        }
+    }
+    if model.accounts.autologin
+        && let Some(user) = model.accounts.users.first()
+    {
+        match model.software.display_manager() {
+            Some(_) => text.push_str(&format!("Autologin: {}\n", user.username)),
+            None => {
+                let _ = cliclack::log::warning(format!(
+                    "Autologin is skipped: desktop {} installs no display manager",
+                    model.software.selection
+                ));
+            }
+        }
+    }
+    if model.accounts.ssh_keys_only {
+        text.push_str("SSH: key authentication only, root login disabled\n");
     }
 
     text.push_str(&format!(
         "Root account: {}\n",
-        if model.accounts.root_password_hash.is_some() {
-            "password set"
-        } else {
-            "not configured"
+        match &model.accounts.root {
+            Some(RootAccount::Password(_)) => "password set",
+            Some(RootAccount::Locked) => "locked, administrators use sudo",
+            Some(RootAccount::Disabled) => "disabled",
+            None => "not configured",
         }
     ));
     text.push('\n');
-    text.push_str(&storage::render_plan(plan));
+    text.push_str(&storage_changes);
 
     cliclack::note("Installation summary", text).map_err(|_| StepError::UserAborted)?;
+    // Settings the install would refuse are reported before asking to erase anything
+    validate(installer, model).await?;
 
-    let confirmed = cliclack::confirm(format!(
-        "Erase {} and install? ALL DATA ON THIS DISK WILL BE DESTROYED.",
-        model.storage.disk,
-    ))
-    // Default to no: the six prompts before this one all default to yes, and
-    // a reflexive Enter here wipes the disk.
-    .initial_value(false)
-    .interact()
-    .map_err(|_| StepError::UserAborted)?;
+    let question = if model.storage.refresh.is_some() {
+        format!(
+            "Erase the root and boot filesystems on {} and reinstall? /home is kept.",
+            model.storage.disk
+        )
+    } else {
+        format!(
+            "Erase {} and install? ALL DATA ON THIS DISK WILL BE DESTROYED.",
+            model.storage.disk
+        )
+    };
+    let confirmed = cliclack::confirm(question)
+        // Default to no: the six prompts before this one all default to yes, and
+        // a reflexive Enter here wipes the disk.
+        .initial_value(false)
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
 
     if !confirmed {
         return Err(StepError::UserAborted);
     }
 
+    let mut view = progress::View::start("Installing AerynOS to the target disk (this can take several minutes)");
+    let result = install(installer, model, &mut |update| view.update(update)).await;
+
+    match result {
+        Ok(()) => {
+            view.stop("AerynOS installed");
+            Ok(())
+        }
+        Err(e) => {
+            view.error("Installation failed");
+            Err(e)
+        }
+    }
+}
+
+/// Check the model's settings with the backend, refusing any the install
+/// would fail on before the target is partitioned, then prepare the target
+/// filesystems, write the model records and install the target, passing
+/// each progress update from the backend to `progress`
+pub async fn install(
+    installer: &Installer,
+    model: &Model,
+    progress: &mut dyn FnMut(&InstallProgress),
+) -> Result<(), StepError> {
+    let system_model = install_model::system_model_kdl(model);
+    let mut request = install_request(model, &system_model)?;
+    let mut install = installer.install().await?;
+    install.validate_install(request.clone()).await?;
+
+    request.mounts = prepare_target(installer, model).await?;
+    let root_device = request
+        .mounts
+        .iter()
+        .find(|mount| mount.mountpoint == "/")
+        .map(|mount| mount.device.clone())
+        .ok_or_else(|| StepError::Failed("the target has no root mount".to_string()))?;
+    install
+        .write_system_model(WriteSystemModelRequest {
+            root_device,
+            system_model,
+            install_model: install_model::to_kdl(model),
+        })
+        .await?;
+
+    let mut stream = install.install_system(request).await?.into_inner();
+
+    while let Some(update) = stream.message().await? {
+        if update.finished {
+            return Ok(());
+        }
+
+        progress(&update);
+    }
+
+    Err(StepError::Failed("install stream ended without completing".to_string()))
+}
+
+/// Check the model's settings with the backend, as the install will, without
+/// touching any disk
+pub async fn validate(installer: &Installer, model: &Model) -> Result<(), StepError> {
+    let request = install_request(model, &install_model::system_model_kdl(model))?;
+    installer.install().await?.validate_install(request).await?;
+
+    Ok(())
+}
+
+/// The install request for the model, without the target mounts
+fn install_request(model: &Model, system_model: &str) -> Result<InstallSystemRequest, StepError> {
+    let repositories = install_model::repositories(system_model)
+        .map_err(|e| StepError::Failed(format!("generated system-model failed to parse: {e}")))?
+        .into_iter()
+        .map(|repo| RepoSpec {
+            id: repo.id,
+            uri: repo.uri,
+        })
+        .collect();
+
+    Ok(InstallSystemRequest {
+        mounts: Vec::new(),
+        locale: model.region.language.clone(),
+        timezone: model.region.timezone.clone(),
+        root_password_hash: match &model.accounts.root {
+            Some(RootAccount::Password(hash)) => hash.clone(),
+            _ => String::new(),
+        },
+        root_account: match &model.accounts.root {
+            Some(RootAccount::Password(_)) => install::RootAccount::Password,
+            Some(RootAccount::Locked) => install::RootAccount::Locked,
+            Some(RootAccount::Disabled) => install::RootAccount::Disabled,
+            None => install::RootAccount::Unspecified,
+        } as i32,
+        users: model
+            .accounts
+            .users
+            .iter()
+            .map(|user| UserSpec {
+                username: user.username.clone(),
+                real_name: user.real_name.clone(),
+                password_hash: user.password_hash.clone(),
+                shell: user.shell.clone(),
+                admin: user.admin,
+                groups: user.groups.clone(),
+                uid: user.uid,
+                gid: user.gid,
+                home: user.home.clone(),
+                no_create_home: !user.create_home,
+                ssh_keys: user.ssh_keys.clone(),
+            })
+            .collect(),
+        ssh_keys_only: model.accounts.ssh_keys_only,
+        autologin: autologin(model),
+        repositories,
+        keyboard: Some(KeyboardLayout {
+            layout: model.keyboard.layout.clone(),
+            variant: model.keyboard.variant.clone(),
+            keymap: model.keyboard.keymap.clone(),
+        }),
+        locale_overrides: model
+            .region
+            .formats
+            .categories()
+            .filter_map(|(category, locale)| {
+                locale.map(|locale| LocaleOverride {
+                    category: category.to_string(),
+                    locale: locale.to_string(),
+                })
+            })
+            .collect(),
+        rtc_local: model.region.rtc_local,
+        ntp_servers: model.region.ntp_servers.clone(),
+    })
+}
+
+/// Create the target filesystems: partition the disk with the chosen
+/// strategy, or recreate a previous installation's in place, keeping /home
+async fn prepare_target(installer: &Installer, model: &Model) -> Result<Vec<TargetMount>, StepError> {
+    if let Some(existing) = &model.storage.refresh {
+        installer
+            .install()
+            .await?
+            .refresh_target(RefreshTargetRequest {
+                mounts: existing.clone(),
+            })
+            .await?;
+
+        // The backend derives a btrfs root's @home mount from the root itself
+        let root = existing.iter().find(|mount| mount.mountpoint == "/");
+        return Ok(existing
+            .iter()
+            .filter(|mount| {
+                mount.mountpoint == "/"
+                    || !root.is_some_and(|root| root.device == mount.device && mount.subvol.is_some())
+            })
+            .map(|mount| TargetMount {
+                device: mount.device.clone(),
+                mountpoint: mount.mountpoint.clone(),
+            })
+            .collect());
+    }
+
     let mut provisioner = installer.provisioner().await?;
     let applied = provisioner
         .apply_strategy(ApplyStrategyRequest {
@@ -73,32 +300,8 @@ pub async fn run(installer: &Installer, model: &mut Model) -> Result<(), StepErr
     let applied_plan = applied
         .plan
         .ok_or_else(|| StepError::Failed("backend returned no applied plan".to_string()))?;
-    let root_device = applied_plan
-        .role_mounts
-        .iter()
-        .find(|role_mount| role_mount.mountpoint == "/")
-        .map(|role_mount| role_mount.device.clone())
-        .ok_or_else(|| StepError::Failed("applied plan has no root mount".to_string()))?;
-    let system_model = install_model::system_model_kdl(model);
-    let install_record = install_model::to_kdl(model);
-    let repositories = install_model::repositories(&system_model)
-        .map_err(|e| StepError::Failed(format!("generated system-model failed to parse: {e}")))?
-        .into_iter()
-        .map(|repo| RepoSpec {
-            id: repo.id,
-            uri: repo.uri,
-        })
-        .collect();
-    let mut install = installer.install().await?;
-    install
-        .write_system_model(WriteSystemModelRequest {
-            root_device,
-            system_model,
-            install_model: install_record,
-        })
-        .await?;
 
-    let mounts = applied_plan
+    Ok(applied_plan
         .role_mounts
         .iter()
         .filter(|role_mount| role_mount.mountpoint.starts_with('/'))
@@ -106,60 +309,27 @@ pub async fn run(installer: &Installer, model: &mut Model) -> Result<(), StepErr
             device: role_mount.device.clone(),
             mountpoint: role_mount.mountpoint.clone(),
         })
-        .collect();
-
-    let spinner = cliclack::spinner();
-    // Count-match, not subset-match: this is the last gate before an
-    // irreversible whole-disk wipe, and applying the strategy to only the
-    // disks that happened to resolve is not what the user approved.
-    spinner.start("Installing AerynOS to the target disk (this can take several minutes)");
-
-    let mut stream = match install
-        .install_system(InstallSystemRequest {
-            mounts,
-            locale: model.region.language.clone(),
-            timezone: model.region.timezone.clone(),
-            root_password_hash: model.accounts.root_password_hash.clone().unwrap_or_default(),
-            user: model.accounts.user.as_ref().map(|user| UserSpec {
-                username: user.username.clone(),
-                real_name: user.real_name.clone(),
-                password_hash: user.password_hash.clone(),
-            }),
-            repositories,
-        })
-        .await
-    {
-        Ok(response) => response.into_inner(),
-        Err(e) => {
-            spinner.error("Installation failed");
-            return Err(e.into());
-        }
-    };
+        .collect())
+}
 
-    loop {
-        match stream.message().await {
-            Ok(Some(update)) => {
-                if update.finished {
-                    spinner.stop("AerynOS installed");
-                    break;
-                }
-
-                if !update.message.is_empty() {
-                    spinner.set_message(update.message);
-                }
-            }
-            Ok(None) => {
-                spinner.error("Installation failed");
-                return Err(StepError::Failed("install stream ended without completing".to_string()));
-            }
-            Err(e) => {
-                spinner.error("Installation failed");
-                return Err(e.into());
-            }
-        }
+/// Autologin for the primary user, through the display manager the
+/// selection installs; a desktop without one has no login screen to skip
+fn autologin(model: &Model) -> Option<Autologin> {
+    if !model.accounts.autologin {
+        return None;
     }
 
-    Ok(())
+    let user = model.accounts.users.first()?;
+    let display_manager = match model.software.display_manager()? {
+        DisplayManager::Gdm => install::DisplayManager::Gdm,
+        DisplayManager::Sddm => install::DisplayManager::Sddm,
+        DisplayManager::CosmicGreeter => install::DisplayManager::CosmicGreeter,
+    };
+
+    Some(Autologin {
+        user: user.username.clone(),
+        display_manager: display_manager as i32,
+    })
 }
 
 register_step! {
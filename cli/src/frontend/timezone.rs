@@ -3,21 +3,84 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use crate::{CliStep, FrontendStep};
-use chrono_tz::TZ_VARIANTS;
 use installer::{DisplayInfo, Icon, Installer, Model, StepError, register_step};
+use protocols::{
+    is_valid_ntp_server,
+    lichen::{locales::GetLocaleRequest, timezones::ListTimezonesRequest},
+};
 
-pub async fn run(_installer: &Installer, model: &mut Model) -> Result<(), StepError> {
+pub async fn run(installer: &Installer, model: &mut Model) -> Result<(), StepError> {
     if model.imported && !model.region.timezone.is_empty() {
         let _ = cliclack::log::info(format!("Using imported timezone {}", model.region.timezone));
         return Ok(());
     }
-    let items = TZ_VARIANTS
+
+    // The chosen locale's territory seeds the proposal; an unknown locale
+    // just means the backend falls back to the live system's zone
+    let mut locales = installer.locales().await?;
+    let territory = locales
+        .get_locale(GetLocaleRequest {
+            name: model.region.language.clone(),
+        })
+        .await
+        .ok()
+        .and_then(|locale| locale.into_inner().territory)
+        .map(|territory| territory.code)
+        .unwrap_or_default();
+
+    let mut timezones = installer.timezones().await?;
+    let listing = timezones
+        .list_timezones(ListTimezonesRequest { territory })
+        .await?
+        .into_inner();
+    let proposed_region = listing
+        .regions
+        .iter()
+        .position(|region| {
+            region
+                .countries
+                .iter()
+                .any(|country| country.zones.iter().any(|zone| zone.name == listing.proposed))
+        })
+        .unwrap_or(0);
+
+    let region_items = listing
+        .regions
         .iter()
-        .map(|tz| (tz.to_string(), tz.to_string(), ""))
+        .enumerate()
+        .map(|(index, region)| {
+            (
+                index,
+                region.name.clone(),
+                format!("{} countries", region.countries.len()),
+            )
+        })
+        .collect::<Vec<_>>();
+    let region_index = cliclack::select("Select your region")
+        .items(&region_items)
+        .initial_value(proposed_region)
+        .filter_mode()
+        .set_size(12)
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
+    let region = listing.regions.get(region_index).ok_or(StepError::UserAborted)?;
+
+    let zone_items = region
+        .countries
+        .iter()
+        .flat_map(|country| {
+            country.zones.iter().map(move |zone| {
+                (
+                    zone.name.clone(),
+                    format!("{} - {}", country.name, zone.name),
+                    zone.comment.clone(),
+                )
+            })
+        })
         .collect::<Vec<_>>();
     let picked = cliclack::select("Select your timezone")
-        .items(&items)
-        .initial_value(model.region.timezone.clone())
+        .items(&zone_items)
+        .initial_value(listing.proposed.clone())
         .filter_mode()
         .set_size(12)
         .interact()
@@ -26,6 +89,26 @@ pub async fn run(_installer: &Installer, model: &mut Model) -> Result<(), StepEr
     tracing::info!("Selected timezone {picked}");
     model.region.timezone = picked;
 
+    model.region.rtc_local =
+        cliclack::confirm("Keep the hardware clock in local time? (needed when dual-booting Windows)")
+            .initial_value(model.region.rtc_local)
+            .interact()
+            .map_err(|_| StepError::UserAborted)?;
+
+    let servers: String = cliclack::input("NTP servers, separated by spaces (leave empty for the default pool)")
+        .default_input(&model.region.ntp_servers.join(" "))
+        .required(false)
+        .validate(|input: &String| {
+            if input.split_whitespace().all(is_valid_ntp_server) {
+                Ok(())
+            } else {
+                Err("use host names or addresses, with an optional :port, separated by spaces")
+            }
+        })
+        .interact()
+        .map_err(|_| StepError::UserAborted)?;
+    model.region.ntp_servers = servers.split_whitespace().map(str::to_string).collect();
+
     Ok(())
 }
 
@@ -14,11 +14,20 @@
 //!
 //! The installer is thereby a function from system-model to install-model: a
 //! bare system-model can be ingested (packages) and decorated interactively
-//! into a full install-model.
+//! into a full install-model. The documents themselves, their schema and
+//! its migrations live in the `models` crate; this module maps them to and
+//! from the installer's [`Model`].
 
 use chrono::Utc;
-use installer::{Model, User};
-use kdl::{KdlDocument, KdlEntry, KdlError, KdlNode};
+use installer::{DiskId, Model, RootAccount, User};
+use models::{
+    Decoded, Error, Mode,
+    diff::Diff,
+    install::{self, InstallModel, Rtc},
+    system::SystemModel,
+};
+
+pub use models::install::is_install_model;
 
 /// A repository definition extracted from a system-model document
 pub struct Repository {
@@ -28,314 +37,316 @@ pub struct Repository {
 
 /// Extract directly-addressable repos carrying a `uri` node
 /// from a system-model document
-pub fn repositories(content: &str) -> Result<Vec<Repository>, KdlError> {
-    let doc: KdlDocument = content.parse()?;
-    let mut repos = Vec::new();
-
-    if let Some(node) = doc.get("repositories") {
-        for repo in node.iter_children() {
-            if let Some(uri) = repo_uri(repo) {
-                repos.push(Repository {
-                    id: repo.name().value().to_string(),
-                    uri,
-                });
-            }
-        }
-    }
-
-    Ok(repos)
-}
-
-/// The index URI of a repository node: a direct `uri`, or gotten from
-/// `base-uri` + `channel` + `version` + `arch` the same way moss builds it
-fn repo_uri(repo: &KdlNode) -> Option<String> {
-    repo.children()?.get("uri").and_then(first_arg).map(str::to_string)
+pub fn repositories(content: &str) -> Result<Vec<Repository>, Error> {
+    let system = SystemModel::from_kdl(content, Mode::Lenient)?.value;
+
+    Ok(system
+        .direct_repositories()
+        .map(|(id, uri)| Repository {
+            id: id.to_string(),
+            uri: uri.to_string(),
+        })
+        .collect())
 }
 
 /// Serialize the collected installation model to KDL text
 pub fn to_kdl(model: &Model) -> String {
-    let mut children = KdlDocument::new();
-
-    let mut push_arg = |name: &str, value: &str| {
-        if !value.is_empty() {
-            let mut child = KdlNode::new(name);
-            child.push(KdlEntry::new(value));
-            children.nodes_mut().push(child);
-        }
-    };
+    InstallModel {
+        installed: Some(Utc::now().to_rfc3339()),
+        ..record(model)
+    }
+    .to_kdl()
+}
 
-    push_arg("strategy", &model.storage.strategy_id);
-    push_arg("disk", &model.storage.disk);
-    push_arg("locale", &model.region.language);
-    push_arg("timezone", &model.region.timezone);
-    push_arg("desktop", &model.software.selection);
-    push_arg("installed", &Utc::now().to_rfc3339());
+/// What adopting `after` would change from `before`
+pub fn diff(before: &Model, after: &Model) -> Diff {
+    Diff::between(&record(before), &record(after))
+}
 
-    let mut accounts = KdlNode::new("accounts");
-    let mut account_children = KdlDocument::new();
+/// The installation model as an install-model record, undated
+fn record(model: &Model) -> InstallModel {
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    let formats = &model.region.formats;
+    let keyboard = &model.keyboard;
+
+    let accounts = install::Accounts {
+        root: model.accounts.root.as_ref().map(|account| match account {
+            RootAccount::Password(hash) => install::Root {
+                hash: Some(hash.clone()),
+                mode: None,
+            },
+            RootAccount::Locked => install::Root {
+                hash: None,
+                mode: Some(install::RootMode::Locked),
+            },
+            RootAccount::Disabled => install::Root {
+                hash: None,
+                mode: Some(install::RootMode::Disabled),
+            },
+        }),
+        users: model.accounts.users.iter().map(user_record).collect(),
+        autologin: model.accounts.autologin,
+        sshd: model
+            .accounts
+            .ssh_keys_only
+            .then_some(install::Sshd { keys_only: true }),
+    };
 
-    if let Some(hash) = &model.accounts.root_password_hash {
-        let mut root = KdlNode::new("root");
-        root.push(KdlEntry::new_prop("hash", hash.as_str()));
-        account_children.nodes_mut().push(root);
+    InstallModel {
+        strategy: non_empty(&model.storage.strategy_id),
+        disk: non_empty(&model.storage.disk).map(|device| disk_record(device, model.storage.disk_id.as_ref())),
+        locale: non_empty(&model.region.language),
+        timezone: non_empty(&model.region.timezone),
+        desktop: non_empty(&model.software.selection),
+        formats: formats.is_overridden().then(|| install::Formats {
+            time: formats.time.clone(),
+            numeric: formats.numeric.clone(),
+            monetary: formats.monetary.clone(),
+            paper: formats.paper.clone(),
+            measurement: formats.measurement.clone(),
+        }),
+        rtc: model.region.rtc_local.then_some(Rtc::Local),
+        ntp: model.region.ntp_servers.clone(),
+        keyboard: (!keyboard.layout.is_empty() || !keyboard.keymap.is_empty()).then(|| install::Keyboard {
+            layout: non_empty(&keyboard.layout),
+            variant: non_empty(&keyboard.variant),
+            keymap: non_empty(&keyboard.keymap),
+        }),
+        accounts: (accounts != install::Accounts::default()).then_some(accounts),
+        system_model: Some(system_model(model)),
+        ..Default::default()
     }
+}
 
-    if let Some(user) = &model.accounts.user {
-        let mut user_node = KdlNode::new("user");
-        user_node.push(KdlEntry::new_prop("name", user.username.as_str()));
-        user_node.push(KdlEntry::new_prop("realname", user.real_name.as_str()));
-        user_node.push(KdlEntry::new_prop("hash", user.password_hash.as_str()));
-        account_children.nodes_mut().push(user_node);
-    }
+/// The disk as recorded: the device node it had, and its stable id
+fn disk_record(device: String, id: Option<&DiskId>) -> install::Disk {
+    let mut disk = install::Disk {
+        device,
+        ..Default::default()
+    };
 
-    if !account_children.nodes().is_empty() {
-        accounts.set_children(account_children);
-        children.nodes_mut().push(accounts);
+    match id.cloned() {
+        Some(DiskId::Wwn(wwn)) => disk.wwn = Some(wwn),
+        Some(DiskId::ById(link)) => disk.by_id = Some(link),
+        Some(DiskId::Serial(serial)) => disk.serial = Some(serial),
+        Some(DiskId::ByPath(link)) => disk.by_path = Some(link),
+        None => {}
     }
 
-    let mut system = KdlNode::new("system-model");
-    system.set_children(system_model_document(model));
-    children.nodes_mut().push(system);
-
-    let mut root = KdlNode::new("install-model");
-    root.set_children(children);
+    disk
+}
 
-    let mut doc = KdlDocument::new();
-    doc.nodes_mut().push(root);
-    doc.autoformat();
-    doc.to_string()
+/// One account as recorded; settings at their defaults are omitted
+fn user_record(user: &User) -> install::User {
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+
+    install::User {
+        name: user.username.clone(),
+        realname: user.real_name.clone(),
+        hash: user.password_hash.clone(),
+        admin: user.admin,
+        shell: non_empty(&user.shell),
+        uid: user.uid,
+        gid: user.gid,
+        home: non_empty(&user.home),
+        create_home: user.create_home,
+        groups: user.groups.clone(),
+        ssh_keys: user
+            .ssh_keys
+            .iter()
+            .map(|key| install::SshKey { key: key.clone() })
+            .collect(),
+    }
 }
 
 /// Parse a previously emitted model, tolerantly: absent nodes leave the
 /// corresponding fields at their defaults so the steps prompt as usual.
-pub fn from_kdl(content: &str) -> Result<Model, KdlError> {
-    let doc: KdlDocument = content.parse()?;
+/// Ignored fields are logged.
+pub fn from_kdl(content: &str) -> Result<Model, Error> {
     let mut model = Model::default();
 
-    if doc.get("install-model").is_some() {
-        apply_install_model(&mut model, content)?;
+    let warnings = if is_install_model(content)? {
+        apply_install_model(&mut model, content, Mode::Lenient)?
     } else {
-        apply_system_model(&mut model, content)?;
+        apply_system_model(&mut model, content, Mode::Lenient)?
+    };
+    for warning in warnings {
+        tracing::warn!("{warning}");
     }
 
     Ok(model)
 }
 
-/// Check if the model passed is an install-model.kdl file
-pub fn is_install_model(content: &str) -> Result<bool, KdlError> {
-    let doc: KdlDocument = content.parse()?;
-    Ok(doc.get("install-model").is_some())
+/// A model with none of the settings the interactive steps preselect, such
+/// as the en_US.UTF-8 locale and UTC. Unattended installs start from it, so
+/// a setting the documents leave out is missing rather than silently
+/// defaulted.
+pub fn blank() -> Model {
+    let mut model = Model::default();
+    model.region.language.clear();
+    model.region.timezone.clear();
+    model.storage.strategy_id.clear();
+    model.software.selection.clear();
+    model
 }
 
-/// Apply an install-model.kdl: the installer fields plus,
-/// when present, the nested system-model's package set.
-pub fn apply_install_model(model: &mut Model, content: &str) -> Result<(), KdlError> {
-    let doc: KdlDocument = content.parse()?;
-
-    if let Some(install) = doc.get("install-model") {
-        for child in install.iter_children() {
-            apply_installer_field(model, child);
-        }
-
-        if let Some(system) = install.children().and_then(|children| children.get("system-model"))
-            && let Some(inner) = system.children()
-        {
-            apply_packages(model, inner);
-        }
+/// Apply an install-model.kdl: the installer fields plus, when present, the
+/// nested system-model's package set. Older schema versions are migrated
+/// first. Returns a warning for each field that was ignored.
+pub fn apply_install_model(model: &mut Model, content: &str, mode: Mode) -> Result<Vec<String>, Error> {
+    let Decoded {
+        value: record,
+        warnings,
+    } = InstallModel::from_kdl(content, mode)?;
+
+    if let Some(strategy) = record.strategy {
+        model.storage.strategy_id = strategy.clone();
+        model.storage.strategy_name = strategy;
     }
-
-    Ok(())
-}
-
-/// Apply a bare system-model document to the model: the package set only.
-pub fn apply_system_model(model: &mut Model, content: &str) -> Result<(), KdlError> {
-    let doc: KdlDocument = content.parse()?;
-    apply_packages(model, &doc);
-    Ok(())
-}
-
-/// Apply one installer-section field to the model
-fn apply_installer_field(model: &mut Model, child: &KdlNode) {
-    match child.name().value() {
-        "strategy" => {
-            if let Some(value) = first_arg(child) {
-                model.storage.strategy_id = value.to_string();
-                model.storage.strategy_name = value.to_string();
-            }
-        }
-        "disk" => {
-            if let Some(value) = first_arg(child) {
-                model.storage.disk = value.to_string();
-            }
-        }
-        "locale" => {
-            if let Some(value) = first_arg(child) {
-                model.region.language = value.to_string();
-            }
-        }
-        "timezone" => {
-            if let Some(value) = first_arg(child) {
-                model.region.timezone = value.to_string();
-            }
-        }
-        "desktop" => {
-            if let Some(value) = first_arg(child) {
-                model.software.selection = value.to_string();
+    if let Some(disk) = record.disk {
+        model.storage.disk_id = [
+            disk.wwn.map(DiskId::Wwn),
+            disk.by_id.map(DiskId::ById),
+            disk.serial.map(DiskId::Serial),
+            disk.by_path.map(DiskId::ByPath),
+        ]
+        .into_iter()
+        .flatten()
+        .next();
+        model.storage.disk = disk.device;
+    }
+    if let Some(locale) = record.locale {
+        model.region.language = locale;
+    }
+    if let Some(timezone) = record.timezone {
+        model.region.timezone = timezone;
+    }
+    if let Some(desktop) = record.desktop {
+        model.software.selection = desktop;
+    }
+    if let Some(formats) = record.formats {
+        let slots = [
+            (&mut model.region.formats.time, formats.time),
+            (&mut model.region.formats.numeric, formats.numeric),
+            (&mut model.region.formats.monetary, formats.monetary),
+            (&mut model.region.formats.paper, formats.paper),
+            (&mut model.region.formats.measurement, formats.measurement),
+        ];
+        for (slot, locale) in slots {
+            if locale.is_some() {
+                *slot = locale;
             }
         }
-        "accounts" => {
-            for account in child.iter_children() {
-                match account.name().value() {
-                    "root" => {
-                        model.accounts.root_password_hash = prop(account, "hash").map(str::to_string);
-                    }
-                    "user" => {
-                        if let (Some(name), Some(hash)) = (prop(account, "name"), prop(account, "hash")) {
-                            model.accounts.user = Some(User {
-                                username: name.to_string(),
-                                real_name: prop(account, "realname").unwrap_or_default().to_string(),
-                                password_hash: hash.to_string(),
-                            });
-                        }
-                    }
-                    _ => {}
-                }
-            }
+    }
+    if let Some(rtc) = record.rtc {
+        model.region.rtc_local = rtc == Rtc::Local;
+    }
+    if !record.ntp.is_empty() {
+        model.region.ntp_servers = record.ntp;
+    }
+    if let Some(keyboard) = record.keyboard {
+        model.keyboard.layout = keyboard.layout.unwrap_or_default();
+        model.keyboard.variant = keyboard.variant.unwrap_or_default();
+        model.keyboard.keymap = keyboard.keymap.unwrap_or_default();
+    }
+    if let Some(accounts) = record.accounts {
+        if let Some(root) = accounts.root {
+            model.accounts.root = match root.mode {
+                Some(install::RootMode::Locked) => Some(RootAccount::Locked),
+                Some(install::RootMode::Disabled) => Some(RootAccount::Disabled),
+                None => root.hash.map(RootAccount::Password),
+            };
         }
-        _ => {}
+        model.accounts.users = accounts.users.into_iter().map(account).collect();
+        model.accounts.autologin = accounts.autologin;
+        model.accounts.ssh_keys_only = accounts.sshd.is_some_and(|sshd| sshd.keys_only);
     }
-}
-
-/// Ingest the packages node from a system-model document
-fn apply_packages(model: &mut Model, doc: &KdlDocument) {
-    if let Some(packages) = doc.get("packages") {
-        model.software.packages = packages
-            .iter_children()
-            .map(|node| node.name().value().to_string())
-            .collect();
+    if let Some(system) = record.system_model {
+        model.software.packages = system.packages;
     }
-}
-
-/// The moss-owned repo node: cloned from the live system-model when
-/// running on AerynOS media, otherwise a built-in default template
-fn repositories_node() -> KdlNode {
-    let mut unstable = KdlNode::new("unstable");
-    let mut unstable_children = KdlDocument::new();
-    let mut description = KdlNode::new("description");
 
-    description.push(KdlEntry::new("AerynOS unstable package stream"));
-    unstable_children.nodes_mut().push(description);
-
-    let mut base_uri = KdlNode::new("base-uri");
-    base_uri.push(KdlEntry::new("https://cdn.aerynos.dev/"));
-    unstable_children.nodes_mut().push(base_uri);
-
-    let mut version = KdlNode::new("version");
-    version.push(KdlEntry::new("stream/unstable"));
-    unstable_children.nodes_mut().push(version);
-
-    let mut priority = KdlNode::new("priority");
-    priority.push(KdlEntry::new(0i128));
-    unstable_children.nodes_mut().push(priority);
+    Ok(warnings)
+}
 
-    unstable.set_children(unstable_children);
+/// A recorded account as the installer's user
+fn account(record: install::User) -> User {
+    let mut user = User::new(record.name, record.realname, record.hash);
 
-    let mut node = KdlNode::new("repositories");
-    let mut children = KdlDocument::new();
-    children.nodes_mut().push(unstable);
-    node.set_children(children);
+    user.admin = record.admin;
+    user.create_home = record.create_home;
+    user.shell = record.shell.unwrap_or_default();
+    user.home = record.home.unwrap_or_default();
+    user.uid = record.uid;
+    user.gid = record.gid;
+    user.groups = record.groups;
+    user.ssh_keys = record.ssh_keys.into_iter().map(|ssh_key| ssh_key.key).collect();
 
-    node
+    user
 }
 
-/// The moss-owned packages node, one child node per package/provider
-fn packages_node(packages: &[String]) -> KdlNode {
-    let mut node = KdlNode::new("packages");
-    let mut children = KdlDocument::new();
-
-    packages.iter().for_each(|pkg| {
-        children.nodes_mut().push(KdlNode::new(pkg.as_str()));
-    });
-
-    node.set_children(children);
-    node
+/// Apply a bare system-model document to the model: the package set only.
+pub fn apply_system_model(model: &mut Model, content: &str, mode: Mode) -> Result<Vec<String>, Error> {
+    let Decoded {
+        value: system,
+        warnings,
+    } = SystemModel::from_kdl(content, mode)?;
+    model.software.packages = system.packages;
+    Ok(warnings)
 }
 
 /// Serialize the bare, moss-pure system-model
 pub fn system_model_kdl(model: &Model) -> String {
-    let mut doc = system_model_document(model);
-    doc.autoformat();
-    doc.to_string()
+    system_model(model).to_kdl()
 }
 
 /// The moss-owned system-model.kdl
-fn system_model_document(model: &Model) -> KdlDocument {
-    let mut doc = KdlDocument::new();
-    doc.nodes_mut().push(repositories_node());
-    doc.nodes_mut().push(packages_node(&model.software.packages));
-    doc
-}
-
-/// First positional argument of a node, as a str
-fn first_arg(node: &KdlNode) -> Option<&str> {
-    node.entries()
-        .iter()
-        .find(|entry| entry.name().is_none())
-        .and_then(|entry| entry.value().as_string())
-}
-
-/// Named property of a node, as a str
-pub(crate) fn prop<'a>(node: &'a KdlNode, key: &str) -> Option<&'a str> {
-    node.get(key).and_then(|value| value.as_string())
-}
-
-/// Render a KDL parse failure as a line/column detail per diagnostic
-pub fn parse_error_detail(error: &KdlError) -> String {
-    let mut details = Vec::new();
-
-    for diag in &error.diagnostics {
-        let offset = diag.span.offset().min(error.input.len());
-        let prefix = error.input.get(..offset).unwrap_or_default();
-        let line = prefix.matches('\n').count() + 1;
-        let column = prefix.rsplit('\n').next().map_or(0, str::len) + 1;
-        let message = diag.message.as_deref().unwrap_or("invalid KDL");
-
-        match diag.help.as_deref() {
-            Some(help) => details.push(format!("line {line}, column {column}: {message} ({help})")),
-            None => details.push(format!("line {line}, column {column}: {message}")),
-        }
-    }
-
-    if details.is_empty() {
-        return error.to_string();
-    }
-
-    details.join("; ")
+fn system_model(model: &Model) -> SystemModel {
+    SystemModel::new(model.software.packages.clone())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use kdl::KdlDocument;
 
     fn sample_model() -> Model {
         let mut model = Model::default();
         model.storage.disk = "/dev/vda".to_string();
+        model.storage.disk_id = Some(DiskId::Wwn("0x5002538e40a1b2c3".to_string()));
         model.storage.strategy_id = "whole_disk".to_string();
         model.storage.strategy_name = "whole_disk".to_string();
         model.region.language = "en_US.UTF-8".to_string();
         model.region.timezone = "America/Los_Angeles".to_string();
+        model.region.formats.time = Some("en_GB.UTF-8".to_string());
+        model.region.formats.measurement = Some("de_DE.UTF-8".to_string());
+        model.region.rtc_local = true;
+        model.region.ntp_servers = vec!["0.example.pool.ntp.org".to_string(), "time.example.com".to_string()];
         model.software.selection = "gnome".to_string();
+        model.keyboard.layout = "de".to_string();
+        model.keyboard.variant = "nodeadkeys".to_string();
+        model.keyboard.keymap = "de-nodeadkeys".to_string();
         model.software.packages = vec![
             "binary(cc)".to_string(),
             "pkgconfig(zlib)".to_string(),
             "firefox".to_string(),
         ];
-        model.accounts.root_password_hash = Some("$6$salt$roothash".to_string());
-        model.accounts.user = Some(User {
-            username: "john".to_string(),
-            real_name: "John Doe".to_string(),
-            password_hash: "$6$salt$userhash".to_string(),
-        });
+        model.accounts.root = Some(RootAccount::Password("$6$salt$roothash".to_string()));
+        let mut john = User::new("john", "John Doe", "$6$salt$userhash");
+        john.admin = true;
+        let mut jane = User::new("jane", "Jane Doe", "$6$salt$janehash");
+        jane.shell = "/usr/bin/zsh".to_string();
+        jane.groups = vec!["docker".to_string(), "libvirt".to_string()];
+        jane.uid = Some(1500);
+        jane.gid = Some(100);
+        jane.home = "/srv/home/jane".to_string();
+        jane.create_home = false;
+        jane.ssh_keys = vec![
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5 jane@laptop".to_string(),
+            "ecdsa-sha2-nistp256 AAAAE2VjZHNh jane@desktop".to_string(),
+        ];
+        model.accounts.users = vec![john, jane];
+        model.accounts.ssh_keys_only = true;
+        model.accounts.autologin = true;
 
         model
     }
@@ -346,10 +357,28 @@ mod tests {
         let parsed = from_kdl(&text).expect("emitted model must parse");
 
         assert_eq!(parsed.storage.disk, "/dev/vda");
+        assert_eq!(
+            parsed.storage.disk_id,
+            Some(DiskId::Wwn("0x5002538e40a1b2c3".to_string()))
+        );
         assert_eq!(parsed.storage.strategy_id, "whole_disk");
         assert_eq!(parsed.region.language, "en_US.UTF-8");
         assert_eq!(parsed.region.timezone, "America/Los_Angeles");
+        assert_eq!(parsed.region.formats.time.as_deref(), Some("en_GB.UTF-8"));
+        assert_eq!(parsed.region.formats.measurement.as_deref(), Some("de_DE.UTF-8"));
+        assert!(
+            parsed.region.formats.monetary.is_none(),
+            "unset categories inherit the locale"
+        );
+        assert!(parsed.region.rtc_local);
+        assert_eq!(
+            parsed.region.ntp_servers,
+            vec!["0.example.pool.ntp.org".to_string(), "time.example.com".to_string()]
+        );
         assert_eq!(parsed.software.selection, "gnome");
+        assert_eq!(parsed.keyboard.layout, "de");
+        assert_eq!(parsed.keyboard.variant, "nodeadkeys");
+        assert_eq!(parsed.keyboard.keymap, "de-nodeadkeys");
         assert_eq!(
             parsed.software.packages,
             vec![
@@ -358,12 +387,80 @@ mod tests {
                 "firefox".to_string(),
             ]
         );
-        assert_eq!(parsed.accounts.root_password_hash.as_deref(), Some("$6$salt$roothash"));
+        assert_eq!(
+            parsed.accounts.root,
+            Some(RootAccount::Password("$6$salt$roothash".to_string()))
+        );
 
-        let user = parsed.accounts.user.expect("user must round trip");
-        assert_eq!(user.username, "john");
-        assert_eq!(user.real_name, "John Doe");
-        assert_eq!(user.password_hash, "$6$salt$userhash");
+        let [john, jane] = parsed.accounts.users.as_slice() else {
+            panic!("both users must round trip");
+        };
+        assert_eq!(john.username, "john");
+        assert_eq!(john.real_name, "John Doe");
+        assert_eq!(john.password_hash, "$6$salt$userhash");
+        assert!(john.admin);
+        assert!(john.create_home);
+        assert!(john.shell.is_empty() && john.groups.is_empty() && john.uid.is_none());
+
+        assert_eq!(jane.username, "jane");
+        assert!(!jane.admin);
+        assert_eq!(jane.shell, "/usr/bin/zsh");
+        assert_eq!(jane.groups, vec!["docker".to_string(), "libvirt".to_string()]);
+        assert_eq!(jane.uid, Some(1500));
+        assert_eq!(jane.gid, Some(100));
+        assert_eq!(jane.home, "/srv/home/jane");
+        assert!(!jane.create_home);
+        assert_eq!(
+            jane.ssh_keys,
+            vec![
+                "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5 jane@laptop".to_string(),
+                "ecdsa-sha2-nistp256 AAAAE2VjZHNh jane@desktop".to_string(),
+            ]
+        );
+        assert!(john.ssh_keys.is_empty());
+        assert!(parsed.accounts.ssh_keys_only);
+        assert!(parsed.accounts.autologin);
+    }
+
+    #[test]
+    fn locked_root_round_trips() {
+        for account in [RootAccount::Locked, RootAccount::Disabled] {
+            let mut model = sample_model();
+            model.accounts.root = Some(account.clone());
+
+            let parsed = from_kdl(&to_kdl(&model)).expect("emitted model must parse");
+            assert_eq!(parsed.accounts.root, Some(account));
+            assert!(parsed.accounts.has_admin_path());
+        }
+    }
+
+    #[test]
+    fn legacy_user_nodes_are_administrators() {
+        let text = r#"install-model {
+    accounts {
+        user name=john realname="John Doe" hash="$6$salt$userhash"
+    }
+}
+"#;
+        let parsed = from_kdl(text).expect("legacy record must parse");
+
+        assert_eq!(parsed.accounts.users.len(), 1);
+        assert!(parsed.accounts.users[0].admin, "every legacy user was in wheel");
+        assert!(parsed.accounts.users[0].create_home);
+    }
+
+    #[test]
+    fn current_users_without_admin_are_not_administrators() {
+        let text = r#"install-model {
+    version 2
+    accounts {
+        user name=jane realname="Jane Doe" hash="$6$salt$janehash"
+    }
+}
+"#;
+        let parsed = from_kdl(text).expect("current record must parse");
+
+        assert!(!parsed.accounts.users[0].admin, "only version 1 records are migrated");
     }
 
     #[test]
@@ -398,8 +495,8 @@ mod tests {
         let record = to_kdl(&sample_model());
         let live = "packages {\n    firefox\n    helix\n}\n";
 
-        apply_install_model(&mut model, &record).expect("record must parse");
-        apply_system_model(&mut model, live).expect("live model must parse");
+        apply_install_model(&mut model, &record, Mode::Lenient).expect("record must parse");
+        apply_system_model(&mut model, live, Mode::Lenient).expect("live model must parse");
 
         assert_eq!(model.storage.disk, "/dev/vda", "fields come from the install record");
         assert_eq!(
@@ -414,7 +511,24 @@ mod tests {
         let text = to_kdl(&Model::default());
         let parsed = from_kdl(&text).expect("empty model must round trip");
         assert!(parsed.software.packages.is_empty());
-        assert!(parsed.accounts.user.is_none());
+        assert!(parsed.accounts.users.is_empty());
+        assert!(!parsed.accounts.autologin);
+    }
+
+    #[test]
+    fn diff_reports_only_what_changes() {
+        let current = sample_model();
+        let mut refreshed = from_kdl(&to_kdl(&current)).expect("emitted model must parse");
+        assert!(diff(&current, &refreshed).is_empty());
+
+        refreshed.region.timezone = "Europe/Berlin".to_string();
+        refreshed.software.packages.push("helix".to_string());
+
+        let changes = diff(&current, &refreshed);
+        assert_eq!(changes.settings.len(), 1);
+        assert_eq!(changes.settings[0].field, "timezone");
+        assert_eq!(changes.packages_added, ["helix"]);
+        assert!(changes.packages_removed.is_empty());
     }
 
     #[test]
@@ -446,13 +560,4 @@ mod tests {
             "https://cdn.aerynos.dev/main/stream/volatile/x86_64/stone.index"
         );
     }
-
-    #[test]
-    fn parse_errors_carry_location() {
-        let truncated = "repositories {\n    volatile {\n        priority 0\n}\npackages {\n            bash\n}\n";
-        let error = from_kdl(truncated).expect_err("truncated document must fail");
-        let detail = parse_error_detail(&error);
-
-        assert!(detail.contains("line "), "detail should locate the failure: {detail}");
-    }
 }
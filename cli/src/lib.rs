@@ -9,14 +9,18 @@ use installer::{DisplayInfo, Installer, Model, Step};
 use protocols::lichen::osinfo::OsInfo;
 
 pub mod args;
+pub mod crypt;
 pub mod frontend;
 pub mod install_model;
 pub mod logging;
+pub mod repair;
 pub mod selections;
+pub mod unattended;
 
 pub enum FrontendStep {
     Storage,
     Locale,
+    Keyboard,
     Timezone,
     Desktop,
     Accounts,
@@ -28,6 +32,7 @@ impl FrontendStep {
         match self {
             Self::Storage => frontend::storage::run(info, installer, model).await?,
             Self::Locale => frontend::locale::run(installer, model).await?,
+            Self::Keyboard => frontend::keyboard::run(installer, model).await?,
             Self::Timezone => frontend::timezone::run(installer, model).await?,
             Self::Desktop => frontend::desktop::run(installer, model).await?,
             Self::Accounts => frontend::accounts::run(installer, model).await?,
@@ -3,10 +3,16 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use clap::Parser;
-use cli::{args::Args, frontend::Frontend, logging::CliclackLayer};
+use cli::{
+    args::{self, Args, Command, ModelCommand},
+    frontend::Frontend,
+    logging::CliclackLayer,
+    repair::{self, Regenerate},
+    unattended,
+};
 use color_eyre::Result;
 use installer::Installer;
-use std::{env, fs::File};
+use std::{env, fs::File, process};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{EnvFilter, Layer, fmt::format::Format, layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -56,15 +62,56 @@ fn configure_tracing() -> Result<()> {
 async fn main() -> Result<()> {
     let args = Args::parse();
 
+    // Offline tools need neither the log file nor the backend
+    if let Some(Command::Model(ModelCommand::Diff { before, after })) = args.subcommand() {
+        let diff = args::diff_models(before, after).unwrap_or_else(|error| error.exit());
+        print!("{diff}");
+        return Ok(());
+    }
+
     setup_eyre();
     configure_tracing()?;
 
+    if let Some(Command::Repair {
+        device,
+        fstab,
+        boot,
+        machine_id,
+    }) = args.subcommand()
+    {
+        let regenerate = Regenerate {
+            fstab: *fstab,
+            boot: *boot,
+            machine_id: *machine_id,
+        };
+        repair::run(device.as_deref(), regenerate).await?;
+        return Ok(());
+    }
+
     // Reject a bad model path before standing up the backend connection
-    let model = args.model().unwrap_or_else(|error| error.exit()).unwrap_or_default();
+    let mut model = args.model().unwrap_or_else(|error| error.exit()).unwrap_or_default();
+    model.accounts.hashing = args.password_hashing();
+
+    if let Some(image) = args.image().unwrap_or_else(|error| error.exit()) {
+        if let Err(failure) = unattended::run_image(model, &image).await {
+            eprintln!("error: {failure}");
+            process::exit(failure.status as i32);
+        }
+        return Ok(());
+    }
+
+    if let Some(wipe) = args.unattended() {
+        if let Err(failure) = unattended::run(model, wipe).await {
+            eprintln!("error: {failure}");
+            process::exit(failure.status as i32);
+        }
+        return Ok(());
+    }
 
     let mut installer = Installer::builder()
         .add_step("storage")
         .add_step("locale")
+        .add_step("keyboard")
         .add_step("timezone")
         .add_step("desktop")
         .add_step("accounts")
@@ -77,6 +124,7 @@ async fn main() -> Result<()> {
     // frontend once the other steps have run
     installer.make_step_available("storage")?;
     installer.make_step_available("locale")?;
+    installer.make_step_available("keyboard")?;
     installer.make_step_available("timezone")?;
     installer.make_step_available("desktop")?;
     installer.make_step_available("accounts")?;
@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Repairing an existing installation in place
+//!
+//! The installation is found the same way the storage step finds one to
+//! refresh, then synced against its own system-model again; nothing is
+//! partitioned or formatted.
+
+use crate::frontend::progress::View;
+use installer::{Installer, StepError};
+use protocols::lichen::install::RepairRequest;
+
+/// What to regenerate besides syncing the packages
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Regenerate {
+    pub fstab: bool,
+    pub boot: bool,
+    pub machine_id: bool,
+}
+
+/// Repair the installation whose root is on `device`, or the one found when
+/// no device is given, asking which when several are
+pub async fn run(device: Option<&str>, regenerate: Regenerate) -> Result<(), StepError> {
+    let installer = Installer::builder().build().await?;
+    let mut install = installer.install().await?;
+
+    let found = install.discover_system_models(()).await?.into_inner().models;
+    let root_device = match device {
+        Some(device) => device.to_string(),
+        None => match found.as_slice() {
+            [] => return Err(StepError::Failed("no existing installation found".to_string())),
+            [only] => only.device.clone(),
+            _ => {
+                let items = found
+                    .iter()
+                    .enumerate()
+                    .map(|(index, model)| (index, model.device.clone(), "".to_string()))
+                    .collect::<Vec<_>>();
+                let index = cliclack::select("Which installation would you like to repair?")
+                    .items(&items)
+                    .interact()
+                    .map_err(|_| StepError::UserAborted)?;
+                found.get(index).ok_or(StepError::UserAborted)?.device.clone()
+            }
+        },
+    };
+
+    tracing::info!("Repairing the installation on {root_device}");
+
+    let mut view = View::start(format!("Repairing the installation on {root_device}"));
+
+    let result = async {
+        let mut stream = install
+            .repair(RepairRequest {
+                root_device: root_device.clone(),
+                regenerate_fstab: regenerate.fstab,
+                regenerate_boot: regenerate.boot,
+                regenerate_machine_id: regenerate.machine_id,
+            })
+            .await?
+            .into_inner();
+
+        while let Some(update) = stream.message().await? {
+            if update.finished {
+                return Ok(());
+            }
+            view.update(&update);
+        }
+
+        Err(StepError::Failed("repair stream ended without completing".to_string()))
+    }
+    .await;
+
+    match &result {
+        Ok(()) => view.stop(format!("Repaired the installation on {root_device}")),
+        Err(_) => view.error("Repair failed"),
+    }
+    result
+}
@@ -8,9 +8,8 @@
 //! the binary. Each names its required packages/providers and the other
 //! selections it depends on.
 
-use crate::install_model::prop;
 use installer::StepError;
-use kdl::KdlDocument;
+use kdl::{KdlDocument, KdlNode};
 use std::collections::{BTreeSet, HashMap, HashSet};
 
 /// A selection definition loaded from data/selections
@@ -115,6 +114,11 @@ fn parse(raw: &str) -> Selection {
     }
 }
 
+/// A string property of a node
+fn prop<'a>(node: &'a KdlNode, key: &str) -> Option<&'a str> {
+    node.get(key).and_then(|value| value.as_string())
+}
+
 /// The packages every installation must carry regardless of what an imported
 /// model lists: the base system and kernel closures
 pub fn mandatory(selection: &str) -> Result<Vec<String>, StepError> {
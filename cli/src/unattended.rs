@@ -0,0 +1,401 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Unattended installation from a complete install-model
+//!
+//! Nothing is prompted for and nothing needs a TTY: progress goes to stdout
+//! as plain lines, and each class of failure exits with its own code so
+//! provisioning tooling can tell a bad model from a failed install. The disk
+//! is only erased when `--yes-wipe` names the same device the model does;
+//! an image install creates a new image file as its disk instead.
+
+use crate::frontend::{progress, storage, summary};
+use installer::{DiskId, Installer, Model, StepError};
+use protocols::lichen::storage::{
+    disks::{AttachImageRequest, DetachImageRequest, Disk, ImageFormat, ListDisksRequest},
+    provisioner::TryStrategyRequest,
+};
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+use tonic::Code;
+
+/// Exit codes for unattended failures; 2 stays clap's usage error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The install-model lacks settings an unattended install needs
+    IncompleteModel = 3,
+    /// The model's disk is missing, or is not the disk `--yes-wipe` allows
+    DiskMismatch = 4,
+    /// The model's strategy cannot be applied to the disk
+    NoPlan = 5,
+    /// The backend could not be reached
+    BackendUnavailable = 6,
+    /// Partitioning or installation failed part way
+    InstallFailed = 7,
+    /// The image file could not be created, attached or written out
+    ImageFailed = 8,
+    /// The install-model has every setting, but the backend refuses some of them
+    InvalidModel = 9,
+}
+
+/// Why an unattended install stopped
+#[derive(Debug)]
+pub struct Failure {
+    pub status: ExitStatus,
+    pub message: String,
+}
+
+impl Failure {
+    fn new(status: ExitStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// An image file to install into instead of a disk
+#[derive(Debug, Clone)]
+pub struct Image {
+    /// Absolute path of the image file to create
+    pub path: PathBuf,
+    /// Size of the image's disk in bytes
+    pub size: u64,
+    pub format: ImageFormat,
+}
+
+/// Install the model onto the disk `wipe` names, without prompting
+pub async fn run(model: Model, wipe: &Path) -> Result<(), Failure> {
+    validate(&model)?;
+
+    let installer = connect().await?;
+    check(&installer, &model).await?;
+    let exclude_loopback = env::var_os("LICHEN_INCLUDE_LOOPBACK").is_none();
+    let available = list_disks(&installer, exclude_loopback).await?;
+    let disk = resolve_disk(&model.storage.disk, model.storage.disk_id.as_ref(), wipe, &available)?;
+    println!("Target disk: {} ({})", disk.device, model.storage.disk);
+
+    install_onto(&installer, model, &disk).await
+}
+
+/// Install the model into a new image file, without prompting. The image is
+/// detached again whether or not the install succeeds.
+pub async fn run_image(mut model: Model, image: &Image) -> Result<(), Failure> {
+    // The image is the disk, whichever disk the model was recorded from
+    model.storage.disk = image.path.display().to_string();
+    model.storage.disk_id = None;
+    validate(&model)?;
+
+    let installer = connect().await?;
+    check(&installer, &model).await?;
+    let image_failed = |e: tonic::Status| Failure::new(ExitStatus::ImageFailed, e.message().to_string());
+    let mut disks = installer
+        .disks()
+        .await
+        .map_err(|e| Failure::new(ExitStatus::BackendUnavailable, e.to_string()))?;
+    let device = disks
+        .attach_image(AttachImageRequest {
+            path: image.path.display().to_string(),
+            size: image.size,
+            format: image.format as i32,
+        })
+        .await
+        .map_err(image_failed)?
+        .into_inner()
+        .device;
+    println!("Attached {} as {device}", image.path.display());
+
+    let installed = async {
+        let disk = list_disks(&installer, false)
+            .await?
+            .into_iter()
+            .find(|disk| disk.device == device)
+            .ok_or_else(|| Failure::new(ExitStatus::ImageFailed, format!("{device} is not listed as a disk")))?;
+        install_onto(&installer, model, &disk).await
+    }
+    .await;
+
+    let detached = disks
+        .detach_image(DetachImageRequest { device })
+        .await
+        .map_err(image_failed);
+    installed?;
+    detached?;
+    println!("Image written to {}", image.path.display());
+
+    Ok(())
+}
+
+/// Reach the backend
+async fn connect() -> Result<Installer, Failure> {
+    Installer::builder()
+        .build()
+        .await
+        .map_err(|e| Failure::new(ExitStatus::BackendUnavailable, format!("backend unavailable: {e}")))
+}
+
+/// Have the backend check the model's settings as the install will, before
+/// any disk or image is touched
+async fn check(installer: &Installer, model: &Model) -> Result<(), Failure> {
+    summary::validate(installer, model).await.map_err(|e| match e {
+        StepError::ProtocolError(status) if status.code() == Code::InvalidArgument => Failure::new(
+            ExitStatus::InvalidModel,
+            format!("install-model is invalid: {}", status.message()),
+        ),
+        e => Failure::new(ExitStatus::BackendUnavailable, e.to_string()),
+    })
+}
+
+async fn list_disks(installer: &Installer, exclude_loopback: bool) -> Result<Vec<Disk>, Failure> {
+    let mut disks = installer
+        .disks()
+        .await
+        .map_err(|e| Failure::new(ExitStatus::BackendUnavailable, e.to_string()))?;
+
+    Ok(disks
+        .list_disks(ListDisksRequest { exclude_loopback })
+        .await
+        .map_err(|e| Failure::new(ExitStatus::BackendUnavailable, e.message().to_string()))?
+        .into_inner()
+        .disks)
+}
+
+/// Plan the model's strategy for the disk, then partition and install
+async fn install_onto(installer: &Installer, mut model: Model, disk: &Disk) -> Result<(), Failure> {
+    let mut provisioner = installer
+        .provisioner()
+        .await
+        .map_err(|e| Failure::new(ExitStatus::BackendUnavailable, e.to_string()))?;
+    let plan = provisioner
+        .try_strategy(TryStrategyRequest {
+            strategy: model.storage.strategy_id.clone(),
+            disks: vec![disk.device.clone()],
+        })
+        .await
+        .map_err(|e| Failure::new(ExitStatus::NoPlan, e.message().to_string()))?
+        .into_inner()
+        .plans
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            Failure::new(
+                ExitStatus::NoPlan,
+                format!(
+                    "strategy {} does not apply to {}",
+                    model.storage.strategy_id, disk.device
+                ),
+            )
+        })?;
+
+    model.storage.disk = disk.device.clone();
+    model.storage.plan = Some(plan);
+    storage::ensure_filesystem_packages(&mut model);
+
+    println!("Installing with strategy {}", model.storage.strategy_id);
+    summary::install(installer, &model, &mut |update| {
+        if let Some(line) = progress::plain(update) {
+            println!("{line}");
+        }
+    })
+    .await
+    .map_err(|e| Failure::new(ExitStatus::InstallFailed, e.to_string()))?;
+    println!("Installation complete");
+
+    Ok(())
+}
+
+/// Check the model carries every setting an interactive run would have asked for
+pub fn validate(model: &Model) -> Result<(), Failure> {
+    let missing = [
+        ("disk", model.storage.disk.is_empty()),
+        ("strategy", model.storage.strategy_id.is_empty()),
+        ("locale", model.region.language.is_empty()),
+        ("timezone", model.region.timezone.is_empty()),
+        ("desktop", model.software.selection.is_empty()),
+        ("accounts", !model.accounts.has_admin_path()),
+    ]
+    .into_iter()
+    .filter_map(|(field, missing)| missing.then_some(field))
+    .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+        return Err(Failure::new(
+            ExitStatus::IncompleteModel,
+            format!("install-model is incomplete, missing: {}", missing.join(", ")),
+        ));
+    }
+    // Interactively the question is never asked; here it would be dropped silently
+    if model.accounts.autologin && model.software.display_manager().is_none() {
+        return Err(Failure::new(
+            ExitStatus::InvalidModel,
+            format!(
+                "install-model is invalid: autologin needs a display manager, and desktop {} installs none",
+                model.software.selection
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The disk the model names, provided it is the one `--yes-wipe` names.
+/// Models carrying a stable disk id are resolved by it, and must match exactly
+/// one disk; older models fall back to the recorded path. Paths may be given as
+/// /dev/disk/by-* links and are compared by the device node they resolve to.
+fn resolve_disk(model_disk: &str, disk_id: Option<&DiskId>, wipe: &Path, available: &[Disk]) -> Result<Disk, Failure> {
+    let resolve = |path: &Path| {
+        fs::canonicalize(path).map_err(|e| {
+            Failure::new(
+                ExitStatus::DiskMismatch,
+                format!("cannot resolve {}: {e}", path.display()),
+            )
+        })
+    };
+
+    let (disk, wanted) = match disk_id {
+        Some(id) => {
+            let disk = id
+                .resolve(available)
+                .map_err(|e| Failure::new(ExitStatus::DiskMismatch, e.to_string()))?;
+            (Some(disk), resolve(Path::new(&disk.device))?)
+        }
+        None => (None, resolve(Path::new(model_disk))?),
+    };
+    if resolve(wipe)? != wanted {
+        return Err(Failure::new(
+            ExitStatus::DiskMismatch,
+            format!(
+                "--yes-wipe {} does not name the model's disk {}",
+                wipe.display(),
+                wanted.display()
+            ),
+        ));
+    }
+
+    disk.or_else(|| {
+        available
+            .iter()
+            .find(|disk| fs::canonicalize(&disk.device).is_ok_and(|device| device == wanted))
+    })
+    .cloned()
+    .ok_or_else(|| {
+        Failure::new(
+            ExitStatus::DiskMismatch,
+            format!("{model_disk} is not an installable disk"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install_model;
+    use installer::{RootAccount, User};
+    use models::Mode;
+
+    fn complete_model() -> Model {
+        let mut model = install_model::blank();
+        model.storage.disk = "/dev/disk/by-id/virtio-lichen".to_string();
+        model.storage.strategy_id = "whole_disk_xfs".to_string();
+        model.region.language = "en_GB.UTF-8".to_string();
+        model.region.timezone = "Europe/London".to_string();
+        model.software.selection = "server".to_string();
+        model.accounts.root = Some(RootAccount::Locked);
+        let mut admin = User::new("ops", "Operations", "$y$j9T$salt$hash");
+        admin.admin = true;
+        model.accounts.users.push(admin);
+        model
+    }
+
+    #[test]
+    fn complete_models_validate() {
+        assert!(validate(&complete_model()).is_ok());
+    }
+
+    #[test]
+    fn incomplete_models_name_what_is_missing() {
+        let mut model = complete_model();
+        model.storage.disk.clear();
+        model.accounts.users.clear();
+
+        let failure = validate(&model).expect_err("model is incomplete");
+        assert_eq!(failure.status, ExitStatus::IncompleteModel);
+        assert!(failure.message.contains("disk"));
+        assert!(failure.message.contains("accounts"));
+        assert!(!failure.message.contains("timezone"));
+    }
+
+    #[test]
+    fn autologin_needs_a_display_manager() {
+        let mut model = complete_model();
+        model.accounts.autologin = true;
+
+        let failure = validate(&model).expect_err("the server selection has no login screen");
+        assert_eq!(failure.status, ExitStatus::InvalidModel);
+
+        model.software.packages.push("sysbinary(gdm)".to_string());
+        assert!(validate(&model).is_ok());
+    }
+
+    #[test]
+    fn settings_left_out_of_the_document_are_not_defaulted() {
+        let text = r#"install-model {
+    version 2
+    strategy "whole_disk_xfs"
+    disk "/dev/vda"
+    locale "en_GB.UTF-8"
+    desktop "server"
+    accounts {
+        root mode="locked"
+        user name=ops realname="Operations" hash="$y$j9T$salt$hash" admin=#true
+    }
+}
+"#;
+        let mut model = install_model::blank();
+        install_model::apply_install_model(&mut model, text, Mode::Strict).expect("the document parses");
+
+        let failure = validate(&model).expect_err("no timezone was given");
+        assert!(failure.message.ends_with("missing: timezone"), "{}", failure.message);
+    }
+
+    #[test]
+    fn wipe_must_name_the_model_disk() {
+        let failure = resolve_disk("/dev/null", None, Path::new("/dev/zero"), &[]).expect_err("different devices");
+        assert_eq!(failure.status, ExitStatus::DiskMismatch);
+
+        let failure = resolve_disk("/dev/null", None, Path::new("/dev/null"), &[]).expect_err("not a listed disk");
+        assert!(failure.message.contains("not an installable disk"));
+    }
+
+    #[test]
+    fn stable_ids_take_precedence_over_paths() {
+        let available = [Disk {
+            device: "/dev/null".to_string(),
+            serial: Some("S1".to_string()),
+            ..Default::default()
+        }];
+        let serial = DiskId::Serial("S1".to_string());
+
+        let disk = resolve_disk("/dev/zero", Some(&serial), Path::new("/dev/null"), &available)
+            .expect("the id names /dev/null, whatever the recorded path");
+        assert_eq!(disk.device, "/dev/null");
+
+        let failure = resolve_disk(
+            "/dev/null",
+            Some(&DiskId::Serial("S2".to_string())),
+            Path::new("/dev/null"),
+            &available,
+        )
+        .expect_err("no disk has the serial");
+        assert_eq!(failure.status, ExitStatus::DiskMismatch);
+    }
+}
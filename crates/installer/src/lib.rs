@@ -7,14 +7,16 @@ mod step;
 use std::collections::{HashMap, HashSet};
 
 use protocols::lichen::{
-    install::install_client::InstallClient, locales::locales_client, storage::disks::disks_client,
-    storage::provisioner::provisioner_client, system::system_client,
+    install::install_client::InstallClient, keyboard::keyboard_client, locales::locales_client,
+    storage::disks::disks_client, storage::provisioner::provisioner_client, system::system_client,
+    timezones::timezones_client,
 };
 pub use step::*;
 mod icon;
 pub use icon::*;
 mod model;
 pub use model::*;
+pub mod password;
 
 pub use inventory;
 use thiserror::Error;
@@ -106,7 +108,7 @@ impl InstallerBuilder {
             available_steps.insert(first_step.clone());
         }
 
-        let channel = protocols::unix_channel("/run/lichen.sock").await?;
+        let channel = protocols::unix_channel(&protocols::socket_path()).await?;
 
         let installer = Installer {
             steps,
@@ -270,6 +272,18 @@ impl Installer {
         Ok(client)
     }
 
+    /// Grab a keyboard RPC client
+    pub async fn keyboard(&self) -> Result<keyboard_client::KeyboardClient<Channel>, Error> {
+        let client = keyboard_client::KeyboardClient::new(self.channel.clone());
+        Ok(client)
+    }
+
+    /// Grab a timezones RPC client
+    pub async fn timezones(&self) -> Result<timezones_client::TimezonesClient<Channel>, Error> {
+        let client = timezones_client::TimezonesClient::new(self.channel.clone());
+        Ok(client)
+    }
+
     /// Grab a system RPC client
     pub async fn system(&self) -> Result<system_client::SystemClient<Channel>, Error> {
         let client = system_client::SystemClient::new(self.channel.clone());
@@ -4,11 +4,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
 mod accounts;
+mod keyboard;
 mod region;
 mod software;
 mod storage;
 
-pub use accounts::User;
+pub use accounts::{PasswordHashing, RootAccount, User};
+pub use software::DisplayManager;
+pub use storage::{DiskId, DiskIdError};
 
 /// Installation settings
 ///
@@ -19,6 +22,8 @@ pub use accounts::User;
 pub struct Model {
     /// Region specific installation settings
     pub region: region::Model,
+    /// Keyboard layout selections
+    pub keyboard: keyboard::Model,
     /// Storage and partitioning selections
     pub storage: storage::Model,
     /// Account selections
@@ -11,6 +11,70 @@ pub struct User {
     pub real_name: String,
     /// crypt(3) password hash
     pub password_hash: String,
+    /// Login shell; empty uses the target's useradd default
+    pub shell: String,
+    /// Administrator: member of wheel and adm
+    pub admin: bool,
+    /// Supplementary groups beyond the standard desktop set, created on the
+    /// target if missing
+    pub groups: Vec<String>,
+    /// Fixed uid; allocated by useradd when unset
+    pub uid: Option<u32>,
+    /// Fixed primary gid; a per-user group is allocated when unset
+    pub gid: Option<u32>,
+    /// Home directory; empty uses /home/<username>
+    pub home: String,
+    /// Create the home directory (false when it lives on a network mount)
+    pub create_home: bool,
+    /// OpenSSH public keys for ~/.ssh/authorized_keys, one key per entry
+    pub ssh_keys: Vec<String>,
+}
+
+impl User {
+    /// A new account with the standard settings
+    pub fn new(username: impl Into<String>, real_name: impl Into<String>, password_hash: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            real_name: real_name.into(),
+            password_hash: password_hash.into(),
+            shell: String::new(),
+            admin: false,
+            groups: Vec::new(),
+            uid: None,
+            gid: None,
+            home: String::new(),
+            create_home: true,
+            ssh_keys: Vec::new(),
+        }
+    }
+}
+
+/// How the root account is left on the installed system
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RootAccount {
+    /// Root logs in with this crypt(3) password hash
+    Password(String),
+    /// Root has no usable password; administrators reach it through sudo
+    Locked,
+    /// Locked, with a nologin shell so `su` and console logins are refused too
+    Disabled,
+}
+
+impl RootAccount {
+    /// True if root itself can be logged in to, so no administrator is required
+    pub fn is_usable(&self) -> bool {
+        matches!(self, Self::Password(_))
+    }
+}
+
+/// The crypt(3) scheme frontends hash new passwords with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PasswordHashing {
+    /// yescrypt, the shadow and libxcrypt default
+    #[default]
+    Yescrypt,
+    /// sha512-crypt with the given number of rounds
+    Sha512 { rounds: usize },
 }
 
 /// Account installation settings
@@ -19,8 +83,26 @@ pub struct User {
 /// be stored in the model.
 #[derive(Debug, Default)]
 pub struct Model {
-    /// crypt(3) hash of the root password
-    pub root_password_hash: Option<String>,
-    /// The primary admin account
-    pub user: Option<User>,
+    /// Root account handling; `None` until the accounts step has run
+    pub root: Option<RootAccount>,
+    /// Accounts to create, in order
+    pub users: Vec<User>,
+    /// Restrict sshd to key authentication and refuse root logins
+    pub ssh_keys_only: bool,
+    /// Scheme for hashing passwords entered in this session
+    pub hashing: PasswordHashing,
+    /// Log the primary (first) user in automatically at boot
+    pub autologin: bool,
+}
+
+impl Model {
+    /// True if someone can administer the installed system: either root
+    /// keeps a password, or an administrator can authenticate to sudo
+    pub fn has_admin_path(&self) -> bool {
+        self.root.as_ref().is_some_and(RootAccount::is_usable)
+            || self
+                .users
+                .iter()
+                .any(|user| user.admin && !user.password_hash.is_empty())
+    }
 }
@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+/// Keyboard installation settings, shared by the console and graphical session
+#[derive(Debug)]
+pub struct Model {
+    /// XKB layout name (i.e. "us")
+    pub layout: String,
+
+    /// XKB layout variant, empty for the layout's default
+    pub variant: String,
+
+    /// Console keymap for the virtual terminal (i.e. "us")
+    pub keymap: String,
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Self {
+            layout: String::from("us"),
+            variant: String::new(),
+            keymap: String::from("us"),
+        }
+    }
+}
@@ -9,15 +9,95 @@ pub struct Model {
     /// System language (i.e. "en_US")
     pub language: String,
 
+    /// Regional format overrides, independent of the UI language
+    pub formats: Formats,
+
     /// System timezone (i.e. "Europe/London")
     pub timezone: String,
+
+    /// Keep the hardware clock in local time (dual-boot with Windows)
+    pub rtc_local: bool,
+
+    /// NTP servers; empty keeps the distribution's default pool
+    pub ntp_servers: Vec<String>,
 }
 
 impl Default for Model {
     fn default() -> Self {
         Self {
             language: String::from("en_US.UTF-8"),
+            formats: Formats::default(),
             timezone: String::from("UTC"),
+            rtc_local: false,
+            ntp_servers: Vec::new(),
+        }
+    }
+}
+
+/// Per-category locale overrides. `None` inherits the system language.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Formats {
+    /// Date and time formats (LC_TIME)
+    pub time: Option<String>,
+
+    /// Number formats (LC_NUMERIC)
+    pub numeric: Option<String>,
+
+    /// Currency formats (LC_MONETARY)
+    pub monetary: Option<String>,
+
+    /// Default paper size (LC_PAPER)
+    pub paper: Option<String>,
+
+    /// Measurement units (LC_MEASUREMENT)
+    pub measurement: Option<String>,
+}
+
+impl Formats {
+    /// Use one locale for every format category
+    pub fn set_all(&mut self, locale: &str) {
+        for slot in self.slots_mut() {
+            *slot = Some(locale.to_string());
         }
     }
+
+    /// True if any category overrides the system language
+    pub fn is_overridden(&self) -> bool {
+        self.categories().any(|(_, value)| value.is_some())
+    }
+
+    /// Each category as its locale(7) environment variable name and override
+    pub fn categories(&self) -> impl Iterator<Item = (&'static str, Option<&str>)> {
+        [
+            ("LC_TIME", &self.time),
+            ("LC_NUMERIC", &self.numeric),
+            ("LC_MONETARY", &self.monetary),
+            ("LC_PAPER", &self.paper),
+            ("LC_MEASUREMENT", &self.measurement),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name, value.as_deref()))
+    }
+
+    /// Mutable access to a category by its locale(7) environment variable name
+    pub fn category_mut(&mut self, name: &str) -> Option<&mut Option<String>> {
+        match name {
+            "LC_TIME" => Some(&mut self.time),
+            "LC_NUMERIC" => Some(&mut self.numeric),
+            "LC_MONETARY" => Some(&mut self.monetary),
+            "LC_PAPER" => Some(&mut self.paper),
+            "LC_MEASUREMENT" => Some(&mut self.measurement),
+            _ => None,
+        }
+    }
+
+    fn slots_mut(&mut self) -> [&mut Option<String>; 5] {
+        [
+            &mut self.time,
+            &mut self.numeric,
+            &mut self.monetary,
+            &mut self.paper,
+            &mut self.measurement,
+        ]
+    }
 }
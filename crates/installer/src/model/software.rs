@@ -2,6 +2,14 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
+/// A display manager the installer can configure for autologin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayManager {
+    Gdm,
+    Sddm,
+    CosmicGreeter,
+}
+
 /// Software installation settings
 #[derive(Debug, Default)]
 pub struct Model {
@@ -10,3 +18,17 @@ pub struct Model {
     /// Fully resolved package/provider list for the target installation
     pub packages: Vec<String>,
 }
+
+impl Model {
+    /// The display manager the resolved package set installs, if any
+    pub fn display_manager(&self) -> Option<DisplayManager> {
+        [
+            ("sysbinary(gdm)", DisplayManager::Gdm),
+            ("binary(sddm)", DisplayManager::Sddm),
+            ("cosmic-greeter", DisplayManager::CosmicGreeter),
+        ]
+        .into_iter()
+        .find(|(package, _)| self.packages.iter().any(|installed| installed == package))
+        .map(|(_, manager)| manager)
+    }
+}
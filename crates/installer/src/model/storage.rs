@@ -2,13 +2,115 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use protocols::lichen::storage::provisioner::StrategyPlan;
+use std::fmt;
+
+use protocols::lichen::{
+    install::ExistingMount,
+    storage::{disks::Disk, provisioner::StrategyPlan},
+};
+use thiserror::Error;
+
+/// A stable way to name a disk that survives device node renumbering
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiskId {
+    /// World Wide Name reported by the disk
+    Wwn(String),
+    /// A /dev/disk/by-id link
+    ById(String),
+    /// Serial number reported by the disk
+    Serial(String),
+    /// A /dev/disk/by-path link, tied to the port the disk is attached to
+    ByPath(String),
+}
+
+/// Why a recorded disk identifier could not be resolved
+#[derive(Debug, Error)]
+pub enum DiskIdError {
+    #[error("no disk matches {0}")]
+    NotFound(DiskId),
+    #[error("{id} matches more than one disk: {}", devices.join(", "))]
+    Ambiguous { id: DiskId, devices: Vec<String> },
+}
+
+impl DiskId {
+    /// The most stable identifier the disk reports, if any
+    pub fn of(disk: &Disk) -> Option<Self> {
+        disk.wwn
+            .clone()
+            .map(Self::Wwn)
+            .or_else(|| disk.by_id.first().cloned().map(Self::ById))
+            .or_else(|| disk.serial.clone().map(Self::Serial))
+            .or_else(|| disk.by_path.clone().map(Self::ByPath))
+    }
+
+    /// Key naming this kind of identifier in the install-model
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Wwn(_) => "wwn",
+            Self::ById(_) => "by-id",
+            Self::Serial(_) => "serial",
+            Self::ByPath(_) => "by-path",
+        }
+    }
+
+    /// The identifier itself
+    pub fn value(&self) -> &str {
+        match self {
+            Self::Wwn(value) | Self::ById(value) | Self::Serial(value) | Self::ByPath(value) => value,
+        }
+    }
+
+    /// Rebuild an identifier from its install-model key and value
+    pub fn from_kind(kind: &str, value: &str) -> Option<Self> {
+        let value = value.to_string();
+        match kind {
+            "wwn" => Some(Self::Wwn(value)),
+            "by-id" => Some(Self::ById(value)),
+            "serial" => Some(Self::Serial(value)),
+            "by-path" => Some(Self::ByPath(value)),
+            _ => None,
+        }
+    }
+
+    /// Whether the disk carries this identifier
+    pub fn matches(&self, disk: &Disk) -> bool {
+        match self {
+            Self::Wwn(wwn) => disk.wwn.as_ref() == Some(wwn),
+            Self::ById(link) => disk.by_id.contains(link),
+            Self::Serial(serial) => disk.serial.as_ref() == Some(serial),
+            Self::ByPath(link) => disk.by_path.as_ref() == Some(link),
+        }
+    }
+
+    /// The single disk carrying this identifier. Cloned disks can share a
+    /// serial, so more than one match is refused rather than guessed at.
+    pub fn resolve<'a>(&self, disks: &'a [Disk]) -> Result<&'a Disk, DiskIdError> {
+        let matches = disks.iter().filter(|disk| self.matches(disk)).collect::<Vec<_>>();
+        match matches.as_slice() {
+            [disk] => Ok(disk),
+            [] => Err(DiskIdError::NotFound(self.clone())),
+            _ => Err(DiskIdError::Ambiguous {
+                id: self.clone(),
+                devices: matches.iter().map(|disk| disk.device.clone()).collect(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for DiskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind(), self.value())
+    }
+}
 
 /// Storage and partitioning installation settings
 #[derive(Debug, Default)]
 pub struct Model {
     /// Target disk path in /dev
     pub disk: String,
+    /// Stable identifier for the target disk, preferred over `disk` when
+    /// a recorded model is imported
+    pub disk_id: Option<DiskId>,
     /// Human readable description of the target disk
     pub disk_display: String,
     /// Identifier of the chosen provisioning strategy
@@ -17,4 +119,51 @@ pub struct Model {
     pub strategy_name: String,
     /// The partitioning plan computed for the chosen disk and strategy
     pub plan: Option<StrategyPlan>,
+    /// A previous installation's filesystems, recreated in place instead of
+    /// applying `plan` so that its /home is kept
+    pub refresh: Option<Vec<ExistingMount>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disk(device: &str, serial: &str) -> Disk {
+        Disk {
+            device: device.to_string(),
+            serial: Some(serial.to_string()),
+            by_id: vec![format!("/dev/disk/by-id/ata-Disk_{serial}")],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn prefers_the_most_stable_identifier() {
+        let mut sda = disk("/dev/sda", "S1");
+        assert_eq!(
+            DiskId::of(&sda),
+            Some(DiskId::ById("/dev/disk/by-id/ata-Disk_S1".to_string()))
+        );
+
+        sda.wwn = Some("0x5000c500a1b2c3d4".to_string());
+        assert_eq!(DiskId::of(&sda), Some(DiskId::Wwn("0x5000c500a1b2c3d4".to_string())));
+        assert_eq!(DiskId::of(&Disk::default()), None);
+    }
+
+    #[test]
+    fn resolve_requires_exactly_one_match() {
+        let disks = [disk("/dev/sda", "S1"), disk("/dev/sdb", "S2"), disk("/dev/sdc", "S2")];
+
+        let id = DiskId::Serial("S1".to_string());
+        assert_eq!(id.resolve(&disks).expect("unique serial").device, "/dev/sda");
+
+        assert!(matches!(
+            DiskId::Serial("S3".to_string()).resolve(&disks),
+            Err(DiskIdError::NotFound(_))
+        ));
+        assert!(matches!(
+            DiskId::Serial("S2".to_string()).resolve(&disks),
+            Err(DiskIdError::Ambiguous { devices, .. }) if devices == ["/dev/sdb", "/dev/sdc"]
+        ));
+    }
 }
@@ -0,0 +1,275 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Password policy
+//!
+//! A policy is a list of checks, each of which may object to a password.
+//! Errors reject the password outright; warnings are shown to the user, who
+//! may keep the password anyway. Frontends evaluate the policy before the
+//! password is hashed, since the hash can no longer be inspected.
+
+/// How serious a policy finding is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The user may keep the password after seeing the warning
+    Warning,
+    /// The password is rejected
+    Error,
+}
+
+/// One objection raised by a check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+}
+
+/// A single password check
+pub trait PasswordCheck: Send + Sync {
+    /// Inspect the password chosen for `username`
+    fn check(&self, password: &str, username: &str) -> Option<Finding>;
+}
+
+/// Reject passwords shorter than the given number of characters
+pub struct MinLength(pub usize);
+
+impl PasswordCheck for MinLength {
+    fn check(&self, password: &str, _username: &str) -> Option<Finding> {
+        (password.chars().count() < self.0).then(|| Finding::error(format!("use at least {} characters", self.0)))
+    }
+}
+
+/// Reject passwords built from the account name, forwards or backwards
+pub struct NotUsername;
+
+impl PasswordCheck for NotUsername {
+    fn check(&self, password: &str, username: &str) -> Option<Finding> {
+        if username.len() < 3 {
+            return None;
+        }
+
+        let password = password.to_lowercase();
+        let reversed = username.chars().rev().collect::<String>();
+        (password.contains(username) || password.contains(&reversed))
+            .then(|| Finding::error("the password must not contain the username"))
+    }
+}
+
+/// Reject the most common passwords, ignoring case and trailing digits and
+/// symbols, so "Password123!" is caught along with "password"
+pub struct CommonPasswords;
+
+/// Widely breached passwords and keyboard walks
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "111111",
+    "000000",
+    "654321",
+    "abc123",
+    "abcd1234",
+    "admin",
+    "administrator",
+    "aerynos",
+    "baseball",
+    "changeme",
+    "dragon",
+    "football",
+    "iloveyou",
+    "letmein",
+    "linux",
+    "login",
+    "master",
+    "monkey",
+    "passw0rd",
+    "password",
+    "princess",
+    "qwerty",
+    "qwertyuiop",
+    "root",
+    "secret",
+    "shadow",
+    "starwars",
+    "sunshine",
+    "superman",
+    "toor",
+    "trustno1",
+    "welcome",
+    "whatever",
+    "asdfgh",
+    "zxcvbn",
+];
+
+impl PasswordCheck for CommonPasswords {
+    fn check(&self, password: &str, _username: &str) -> Option<Finding> {
+        let lowered = password.to_lowercase();
+        let stem = lowered.trim_end_matches(|ch: char| !ch.is_ascii_alphabetic());
+
+        [lowered.as_str(), stem]
+            .iter()
+            .any(|candidate| COMMON_PASSWORDS.contains(candidate))
+            .then(|| Finding::error("this is one of the most commonly used passwords"))
+    }
+}
+
+/// Warn when fewer than `min` of lowercase, uppercase, digits and symbols
+/// are used, in the manner of pwquality's minclass
+pub struct CharacterClasses {
+    pub min: usize,
+}
+
+impl PasswordCheck for CharacterClasses {
+    fn check(&self, password: &str, _username: &str) -> Option<Finding> {
+        let classes = [
+            password.chars().any(|ch| ch.is_lowercase()),
+            password.chars().any(|ch| ch.is_uppercase()),
+            password.chars().any(|ch| ch.is_ascii_digit()),
+            password.chars().any(|ch| !ch.is_alphanumeric()),
+        ]
+        .into_iter()
+        .filter(|used| *used)
+        .count();
+
+        (classes < self.min).then(|| {
+            Finding::warning(format!(
+                "mix at least {} of lowercase, uppercase, digits and symbols",
+                self.min
+            ))
+        })
+    }
+}
+
+/// Warn on runs of the same character or of sequential characters, in the
+/// manner of pwquality's maxrepeat and maxsequence
+pub struct Repetition {
+    pub max: usize,
+}
+
+impl PasswordCheck for Repetition {
+    fn check(&self, password: &str, _username: &str) -> Option<Finding> {
+        let chars = password.chars().map(u32::from).collect::<Vec<_>>();
+        let (mut repeat, mut ascending, mut descending) = (1, 1, 1);
+
+        for pair in chars.windows(2) {
+            repeat = if pair[0] == pair[1] { repeat + 1 } else { 1 };
+            ascending = if pair[1] == pair[0] + 1 { ascending + 1 } else { 1 };
+            descending = if pair[0] == pair[1] + 1 { descending + 1 } else { 1 };
+
+            if repeat.max(ascending).max(descending) > self.max {
+                return Some(Finding::warning(format!(
+                    "avoid more than {} repeated or sequential characters in a row",
+                    self.max
+                )));
+            }
+        }
+
+        None
+    }
+}
+
+/// An ordered set of password checks
+pub struct PasswordPolicy {
+    checks: Vec<Box<dyn PasswordCheck>>,
+}
+
+impl PasswordPolicy {
+    /// A policy with no checks at all
+    pub fn empty() -> Self {
+        Self { checks: Vec::new() }
+    }
+
+    /// Add a check to the policy
+    pub fn with(mut self, check: impl PasswordCheck + 'static) -> Self {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    /// Every finding the policy raises, errors first
+    pub fn evaluate(&self, password: &str, username: &str) -> Vec<Finding> {
+        let mut findings = self
+            .checks
+            .iter()
+            .filter_map(|check| check.check(password, username))
+            .collect::<Vec<_>>();
+        findings.sort_by_key(|finding| finding.severity != Severity::Error);
+        findings
+    }
+
+    /// The first error, if the password is rejected
+    pub fn rejection(&self, password: &str, username: &str) -> Option<Finding> {
+        self.evaluate(password, username)
+            .into_iter()
+            .find(|finding| finding.severity == Severity::Error)
+    }
+}
+
+impl Default for PasswordPolicy {
+    /// pwquality's defaults, less its cracklib dictionary
+    fn default() -> Self {
+        Self::empty()
+            .with(MinLength(8))
+            .with(NotUsername)
+            .with(CommonPasswords)
+            .with(CharacterClasses { min: 3 })
+            .with(Repetition { max: 3 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy_rejects_weak_passwords() {
+        let policy = PasswordPolicy::default();
+
+        assert!(policy.rejection("short1!", "jane").is_some());
+        assert!(policy.rejection("Password123!", "jane").is_some());
+        assert!(policy.rejection("xjanex-2026", "jane").is_some());
+        assert!(
+            policy.rejection("Enaj-Horse-42", "jane").is_some(),
+            "reversed names are caught"
+        );
+        assert!(policy.evaluate("Correct-Horse-42", "jane").is_empty());
+    }
+
+    #[test]
+    fn warnings_do_not_reject() {
+        let policy = PasswordPolicy::default();
+        let findings = policy.evaluate("correcthorsebattery", "jane");
+
+        assert!(policy.rejection("correcthorsebattery", "jane").is_none());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+
+        assert!(!policy.evaluate("Horse-abcde-42", "jane").is_empty(), "sequences warn");
+        assert!(!policy.evaluate("Horse-aaaa-42", "jane").is_empty(), "repeats warn");
+    }
+
+    #[test]
+    fn policies_are_composable() {
+        let policy = PasswordPolicy::empty().with(MinLength(4));
+
+        assert!(policy.evaluate("password", "jane").is_empty());
+        assert!(policy.rejection("abc", "jane").is_some());
+    }
+}
@@ -0,0 +1,651 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deserializing typed documents from KDL
+//!
+//! Deserialization is driven by the target type: a struct names the fields
+//! it knows, so anything else in the document is reported as unknown, and a
+//! sequence accepts either one node's arguments or a run of repeated nodes.
+
+use std::{cell::RefCell, vec};
+
+use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor, value::SeqDeserializer},
+    forward_to_deserialize_any,
+};
+
+use crate::{Decoded, Error, Mode};
+
+/// Deserialize a struct or map from the nodes of a document
+pub fn from_document<T: DeserializeOwned>(doc: &KdlDocument, mode: Mode) -> Result<Decoded<T>, Error> {
+    let context = Context {
+        mode,
+        warnings: RefCell::default(),
+    };
+    let value = T::deserialize(Content {
+        context: &context,
+        path: String::new(),
+        entries: &[],
+        children: doc.nodes(),
+    })?;
+
+    Ok(Decoded {
+        value,
+        warnings: context.warnings.into_inner(),
+    })
+}
+
+/// Decides what happens to parts of a document the target type has no place for
+struct Context {
+    mode: Mode,
+    warnings: RefCell<Vec<String>>,
+}
+
+impl Context {
+    /// Refuse the document in strict mode, otherwise note what was skipped
+    fn ignore(&self, what: String) -> Result<(), Error> {
+        match self.mode {
+            Mode::Strict => Err(Error::Unknown(what)),
+            Mode::Lenient => {
+                self.warnings.borrow_mut().push(format!("ignoring {what}"));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Dotted path to a field, as reported in warnings and errors
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+/// What a field was found as within its parent
+enum Value<'a> {
+    /// A property, or the node's first argument for `$arg`
+    Scalar(&'a KdlValue),
+    /// All of the node's arguments, for `$args`
+    Args(Vec<&'a KdlValue>),
+    /// Every child node with the field's name
+    Nodes(Vec<&'a KdlNode>),
+}
+
+/// A struct or map held by a node's entries and children, or by a document
+struct Content<'a> {
+    context: &'a Context,
+    path: String,
+    entries: &'a [KdlEntry],
+    children: &'a [KdlNode],
+}
+
+impl<'a> Content<'a> {
+    fn of(context: &'a Context, path: String, node: &'a KdlNode) -> Self {
+        Self {
+            context,
+            path,
+            entries: node.entries(),
+            children: node.children().map(KdlDocument::nodes).unwrap_or_default(),
+        }
+    }
+
+    fn args(&self) -> Vec<&'a KdlValue> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.name().is_none())
+            .map(KdlEntry::value)
+            .collect()
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Content<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    /// Properties and child nodes, each keyed by name
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut pairs = Vec::new();
+
+        for entry in self.entries {
+            match entry.name() {
+                Some(name) => pairs.push((name.value().to_owned(), Value::Scalar(entry.value()))),
+                None => self
+                    .context
+                    .ignore(format!("unexpected argument {} to {}", entry.value(), self.path))?,
+            }
+        }
+        for node in self.children {
+            pairs.push((node.name().value().to_owned(), Value::Nodes(vec![node])));
+        }
+
+        visitor.visit_map(Pairs::new(self.context, self.path, pairs))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let args = self.args();
+        let mut used_args = 0;
+        let mut pairs = Vec::new();
+
+        for &field in fields {
+            if field == "$arg" {
+                used_args = used_args.max(1);
+                if let Some(&value) = args.first() {
+                    pairs.push((field.to_owned(), Value::Scalar(value)));
+                }
+            } else if field == "$args" {
+                used_args = args.len();
+                if !args.is_empty() {
+                    pairs.push((field.to_owned(), Value::Args(args.clone())));
+                }
+            } else if let Some(property) = field.strip_prefix('@') {
+                // As in KDL itself, the last property of a name wins
+                if let Some(entry) = self
+                    .entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.name().is_some_and(|name| name.value() == property))
+                {
+                    pairs.push((field.to_owned(), Value::Scalar(entry.value())));
+                }
+            } else {
+                let nodes = self
+                    .children
+                    .iter()
+                    .filter(|node| node.name().value() == field)
+                    .collect::<Vec<_>>();
+                if !nodes.is_empty() {
+                    pairs.push((field.to_owned(), Value::Nodes(nodes)));
+                }
+            }
+        }
+
+        for arg in args.iter().skip(used_args) {
+            self.context
+                .ignore(format!("unexpected argument {arg} to {}", self.path))?;
+        }
+        for entry in self.entries {
+            if let Some(name) = entry.name()
+                && !fields.iter().any(|field| field.strip_prefix('@') == Some(name.value()))
+            {
+                self.context
+                    .ignore(format!("unknown field {}", join(&self.path, name.value())))?;
+            }
+        }
+        for node in self.children {
+            if !fields.contains(&node.name().value()) {
+                self.context
+                    .ignore(format!("unknown field {}", join(&self.path, node.name().value())))?;
+            }
+        }
+
+        visitor.visit_map(Pairs::new(self.context, self.path, pairs))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct enum identifier
+    }
+}
+
+/// Key and value pairs of a struct or map
+struct Pairs<'a> {
+    context: &'a Context,
+    path: String,
+    pairs: vec::IntoIter<(String, Value<'a>)>,
+    value: Option<(String, Value<'a>)>,
+}
+
+impl<'a> Pairs<'a> {
+    fn new(context: &'a Context, path: String, pairs: Vec<(String, Value<'a>)>) -> Self {
+        Self {
+            context,
+            path,
+            pairs: pairs.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for Pairs<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.pairs.next() else {
+            return Ok(None);
+        };
+        let parsed = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(key.as_str()))?;
+        self.value = Some((key, value));
+        Ok(Some(parsed))
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, Error> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or(Error::Unsupported("value requested before its key"))?;
+        let path = if key.starts_with('$') {
+            self.path.clone()
+        } else {
+            join(&self.path, key.trim_start_matches('@'))
+        };
+
+        seed.deserialize(ValueDe {
+            context: self.context,
+            path: path.clone(),
+            value,
+        })
+        .map_err(|error| error.at(&path))
+    }
+}
+
+/// A single argument or property value
+struct Scalar<'a> {
+    context: &'a Context,
+    path: String,
+    value: &'a KdlValue,
+}
+
+impl<'de> de::Deserializer<'de> for Scalar<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            KdlValue::String(value) => visitor.visit_str(value),
+            KdlValue::Integer(value) => {
+                if let Ok(value) = i64::try_from(*value) {
+                    visitor.visit_i64(value)
+                } else if let Ok(value) = u64::try_from(*value) {
+                    visitor.visit_u64(value)
+                } else {
+                    visitor.visit_i128(*value)
+                }
+            }
+            KdlValue::Float(value) => visitor.visit_f64(*value),
+            KdlValue::Bool(value) => visitor.visit_bool(*value),
+            KdlValue::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            KdlValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    /// Unit variants are written as their names
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            KdlValue::String(value) => visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(value.as_str())),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    /// A lone argument also stands for a struct made of just that argument
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if fields.contains(&"$arg") {
+            let pairs = vec![("$arg".to_owned(), Value::Scalar(self.value))];
+            visitor.visit_map(Pairs::new(self.context, self.path, pairs))
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, Error> for Scalar<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// A single node: its arguments, properties and children
+struct Node<'a> {
+    context: &'a Context,
+    path: String,
+    node: &'a KdlNode,
+}
+
+impl<'a> Node<'a> {
+    fn has_content(&self) -> bool {
+        self.node.entries().iter().any(|entry| entry.name().is_some())
+            || self
+                .node
+                .children()
+                .is_some_and(|children| !children.nodes().is_empty())
+    }
+
+    fn args(&self) -> Vec<Scalar<'a>> {
+        self.node
+            .entries()
+            .iter()
+            .filter(|entry| entry.name().is_none())
+            .map(|entry| Scalar {
+                context: self.context,
+                path: self.path.clone(),
+                value: entry.value(),
+            })
+            .collect()
+    }
+
+    /// The node's only argument, for nodes holding a single value
+    fn scalar(self) -> Result<Scalar<'a>, Error> {
+        if self.has_content() {
+            self.context
+                .ignore(format!("properties and children of {}", self.path))?;
+        }
+
+        let mut args = self.args().into_iter();
+        let first = args.next().ok_or_else(|| Error::Invalid {
+            path: self.path.clone(),
+            message: "expected a value".to_owned(),
+        })?;
+        for extra in args {
+            self.context
+                .ignore(format!("unexpected argument {} to {}", extra.value, self.path))?;
+        }
+
+        Ok(first)
+    }
+
+    fn arg_seq(self) -> Result<SeqDeserializer<vec::IntoIter<Scalar<'a>>, Error>, Error> {
+        if self.has_content() {
+            self.context
+                .ignore(format!("properties and children of {}", self.path))?;
+        }
+        Ok(SeqDeserializer::new(self.args().into_iter()))
+    }
+}
+
+macro_rules! node_scalar {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.scalar()?.$method(visitor)
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for Node<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.has_content() {
+            return self.deserialize_map(visitor);
+        }
+
+        match self.args().len() {
+            0 => visitor.visit_unit(),
+            1 => self.scalar()?.deserialize_any(visitor),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    /// A bare node is a flag that is set
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.node.entries().is_empty() && !self.has_content() {
+            visitor.visit_bool(true)
+        } else {
+            self.scalar()?.deserialize_bool(visitor)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node.entries() {
+            [entry] if entry.name().is_none() && entry.value().is_null() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if !self.node.entries().is_empty() || self.has_content() {
+            self.context.ignore(format!("the contents of {}", self.path))?;
+        }
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.arg_seq()?.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        Content::of(self.context, self.path, self.node).deserialize_map(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        Content::of(self.context, self.path, self.node).deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.scalar()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    node_scalar! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_identifier
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, Error> for Node<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// A field's value, whichever form it was found in
+struct ValueDe<'a> {
+    context: &'a Context,
+    path: String,
+    value: Value<'a>,
+}
+
+/// A field narrowed down to one value
+enum One<'a> {
+    Scalar(Scalar<'a>),
+    Args(SeqDeserializer<vec::IntoIter<Scalar<'a>>, Error>),
+    Node(Node<'a>),
+}
+
+impl<'a> ValueDe<'a> {
+    fn scalar(&self, value: &'a KdlValue) -> Scalar<'a> {
+        Scalar {
+            context: self.context,
+            path: self.path.clone(),
+            value,
+        }
+    }
+
+    fn node(&self, node: &'a KdlNode) -> Node<'a> {
+        Node {
+            context: self.context,
+            path: self.path.clone(),
+            node,
+        }
+    }
+
+    /// The value a non-sequence field takes. As in KDL, a repeated node is
+    /// overridden by its last occurrence.
+    fn one(self) -> Result<One<'a>, Error> {
+        match &self.value {
+            Value::Scalar(value) => Ok(One::Scalar(self.scalar(*value))),
+            Value::Args(values) => Ok(One::Args(SeqDeserializer::new(
+                values
+                    .iter()
+                    .map(|&value| self.scalar(value))
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            ))),
+            Value::Nodes(nodes) => {
+                if nodes.len() > 1 {
+                    self.context.ignore(format!("all but the last {} node", self.path))?;
+                }
+                let &last = nodes.last().ok_or(Error::Unsupported("field without nodes"))?;
+                Ok(One::Node(self.node(last)))
+            }
+        }
+    }
+}
+
+macro_rules! one {
+    ($self:ident, $de:ident => $call:expr) => {
+        match $self.one()? {
+            One::Scalar($de) => $call,
+            One::Args($de) => $call,
+            One::Node($de) => $call,
+        }
+    };
+}
+
+macro_rules! value_one {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            one!(self, de => de.$method(visitor))
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for ValueDe<'_> {
+    type Error = Error;
+
+    /// One node's arguments, or each of a run of nodes or of a node with
+    /// content as an element
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if let Value::Nodes(nodes) = &self.value
+            && (nodes.len() > 1 || nodes.iter().any(|&node| self.node(node).has_content()))
+        {
+            let elements = nodes.iter().map(|&node| self.node(node)).collect::<Vec<_>>();
+            return SeqDeserializer::<_, Error>::new(elements.into_iter()).deserialize_seq(visitor);
+        }
+
+        one!(self, de => de.deserialize_seq(visitor))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        one!(self, de => de.deserialize_unit_struct(name, visitor))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        one!(self, de => de.deserialize_struct(name, fields, visitor))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        one!(self, de => de.deserialize_enum(name, variants, visitor))
+    }
+
+    value_one! {
+        deserialize_any deserialize_bool deserialize_option deserialize_unit deserialize_map
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_identifier deserialize_ignored_any
+    }
+}
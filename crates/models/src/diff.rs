@@ -0,0 +1,308 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! What changes between two install-models
+//!
+//! Used to show what adopting a discovered or imported record would change
+//! before it replaces the settings at hand. Password hashes are never shown,
+//! only whether they differ; the install date and version are not compared.
+
+use std::{collections::BTreeSet, fmt};
+
+use crate::install::{Accounts, InstallModel, Root, RootMode, Rtc, User};
+
+/// The differences from one install-model to another
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+    /// Installer settings that differ, in document order
+    pub settings: Vec<Change>,
+    pub packages_added: Vec<String>,
+    pub packages_removed: Vec<String>,
+    pub users_added: Vec<String>,
+    pub users_removed: Vec<String>,
+    /// Users in both, with the settings that differ
+    pub users_changed: Vec<UserChange>,
+}
+
+/// One setting, as it was and as it would be; `None` when unset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub field: &'static str,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserChange {
+    pub name: String,
+    pub fields: Vec<&'static str>,
+}
+
+impl Diff {
+    /// Compare `before` with `after`
+    pub fn between(before: &InstallModel, after: &InstallModel) -> Self {
+        let mut diff = Self::default();
+        let mut setting = |field, before: Option<String>, after: Option<String>| {
+            if before != after {
+                diff.settings.push(Change { field, before, after });
+            }
+        };
+
+        setting("strategy", before.strategy.clone(), after.strategy.clone());
+        setting(
+            "disk",
+            before.disk.as_ref().map(|disk| disk.device.clone()),
+            after.disk.as_ref().map(|disk| disk.device.clone()),
+        );
+        setting("locale", before.locale.clone(), after.locale.clone());
+        setting("timezone", before.timezone.clone(), after.timezone.clone());
+        setting("desktop", before.desktop.clone(), after.desktop.clone());
+
+        let (from, to) = (
+            before.formats.clone().unwrap_or_default(),
+            after.formats.clone().unwrap_or_default(),
+        );
+        setting("LC_TIME", from.time, to.time);
+        setting("LC_NUMERIC", from.numeric, to.numeric);
+        setting("LC_MONETARY", from.monetary, to.monetary);
+        setting("LC_PAPER", from.paper, to.paper);
+        setting("LC_MEASUREMENT", from.measurement, to.measurement);
+
+        setting("rtc", Some(rtc(before.rtc)), Some(rtc(after.rtc)));
+        setting("ntp", list(&before.ntp), list(&after.ntp));
+
+        let (from, to) = (
+            before.keyboard.clone().unwrap_or_default(),
+            after.keyboard.clone().unwrap_or_default(),
+        );
+        setting("keyboard layout", from.layout, to.layout);
+        setting("keyboard variant", from.variant, to.variant);
+        setting("keymap", from.keymap, to.keymap);
+
+        let (from, to) = (
+            before.accounts.clone().unwrap_or_default(),
+            after.accounts.clone().unwrap_or_default(),
+        );
+        setting("root", root(from.root.as_ref()), root(to.root.as_ref()));
+        if let (Some(Root { hash: Some(old), .. }), Some(Root { hash: Some(new), .. })) = (&from.root, &to.root)
+            && old != new
+        {
+            setting(
+                "root",
+                Some("password".to_owned()),
+                Some("a different password".to_owned()),
+            );
+        }
+        setting(
+            "autologin",
+            Some(from.autologin.to_string()),
+            Some(to.autologin.to_string()),
+        );
+        setting(
+            "ssh keys only",
+            Some(keys_only(&from).to_string()),
+            Some(keys_only(&to).to_string()),
+        );
+
+        (diff.packages_added, diff.packages_removed) = added_removed(packages(before), packages(after));
+        diff.compare_users(&from.users, &to.users);
+
+        diff
+    }
+
+    /// Whether adopting `after` changes nothing
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn compare_users(&mut self, before: &[User], after: &[User]) {
+        let names = |users: &[User]| users.iter().map(|user| user.name.clone()).collect::<BTreeSet<_>>();
+        (self.users_added, self.users_removed) = added_removed(names(before), names(after));
+
+        for old in before {
+            let Some(new) = after.iter().find(|user| user.name == old.name) else {
+                continue;
+            };
+
+            let fields = [
+                ("real name", old.realname != new.realname),
+                ("password", old.hash != new.hash),
+                ("administrator", old.admin != new.admin),
+                ("shell", old.shell != new.shell),
+                ("uid", old.uid != new.uid),
+                ("gid", old.gid != new.gid),
+                ("home", old.home != new.home),
+                ("create home", old.create_home != new.create_home),
+                ("groups", old.groups != new.groups),
+                ("ssh keys", old.ssh_keys != new.ssh_keys),
+            ]
+            .into_iter()
+            .filter_map(|(field, differs)| differs.then_some(field))
+            .collect::<Vec<_>>();
+
+            if !fields.is_empty() {
+                self.users_changed.push(UserChange {
+                    name: old.name.clone(),
+                    fields,
+                });
+            }
+        }
+    }
+}
+
+/// One line per difference: `~` a changed setting, `+` added, `-` removed
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+
+        let unset = |value: &Option<String>| value.clone().unwrap_or_else(|| "(unset)".to_owned());
+        for change in &self.settings {
+            writeln!(
+                f,
+                "~ {}: {} -> {}",
+                change.field,
+                unset(&change.before),
+                unset(&change.after)
+            )?;
+        }
+        for name in &self.users_added {
+            writeln!(f, "+ user {name}")?;
+        }
+        for name in &self.users_removed {
+            writeln!(f, "- user {name}")?;
+        }
+        for user in &self.users_changed {
+            writeln!(f, "~ user {}: {}", user.name, user.fields.join(", "))?;
+        }
+        for package in &self.packages_added {
+            writeln!(f, "+ {package}")?;
+        }
+        for package in &self.packages_removed {
+            writeln!(f, "- {package}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn rtc(rtc: Option<Rtc>) -> String {
+    match rtc.unwrap_or(Rtc::Utc) {
+        Rtc::Utc => "utc".to_owned(),
+        Rtc::Local => "local".to_owned(),
+    }
+}
+
+fn root(root: Option<&Root>) -> Option<String> {
+    let root = root?;
+    Some(
+        match root.mode {
+            Some(RootMode::Locked) => "locked",
+            Some(RootMode::Disabled) => "disabled",
+            None if root.hash.is_some() => "password",
+            None => return None,
+        }
+        .to_owned(),
+    )
+}
+
+fn keys_only(accounts: &Accounts) -> bool {
+    accounts.sshd.as_ref().is_some_and(|sshd| sshd.keys_only)
+}
+
+fn list(values: &[String]) -> Option<String> {
+    (!values.is_empty()).then(|| values.join(" "))
+}
+
+fn packages(model: &InstallModel) -> BTreeSet<String> {
+    model
+        .system_model
+        .as_ref()
+        .map(|system| system.packages.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// What is only in `after`, and what is only in `before`, sorted
+fn added_removed(before: BTreeSet<String>, after: BTreeSet<String>) -> (Vec<String>, Vec<String>) {
+    (
+        after.difference(&before).cloned().collect(),
+        before.difference(&after).cloned().collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        install::{Root, Sshd},
+        system::SystemModel,
+    };
+
+    fn record(packages: &[&str], users: &[&str]) -> InstallModel {
+        InstallModel {
+            strategy: Some("whole_disk_xfs".to_owned()),
+            locale: Some("en_US.UTF-8".to_owned()),
+            accounts: Some(Accounts {
+                root: Some(Root {
+                    hash: Some("$y$old".to_owned()),
+                    mode: None,
+                }),
+                users: users
+                    .iter()
+                    .map(|name| User {
+                        name: name.to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }),
+            system_model: Some(SystemModel::new(packages.iter().map(|name| name.to_string()).collect())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn identical_models_have_no_changes() {
+        let model = record(&["bash"], &["jane"]);
+        let diff = Diff::between(&model, &model);
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no changes\n");
+    }
+
+    #[test]
+    fn reports_settings_packages_and_accounts() {
+        let before = record(&["bash", "vim"], &["jane", "john"]);
+        let mut after = record(&["bash", "helix"], &["jane", "alex"]);
+        after.locale = Some("de_DE.UTF-8".to_owned());
+        after.strategy = None;
+        let accounts = after.accounts.as_mut().expect("accounts");
+        accounts.root.as_mut().expect("root").hash = Some("$y$new".to_owned());
+        accounts.users[0].admin = true;
+        accounts.sshd = Some(Sshd { keys_only: true });
+
+        let diff = Diff::between(&before, &after);
+
+        assert_eq!(diff.packages_added, ["helix"]);
+        assert_eq!(diff.packages_removed, ["vim"]);
+        assert_eq!(diff.users_added, ["alex"]);
+        assert_eq!(diff.users_removed, ["john"]);
+        assert_eq!(
+            diff.users_changed,
+            [UserChange {
+                name: "jane".to_owned(),
+                fields: vec!["administrator"],
+            }]
+        );
+
+        let fields = diff.settings.iter().map(|change| change.field).collect::<Vec<_>>();
+        assert_eq!(fields, ["strategy", "locale", "root", "ssh keys only"]);
+
+        let report = diff.to_string();
+        assert!(report.contains("~ strategy: whole_disk_xfs -> (unset)\n"), "{report}");
+        assert!(report.contains("~ locale: en_US.UTF-8 -> de_DE.UTF-8\n"), "{report}");
+        assert!(!report.contains("$y$"), "hashes are never shown: {report}");
+    }
+}
@@ -0,0 +1,373 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! The install-model: the installer's record of an installation
+//!
+//! A strict superset of the system-model: installer settings wrapping a
+//! nested `system-model` node. Written to /etc/moss/install-model.kdl on the
+//! target; re-importing it reproduces the installation. Version 2, with
+//! every node optional:
+//!
+//! ```kdl
+//! install-model {
+//!     version 2
+//!     strategy "whole_disk_xfs"
+//!     disk "/dev/sda" wwn="0x5002538e40a1b2c3" // or by-id=, serial=, by-path=
+//!     locale "en_US.UTF-8"
+//!     timezone "Europe/London"
+//!     desktop "gnome"
+//!     installed "2026-01-01T00:00:00+00:00"
+//!     formats LC_TIME="en_GB.UTF-8" // LC_NUMERIC, LC_MONETARY, LC_PAPER, LC_MEASUREMENT
+//!     rtc "local"
+//!     ntp "0.pool.ntp.org" "1.pool.ntp.org"
+//!     keyboard layout="gb" variant="extd" keymap="uk"
+//!     accounts {
+//!         root hash="$y$..." // or mode="locked" / mode="disabled"
+//!         user name="jane" realname="Jane" hash="$y$..." admin=#true \
+//!             shell="/usr/bin/zsh" uid=1000 gid=1000 home="/home/jane" create-home=#false {
+//!             groups "docker"
+//!             ssh-key "ssh-ed25519 AAAA..."
+//!         }
+//!         autologin #true
+//!         sshd keys-only=#true
+//!     }
+//!     system-model { /* moss's system-model */ }
+//! }
+//! ```
+//!
+//! Documents without a `version` node predate versioning and are version 1,
+//! where users carry no `admin` flag and all of them were administrators.
+//! Older documents are migrated when read.
+
+use kdl::{KdlDocument, KdlEntry, KdlNode};
+use serde::{Deserialize, Serialize};
+
+use crate::{Decoded, Error, Mode, from_document, system::SystemModel, to_document};
+
+/// The install-model schema version this crate writes
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Upgrades from each older schema version to the next, indexed from version 1
+const MIGRATIONS: &[fn(&mut KdlNode)] = &[migrate_v1];
+
+/// An install-model.kdl document
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct InstallModel {
+    /// Schema version; always [`SCHEMA_VERSION`] once read
+    pub version: u32,
+    /// Identifier of the provisioning strategy
+    pub strategy: Option<String>,
+    pub disk: Option<Disk>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub desktop: Option<String>,
+    /// When the installation was made, in RFC 3339
+    pub installed: Option<String>,
+    pub formats: Option<Formats>,
+    pub rtc: Option<Rtc>,
+    pub ntp: Vec<String>,
+    pub keyboard: Option<Keyboard>,
+    pub accounts: Option<Accounts>,
+    pub system_model: Option<SystemModel>,
+}
+
+impl Default for InstallModel {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            strategy: None,
+            disk: None,
+            locale: None,
+            timezone: None,
+            desktop: None,
+            installed: None,
+            formats: None,
+            rtc: None,
+            ntp: Vec::new(),
+            keyboard: None,
+            accounts: None,
+            system_model: None,
+        }
+    }
+}
+
+/// The target disk, by device node and, where it has one, a stable identifier
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Disk {
+    #[serde(rename = "$arg")]
+    pub device: String,
+    #[serde(rename = "@wwn")]
+    pub wwn: Option<String>,
+    #[serde(rename = "@by-id")]
+    pub by_id: Option<String>,
+    #[serde(rename = "@serial")]
+    pub serial: Option<String>,
+    #[serde(rename = "@by-path")]
+    pub by_path: Option<String>,
+}
+
+/// Per-category locale overrides, named as their locale(7) variables
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Formats {
+    #[serde(rename = "@LC_TIME")]
+    pub time: Option<String>,
+    #[serde(rename = "@LC_NUMERIC")]
+    pub numeric: Option<String>,
+    #[serde(rename = "@LC_MONETARY")]
+    pub monetary: Option<String>,
+    #[serde(rename = "@LC_PAPER")]
+    pub paper: Option<String>,
+    #[serde(rename = "@LC_MEASUREMENT")]
+    pub measurement: Option<String>,
+}
+
+/// The time the hardware clock keeps
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rtc {
+    Utc,
+    Local,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keyboard {
+    #[serde(rename = "@layout")]
+    pub layout: Option<String>,
+    #[serde(rename = "@variant")]
+    pub variant: Option<String>,
+    #[serde(rename = "@keymap")]
+    pub keymap: Option<String>,
+}
+
+/// Accounts, with passwords only ever as crypt(3) hashes
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Accounts {
+    pub root: Option<Root>,
+    #[serde(rename = "user")]
+    pub users: Vec<User>,
+    /// Log the first user in automatically
+    #[serde(skip_serializing_if = "is_false")]
+    pub autologin: bool,
+    pub sshd: Option<Sshd>,
+}
+
+/// The root account: a password hash, or a mode without one
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Root {
+    #[serde(rename = "@hash")]
+    pub hash: Option<String>,
+    #[serde(rename = "@mode")]
+    pub mode: Option<RootMode>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RootMode {
+    Locked,
+    Disabled,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct User {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@realname")]
+    pub realname: String,
+    #[serde(rename = "@hash")]
+    pub hash: String,
+    #[serde(rename = "@admin")]
+    pub admin: bool,
+    #[serde(rename = "@shell")]
+    pub shell: Option<String>,
+    #[serde(rename = "@uid")]
+    pub uid: Option<u32>,
+    #[serde(rename = "@gid")]
+    pub gid: Option<u32>,
+    #[serde(rename = "@home")]
+    pub home: Option<String>,
+    #[serde(rename = "@create-home", skip_serializing_if = "is_true")]
+    pub create_home: bool,
+    pub groups: Vec<String>,
+    #[serde(rename = "ssh-key")]
+    pub ssh_keys: Vec<SshKey>,
+}
+
+impl Default for User {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            realname: String::new(),
+            hash: String::new(),
+            admin: false,
+            shell: None,
+            uid: None,
+            gid: None,
+            home: None,
+            create_home: true,
+            groups: Vec::new(),
+            ssh_keys: Vec::new(),
+        }
+    }
+}
+
+/// One OpenSSH public key, a node each
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshKey {
+    #[serde(rename = "$arg")]
+    pub key: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sshd {
+    /// Refuse password logins and root logins
+    #[serde(rename = "@keys-only")]
+    pub keys_only: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+/// Whether a document holds an install-model, rather than a bare system-model
+pub fn is_install_model(text: &str) -> Result<bool, Error> {
+    let doc: KdlDocument = text.parse()?;
+    Ok(doc.get("install-model").is_some())
+}
+
+impl InstallModel {
+    /// Read an install-model document, migrating older schema versions. The
+    /// warnings include a newer version having been read.
+    pub fn from_kdl(text: &str, mode: Mode) -> Result<Decoded<Self>, Error> {
+        let mut doc: KdlDocument = text.parse()?;
+        let install = doc.get_mut("install-model").ok_or(Error::NotInstallModel)?;
+        let mut warnings = Vec::new();
+
+        let version = match install.children().and_then(|children| children.get("version")) {
+            None => 1,
+            Some(node) => match node.get(0).and_then(|value| value.as_integer()) {
+                Some(version) if version >= 1 => u32::try_from(version).unwrap_or(u32::MAX),
+                _ => {
+                    warnings.push(format!(
+                        "invalid install-model version, reading it as version {SCHEMA_VERSION}"
+                    ));
+                    SCHEMA_VERSION
+                }
+            },
+        };
+        if version > SCHEMA_VERSION {
+            warnings.push(format!(
+                "install-model version {version} is newer than this installer's {SCHEMA_VERSION}; \
+                 settings it does not know will be ignored"
+            ));
+        }
+        for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+            migration(install);
+        }
+
+        let mut children = install.children().cloned().unwrap_or_default();
+        children.nodes_mut().retain(|node| node.name().value() != "version");
+
+        let mut decoded = from_document::<Self>(&children, mode)?;
+        decoded.value.version = SCHEMA_VERSION;
+        warnings.append(&mut decoded.warnings);
+        decoded.warnings = warnings;
+
+        Ok(decoded)
+    }
+
+    /// The document as written to the target
+    pub fn to_kdl(&self) -> String {
+        let mut install = KdlNode::new("install-model");
+        install.set_children(to_document(self).expect("install-model fields all have a KDL form"));
+
+        let mut doc = KdlDocument::new();
+        doc.nodes_mut().push(install);
+        doc.autoformat();
+        doc.to_string()
+    }
+}
+
+/// Version 1 to 2: users gained an `admin` flag, and every user a version 1
+/// record created was an administrator
+fn migrate_v1(install: &mut KdlNode) {
+    let Some(accounts) = install
+        .children_mut()
+        .as_mut()
+        .and_then(|children| children.get_mut("accounts"))
+        .and_then(|accounts| accounts.children_mut().as_mut())
+    else {
+        return;
+    };
+
+    for user in accounts
+        .nodes_mut()
+        .iter_mut()
+        .filter(|node| node.name().value() == "user")
+    {
+        if user.get("admin").is_none() {
+            user.push(KdlEntry::new_prop("admin", true));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emitted_models_are_current() {
+        let text = InstallModel::default().to_kdl();
+        let doc: KdlDocument = text.parse().expect("install-model must be valid KDL");
+        let version = doc
+            .get("install-model")
+            .and_then(|install| install.children())
+            .and_then(|children| children.get_arg("version"))
+            .and_then(|value| value.as_integer());
+        assert_eq!(version, Some(i128::from(SCHEMA_VERSION)));
+
+        let decoded = InstallModel::from_kdl(&text, Mode::Strict).expect("emitted model must parse");
+        assert_eq!(decoded.value, InstallModel::default());
+        assert!(decoded.warnings.is_empty());
+    }
+
+    #[test]
+    fn newer_models_warn_or_fail() {
+        let text = r#"install-model {
+    version 3
+    locale "en_US.UTF-8"
+    bootloader "grub"
+    accounts {
+        homed #true
+    }
+}
+"#;
+        let decoded = InstallModel::from_kdl(text, Mode::Lenient).expect("lenient read");
+
+        assert_eq!(
+            decoded.value.locale.as_deref(),
+            Some("en_US.UTF-8"),
+            "known fields still apply"
+        );
+        assert_eq!(decoded.warnings.len(), 3);
+        assert!(decoded.warnings[0].contains("version 3 is newer"));
+        assert!(decoded.warnings[1].contains("bootloader"));
+        assert!(decoded.warnings[2].contains("accounts.homed"));
+
+        let error = InstallModel::from_kdl(text, Mode::Strict).expect_err("strict read");
+        assert_eq!(error.to_string(), "unknown field bootloader");
+    }
+}
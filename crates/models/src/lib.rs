@@ -0,0 +1,223 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Typed AerynOS installation documents
+//!
+//! The install-model and system-model as plain structs, so every frontend
+//! and the backend read and write the same documents. They are mapped to KDL
+//! through serde, with these conventions:
+//!
+//! - A struct or map is a list of nodes, one per field, named after it.
+//! - A scalar field is a node with one argument: `locale "en_US.UTF-8"`.
+//! - `None` and empty sequences are left out.
+//! - A sequence of scalars is one node with an argument each, and a sequence
+//!   of anything else repeats the node: `ntp "a" "b"`, but `user ...` per user.
+//! - A nested struct is a node holding the struct's fields as its children.
+//!   Within it, a field renamed `@name` is the `name=` property, `$arg` is
+//!   the first argument and `$args` all of them.
+//! - `()` is a node with nothing in it, and `bool` may be written as a bare
+//!   node to mean `true`.
+//! - Unit enum variants are written as their names.
+//!
+//! Reading is either [`Mode::Strict`], where anything the types have no place
+//! for is an error, or [`Mode::Lenient`], where it is skipped and reported.
+
+mod de;
+pub mod diff;
+pub mod install;
+mod ser;
+pub mod system;
+
+use kdl::KdlError;
+use thiserror::Error;
+
+pub use de::from_document;
+pub use ser::to_document;
+
+/// How to treat document content the types do not know
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Refuse the document
+    Strict,
+    /// Skip it, with a warning
+    #[default]
+    Lenient,
+}
+
+/// A document read into its typed form
+#[derive(Debug)]
+pub struct Decoded<T> {
+    pub value: T,
+    /// What was skipped in lenient mode, or migrated
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{}", parse_detail(.0))]
+    Parse(#[from] KdlError),
+
+    #[error("{0}")]
+    Unknown(String),
+
+    #[error("{path}: {message}")]
+    Invalid { path: String, message: String },
+
+    #[error("{0}")]
+    Custom(String),
+
+    #[error("{0}")]
+    Unsupported(&'static str),
+
+    #[error("document has no install-model node")]
+    NotInstallModel,
+}
+
+impl Error {
+    /// Attach the path of the field being read, unless a nested field has
+    fn at(self, path: &str) -> Self {
+        match self {
+            Self::Custom(message) if !path.is_empty() => Self::Invalid {
+                path: path.to_owned(),
+                message,
+            },
+            error => error,
+        }
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// Render a KDL parse failure as a line/column detail per diagnostic
+fn parse_detail(error: &KdlError) -> String {
+    let mut details = Vec::new();
+
+    for diag in &error.diagnostics {
+        let offset = diag.span.offset().min(error.input.len());
+        let prefix = error.input.get(..offset).unwrap_or_default();
+        let line = prefix.matches('\n').count() + 1;
+        let column = prefix.rsplit('\n').next().map_or(0, str::len) + 1;
+        let message = diag.message.as_deref().unwrap_or("invalid KDL");
+
+        match diag.help.as_deref() {
+            Some(help) => details.push(format!("line {line}, column {column}: {message} ({help})")),
+            None => details.push(format!("line {line}, column {column}: {message}")),
+        }
+    }
+
+    if details.is_empty() {
+        return error.to_string();
+    }
+
+    details.join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kdl::KdlDocument;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default, rename_all = "kebab-case")]
+    struct Document {
+        name: Option<String>,
+        tags: Vec<String>,
+        #[serde(rename = "item")]
+        items: Vec<Item>,
+        enabled: bool,
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Item {
+        #[serde(rename = "$arg")]
+        id: String,
+        #[serde(rename = "@count")]
+        count: Option<u32>,
+    }
+
+    fn read(text: &str, mode: Mode) -> Result<Decoded<Document>, Error> {
+        from_document(&text.parse::<KdlDocument>()?, mode)
+    }
+
+    #[test]
+    fn round_trips_through_kdl() {
+        let document = Document {
+            name: Some("lichen".to_owned()),
+            tags: vec!["a".to_owned(), "b".to_owned()],
+            items: vec![
+                Item {
+                    id: "one".to_owned(),
+                    count: Some(1),
+                },
+                Item {
+                    id: "two".to_owned(),
+                    count: None,
+                },
+            ],
+            enabled: true,
+        };
+
+        let mut doc = to_document(&document).expect("serializable");
+        let names = doc.nodes().iter().map(|node| node.name().value()).collect::<Vec<_>>();
+        assert_eq!(names, ["name", "tags", "item", "item", "enabled"]);
+        assert_eq!(doc.get_args("tags").len(), 2, "scalars share one node");
+
+        doc.autoformat();
+        let decoded = read(&doc.to_string(), Mode::Strict).expect("round trip");
+        assert_eq!(decoded.value, document);
+        assert!(decoded.warnings.is_empty());
+    }
+
+    #[test]
+    fn lenient_reads_report_unknown_fields() {
+        let text = "name lichen\ncolour red\nitem one count=1 size=2\nenabled\n";
+
+        let decoded = read(text, Mode::Lenient).expect("lenient read");
+        assert_eq!(decoded.value.items[0].count, Some(1));
+        assert!(decoded.value.enabled, "a bare node is a set flag");
+        assert_eq!(
+            decoded.warnings,
+            ["ignoring unknown field colour", "ignoring unknown field item.size"]
+        );
+
+        let error = read(text, Mode::Strict).expect_err("strict read");
+        assert_eq!(error.to_string(), "unknown field colour");
+    }
+
+    #[test]
+    fn single_nodes_fill_sequences() {
+        let decoded = read("item one\ntags a\n", Mode::Strict).expect("single entries");
+        assert_eq!(decoded.value.items.len(), 1);
+        assert_eq!(decoded.value.tags, ["a"]);
+    }
+
+    #[test]
+    fn type_errors_name_the_field() {
+        let error = read("item one count=many\n", Mode::Strict).expect_err("count is a number");
+        assert!(error.to_string().starts_with("item.count: "), "{error}");
+    }
+
+    #[test]
+    fn parse_errors_carry_location() {
+        let truncated = "repositories {\n    volatile {\n        priority 0\n}\npackages {\n            bash\n}\n";
+        let error = read(truncated, Mode::Lenient).expect_err("truncated document must fail");
+
+        assert!(
+            error.to_string().contains("line "),
+            "detail should locate the failure: {error}"
+        );
+    }
+}
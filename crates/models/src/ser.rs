@@ -0,0 +1,356 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Serializing typed documents to KDL
+//!
+//! Values are first collected into a [`Tree`], since whether a sequence
+//! becomes one node's arguments or a run of repeated nodes depends on what it
+//! holds, and then laid out as nodes.
+
+use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+use serde::{
+    Serialize,
+    ser::{self, Impossible},
+};
+
+use crate::Error;
+
+/// A serialized value, before it is laid out as KDL
+enum Tree {
+    /// `None`: nothing is written
+    Absent,
+    /// `()` and unit structs: a node without entries
+    Unit,
+    Scalar(KdlValue),
+    Seq(Vec<Tree>),
+    /// Structs and maps, in field order
+    Map(Vec<(String, Tree)>),
+}
+
+/// Serialize a struct or map as the nodes of a document
+pub fn to_document<T: Serialize + ?Sized>(value: &T) -> Result<KdlDocument, Error> {
+    let Tree::Map(fields) = value.serialize(TreeSerializer)? else {
+        return Err(Error::Unsupported("documents must be structs or maps"));
+    };
+
+    let mut node = KdlNode::new("");
+    let mut doc = KdlDocument::new();
+    lay_out(fields, &mut node, &mut doc)?;
+
+    if node.entries().is_empty() {
+        Ok(doc)
+    } else {
+        Err(Error::Unsupported("documents cannot carry arguments or properties"))
+    }
+}
+
+/// Lay out struct or map content as a node's entries and children
+fn lay_out(fields: Vec<(String, Tree)>, node: &mut KdlNode, children: &mut KdlDocument) -> Result<(), Error> {
+    for (key, tree) in fields {
+        if key == "$arg" || key == "$args" {
+            match tree {
+                Tree::Absent => {}
+                Tree::Scalar(value) => node.push(KdlEntry::new(value)),
+                Tree::Seq(items) => {
+                    for item in items {
+                        let Tree::Scalar(value) = item else {
+                            return Err(Error::Unsupported("arguments must be scalars"));
+                        };
+                        node.push(KdlEntry::new(value));
+                    }
+                }
+                _ => return Err(Error::Unsupported("arguments must be scalars")),
+            }
+        } else if let Some(name) = key.strip_prefix('@') {
+            match tree {
+                Tree::Absent => {}
+                Tree::Scalar(value) => node.push(KdlEntry::new_prop(name, value)),
+                _ => return Err(Error::Unsupported("properties must be scalars")),
+            }
+        } else {
+            children.nodes_mut().extend(nodes(&key, tree)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// The nodes a field is written as
+fn nodes(name: &str, tree: Tree) -> Result<Vec<KdlNode>, Error> {
+    let mut node = KdlNode::new(name);
+
+    match tree {
+        Tree::Absent => return Ok(Vec::new()),
+        Tree::Unit => {}
+        Tree::Scalar(value) => node.push(KdlEntry::new(value)),
+        // Scalars share one node as its arguments, anything else repeats the node
+        Tree::Seq(items) if items.iter().all(|item| matches!(item, Tree::Scalar(_))) => {
+            if items.is_empty() {
+                return Ok(Vec::new());
+            }
+            for item in items {
+                if let Tree::Scalar(value) = item {
+                    node.push(KdlEntry::new(value));
+                }
+            }
+        }
+        Tree::Seq(items) => {
+            let mut repeated = Vec::new();
+            for item in items {
+                repeated.extend(nodes(name, item)?);
+            }
+            return Ok(repeated);
+        }
+        Tree::Map(fields) => {
+            let mut children = KdlDocument::new();
+            lay_out(fields, &mut node, &mut children)?;
+            if !children.nodes().is_empty() {
+                node.set_children(children);
+            }
+        }
+    }
+
+    Ok(vec![node])
+}
+
+/// Serializes any value into a [`Tree`]
+struct TreeSerializer;
+
+impl ser::Serializer for TreeSerializer {
+    type Ok = Tree;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = Impossible<Tree, Error>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = Impossible<Tree, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Tree, Error> {
+        Ok(Tree::Scalar(KdlValue::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Tree, Error> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Tree, Error> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Tree, Error> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Tree, Error> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Tree, Error> {
+        Ok(Tree::Scalar(KdlValue::Integer(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Tree, Error> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Tree, Error> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Tree, Error> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Tree, Error> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Tree, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Tree, Error> {
+        Ok(Tree::Scalar(KdlValue::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Tree, Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Tree, Error> {
+        Ok(Tree::Scalar(KdlValue::String(v.to_owned())))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Tree, Error> {
+        Err(Error::Unsupported("byte strings have no KDL form"))
+    }
+
+    fn serialize_none(self) -> Result<Tree, Error> {
+        Ok(Tree::Absent)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Tree, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Tree, Error> {
+        Ok(Tree::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Tree, Error> {
+        Ok(Tree::Unit)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Tree, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Tree, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Tree, Error> {
+        Err(Error::Unsupported("only unit enum variants have a KDL form"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Unsupported("only unit enum variants have a KDL form"))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            fields: Vec::with_capacity(len.unwrap_or_default()),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Unsupported("only unit enum variants have a KDL form"))
+    }
+}
+
+struct SeqSerializer(Vec<Tree>);
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Tree;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.0.push(value.serialize(TreeSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Tree, Error> {
+        Ok(Tree::Seq(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Tree;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Tree, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Tree;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Tree, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct MapSerializer {
+    fields: Vec<(String, Tree)>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Tree;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match key.serialize(TreeSerializer)? {
+            Tree::Scalar(KdlValue::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::Unsupported("map keys must be strings")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or(Error::Unsupported("map value serialized before its key"))?;
+        self.fields.push((key, value.serialize(TreeSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Tree, Error> {
+        Ok(Tree::Map(self.fields))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Tree;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.fields.push((key.to_owned(), value.serialize(TreeSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Tree, Error> {
+        Ok(Tree::Map(self.fields))
+    }
+}
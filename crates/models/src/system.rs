@@ -0,0 +1,153 @@
+// SPDX-FileCopyrightText: Copyright © 2026 AerynOS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! moss's system-model: the repositories and packages of a system
+
+use std::collections::BTreeMap;
+
+use kdl::KdlDocument;
+use serde::{Deserialize, Serialize};
+
+use crate::{Decoded, Error, Mode, from_document, to_document};
+
+/// A system-model.kdl document
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemModel {
+    /// Repositories by id
+    pub repositories: BTreeMap<String, Repository>,
+    /// Package names and providers, written as one empty node each
+    #[serde(with = "node_names")]
+    pub packages: Vec<String>,
+}
+
+/// One moss repository
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Repository {
+    pub description: Option<String>,
+    /// Direct URI of the repository index
+    pub uri: Option<String>,
+    /// Base URI that moss completes with the channel, version and arch
+    pub base_uri: Option<String>,
+    pub channel: Option<String>,
+    pub version: Option<String>,
+    pub arch: Option<String>,
+    pub priority: Option<i64>,
+}
+
+impl SystemModel {
+    /// A system-model installing `packages` from the AerynOS unstable stream
+    pub fn new(packages: Vec<String>) -> Self {
+        let unstable = Repository {
+            description: Some("AerynOS unstable package stream".to_owned()),
+            base_uri: Some("https://cdn.aerynos.dev/".to_owned()),
+            version: Some("stream/unstable".to_owned()),
+            priority: Some(0),
+            ..Default::default()
+        };
+
+        Self {
+            repositories: BTreeMap::from([("unstable".to_owned(), unstable)]),
+            packages,
+        }
+    }
+
+    /// Read a system-model document
+    pub fn from_kdl(text: &str, mode: Mode) -> Result<Decoded<Self>, Error> {
+        from_document(&text.parse::<KdlDocument>()?, mode)
+    }
+
+    /// The document as moss reads it
+    pub fn to_kdl(&self) -> String {
+        let mut doc = to_document(self).expect("system-model fields all have a KDL form");
+        doc.autoformat();
+        doc.to_string()
+    }
+
+    /// Repositories that can be fetched as is, through a direct `uri`
+    pub fn direct_repositories(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.repositories
+            .iter()
+            .filter_map(|(id, repository)| Some((id.as_str(), repository.uri.as_deref()?)))
+    }
+}
+
+/// Lists written as the names of empty child nodes, as moss writes packages
+mod node_names {
+    use std::fmt;
+
+    use serde::{
+        Deserializer, Serializer,
+        de::{IgnoredAny, MapAccess, Visitor},
+    };
+
+    pub fn serialize<S: Serializer>(names: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(names.iter().map(|name| (name, ())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+        struct Names;
+
+        impl<'de> Visitor<'de> for Names {
+            type Value = Vec<String>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a node per name")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Vec<String>, A::Error> {
+                let mut names = Vec::new();
+                while let Some(name) = map.next_key::<String>()? {
+                    map.next_value::<IgnoredAny>()?;
+                    names.push(name);
+                }
+                Ok(names)
+            }
+        }
+
+        deserializer.deserialize_map(Names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packages_round_trip_in_order() {
+        let model = SystemModel::new(vec!["firefox".to_owned(), "binary(cc)".to_owned()]);
+        let decoded = SystemModel::from_kdl(&model.to_kdl(), Mode::Strict).expect("emitted model must parse");
+
+        assert_eq!(decoded.value, model);
+        assert_eq!(
+            decoded.value.direct_repositories().count(),
+            0,
+            "unstable goes through base-uri"
+        );
+    }
+
+    #[test]
+    fn reads_moss_documents() {
+        let text = r#"
+repositories {
+    local {
+        uri "file:///srv/repo/stone.index"
+        priority 10
+    }
+}
+packages {
+    bash
+    "pkgconfig(zlib)"
+}
+"#;
+        let decoded = SystemModel::from_kdl(text, Mode::Strict).expect("moss system-model");
+
+        assert_eq!(decoded.value.packages, ["bash", "pkgconfig(zlib)"]);
+        assert_eq!(
+            decoded.value.direct_repositories().collect::<Vec<_>>(),
+            [("local", "file:///srv/repo/stone.index")]
+        );
+    }
+}
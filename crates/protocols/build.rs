@@ -11,6 +11,8 @@ fn main() {
     println!("cargo:rerun-if-changed=storage/provisioner.proto");
     println!("cargo:rerun-if-changed=storage/types.proto");
     println!("cargo:rerun-if-changed=install.proto");
+    println!("cargo:rerun-if-changed=keyboard.proto");
+    println!("cargo:rerun-if-changed=timezones.proto");
 
     tonic_build::configure()
         .build_server(true)
@@ -27,6 +29,8 @@ fn main() {
                 "storage/provisioner.proto",
                 "storage/types.proto",
                 "install.proto",
+                "keyboard.proto",
+                "timezones.proto",
             ],
             &["."],
         )
@@ -27,6 +27,7 @@ where
             node: partition.node.to_string_lossy().to_string(),
             device: partition.device.to_string_lossy().to_string(),
             display_size: format_size(partition.size * SECTOR_SIZE),
+            busy: None,
         }
     }
 }
@@ -36,29 +37,48 @@ where
 /// Creates a new Disk protobuf message from either a physical disk or loopback device.
 /// For physical disks, copies name, sectors, device path, model and vendor info, and all partitions.
 /// For loopback devices, copies the same fields but accesses partition info through the backing disk.
+/// Stable identifiers, media attributes, busy state and health are left for
+/// the backend to fill in, as reading them takes the udev database and sysfs.
 impl<T> From<T> for proto_disks::Disk
 where
     T: Deref<Target = BlockDevice>,
 {
     fn from(device: T) -> Self {
         match &*device {
-            BlockDevice::Disk(disk) => proto_disks::Disk {
-                name: device.name().to_owned(),
-                sectors: device.sectors(),
-                device: device.device().to_string_lossy().to_string(),
-                model: disk.model().map(|m| m.to_owned()),
-                vendor: disk.vendor().map(|v| v.to_owned()),
-                partitions: device.partitions().iter().map(Into::into).collect(),
-                kind: match **disk {
-                    disks::Disk::Scsi(_) => proto_disks::DiskKind::Scsi as i32,
-                    disks::Disk::Mmc(_) => proto_disks::DiskKind::Mmc as i32,
-                    disks::Disk::Nvme(_) => proto_disks::DiskKind::Nvme as i32,
-                    disks::Disk::Virtual(_) => proto_disks::DiskKind::Virtual as i32,
-                    disks::Disk::Mock(_) => proto_disks::DiskKind::Unknown as i32,
-                },
-                display_size: disks::format_size(device.size()),
-                image_path: None,
-            },
+            BlockDevice::Disk(disk) => {
+                let kind = match **disk {
+                    disks::Disk::Scsi(_) => proto_disks::DiskKind::Scsi,
+                    disks::Disk::Mmc(_) => proto_disks::DiskKind::Mmc,
+                    disks::Disk::Nvme(_) => proto_disks::DiskKind::Nvme,
+                    disks::Disk::Virtual(_) => proto_disks::DiskKind::Virtual,
+                    disks::Disk::Mock(_) => proto_disks::DiskKind::Unknown,
+                };
+
+                proto_disks::Disk {
+                    name: device.name().to_owned(),
+                    sectors: device.sectors(),
+                    device: device.device().to_string_lossy().to_string(),
+                    model: disk.model().map(|m| m.to_owned()),
+                    vendor: disk.vendor().map(|v| v.to_owned()),
+                    partitions: device.partitions().iter().map(Into::into).collect(),
+                    kind: kind as i32,
+                    display_size: disks::format_size(device.size()),
+                    image_path: None,
+                    by_id: Vec::new(),
+                    wwn: None,
+                    serial: None,
+                    by_path: None,
+                    busy: None,
+                    transport: None,
+                    rotational: false,
+                    removable: false,
+                    logical_sector_size: 0,
+                    physical_sector_size: 0,
+                    partition_table: None,
+                    health: proto_disks::DiskHealth::Unknown as i32,
+                    health_detail: None,
+                }
+            }
             BlockDevice::Loopback(loopback) => proto_disks::Disk {
                 name: device.name().to_owned(),
                 sectors: device.sectors(),
@@ -75,6 +95,19 @@ where
                 image_path: loopback.file_path().map(|p| p.to_string_lossy().to_string()),
                 kind: proto_disks::DiskKind::Loopback as i32,
                 display_size: disks::format_size(device.size()),
+                by_id: Vec::new(),
+                wwn: None,
+                serial: None,
+                by_path: None,
+                busy: None,
+                transport: None,
+                rotational: false,
+                removable: false,
+                logical_sector_size: 0,
+                physical_sector_size: 0,
+                partition_table: None,
+                health: proto_disks::DiskHealth::Unknown as i32,
+                health_detail: None,
             },
         }
     }
@@ -18,6 +18,9 @@ pub mod lichen {
     pub mod install {
         tonic::include_proto!("lichen.install");
     }
+    pub mod keyboard {
+        tonic::include_proto!("lichen.keyboard");
+    }
     pub mod locales {
         tonic::include_proto!("lichen.locales");
     }
@@ -38,6 +41,9 @@ pub mod lichen {
     pub mod system {
         tonic::include_proto!("lichen.system");
     }
+    pub mod timezones {
+        tonic::include_proto!("lichen.timezones");
+    }
 }
 
 #[derive(Error, Debug)]
@@ -50,6 +56,80 @@ pub enum Error {
     Uri(#[from] http::Error),
 }
 
+/// Public key algorithms accepted in authorized_keys, by prefix
+const SSH_KEY_TYPES: &[&str] = &["ssh-", "ecdsa-sha2-", "sk-ssh-", "sk-ecdsa-sha2-"];
+
+/// A single authorized_keys line: optional options, a known key type, then
+/// the base64 key itself and any comment. The frontend checks pasted keys
+/// with the same rule the backend enforces.
+pub fn is_valid_ssh_key(line: &str) -> bool {
+    if line.contains(['\n', '\r']) {
+        return false;
+    }
+
+    // Options may quote spaces, as in from="a, b"
+    let mut fields = Vec::new();
+    let mut quoted = false;
+    let mut start = None;
+    for (at, c) in line.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        match (c.is_whitespace() && !quoted, start) {
+            (true, Some(from)) => {
+                fields.push(&line[from..at]);
+                start = None;
+            }
+            (false, None) => start = Some(at),
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        fields.push(&line[from..]);
+    }
+
+    let is_type = |field: &&str| SSH_KEY_TYPES.iter().any(|prefix| field.starts_with(prefix));
+    let is_base64 = |field: &&str| {
+        !field.is_empty()
+            && field
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+    };
+    let key = match fields.first() {
+        Some(first) if is_type(first) => &fields[..],
+        _ => fields.get(1..).unwrap_or_default(),
+    };
+
+    !quoted && key.first().is_some_and(is_type) && key.get(1).is_some_and(is_base64)
+}
+
+/// Usernames the system's own accounts and groups take: root, nobody, and
+/// the groups the backend adds every account or administrator to. The
+/// frontend refuses them as the backend does, before the disk is touched.
+pub const RESERVED_USERNAMES: &[&str] = &[
+    "root", "nobody", "audio", "render", "kvm", "input", "users", "adm", "wheel",
+];
+
+/// Mountpoints a refresh recreates; every other filesystem of the previous
+/// installation, /home included, is kept with its files
+pub const REFRESHED_MOUNTPOINTS: &[&str] = &["/", "/boot", "/efi", "/boot/efi"];
+
+/// An NTP server as ntpd-rs takes it: a hostname, IPv4 or IPv6 address,
+/// optionally with a port. Server names are written verbatim into its
+/// config, so the frontend refuses the same names the backend does.
+pub fn is_valid_ntp_server(server: &str) -> bool {
+    !server.is_empty()
+        && server
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+}
+
+/// Where the backend serves: `/run/lichen.sock`, unless `LICHEN_SOCKET`
+/// names another path, such as for an unprivileged simulated backend
+pub fn socket_path() -> String {
+    std::env::var("LICHEN_SOCKET").unwrap_or_else(|_| "/run/lichen.sock".to_owned())
+}
+
 /// Create a new channel to the Unix domain socket server.
 pub async fn unix_channel(whence: &str) -> Result<Channel, Error> {
     let encoded_uri = Uri::builder()